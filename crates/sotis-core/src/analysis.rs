@@ -0,0 +1,213 @@
+//! Text analysis shared by indexing, query parsing and highlighting.
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+use tantivy::schema::{FieldType, IndexRecordOption, Schema, TextFieldIndexing, TextOptions};
+use tantivy::tokenizer::{
+    AsciiFoldingFilter, LowerCaser, RawTokenizer, RemoveLongFilter, SimpleTokenizer, TextAnalyzer,
+};
+use tantivy::Index;
+
+use crate::config::ContentTokenizer;
+
+mod cjk;
+mod language;
+pub(crate) use cjk::{contains_cjk, is_cjk_single_char};
+pub use cjk::{CjkBigramTokenStream, CjkBigramTokenizer};
+pub use language::{stemming_analyzer, supported_languages, LanguageDetector};
+
+/// Name the folding analyzer is registered under on every index.
+pub const FOLDING_TOKENIZER: &str = "sotis_folding";
+
+/// Name the CJK bigram analyzer is registered under on every index.
+pub const CJK_BIGRAM_TOKENIZER: &str = "sotis_cjk_bigram";
+
+/// Tokens at or above this length are dropped, matching tantivy's default analyzer.
+pub(crate) const MAX_TOKEN_BYTES: usize = 40;
+
+/// Splits on non-alphanumerics, lowercases Unicode and folds diacritics to ASCII,
+/// so "Müller" indexes as "muller" and "ÉTÉ" as "ete".
+pub fn folding_analyzer() -> TextAnalyzer {
+    TextAnalyzer::builder(SimpleTokenizer::default())
+        .filter(RemoveLongFilter::limit(MAX_TOKEN_BYTES))
+        .filter(LowerCaser)
+        .filter(AsciiFoldingFilter)
+        .build()
+}
+
+/// [`folding_analyzer`] with Chinese, Japanese and Korean runs split into bigrams.
+pub fn cjk_bigram_analyzer() -> TextAnalyzer {
+    TextAnalyzer::builder(CjkBigramTokenizer)
+        .filter(RemoveLongFilter::limit(MAX_TOKEN_BYTES))
+        .filter(LowerCaser)
+        .filter(AsciiFoldingFilter)
+        .build()
+}
+
+fn tokenizer_name(tokenizer: ContentTokenizer) -> &'static str {
+    match tokenizer {
+        ContentTokenizer::Standard => FOLDING_TOKENIZER,
+        ContentTokenizer::CjkBigram => CJK_BIGRAM_TOKENIZER,
+    }
+}
+
+/// Like tantivy's `TEXT`, but analyzed with the folding analyzer for `tokenizer`.
+pub(crate) fn folded_text(tokenizer: ContentTokenizer) -> TextOptions {
+    TextOptions::default().set_indexing_options(
+        TextFieldIndexing::default()
+            .set_tokenizer(tokenizer_name(tokenizer))
+            .set_index_option(IndexRecordOption::WithFreqsAndPositions),
+    )
+}
+
+/// Term-only options for per-language stems, which are added pre-tokenized.
+///
+/// Documents without a detected language fall back to the analyzer for `tokenizer`.
+pub(crate) fn stemmed_text(tokenizer: ContentTokenizer) -> TextOptions {
+    TextOptions::default().set_indexing_options(
+        TextFieldIndexing::default()
+            .set_tokenizer(tokenizer_name(tokenizer))
+            .set_index_option(IndexRecordOption::WithFreqs),
+    )
+}
+
+/// Registers the analyzers index fields may name on `index`.
+pub(crate) fn register_tokenizers(index: &Index) {
+    let tokenizers = index.tokenizers();
+    tokenizers.register(FOLDING_TOKENIZER, folding_analyzer());
+    tokenizers.register(CJK_BIGRAM_TOKENIZER, cjk_bigram_analyzer());
+}
+
+/// Content tokenizer an index was created with.
+pub(crate) fn index_tokenizer(schema: &Schema) -> ContentTokenizer {
+    let tokenizer = schema
+        .get_field("content")
+        .ok()
+        .map(|field| schema.get_field_entry(field).field_type())
+        .and_then(|field_type| match field_type {
+            FieldType::Str(options) => options.get_indexing_options(),
+            _ => None,
+        })
+        .map(TextFieldIndexing::tokenizer);
+    if tokenizer == Some(CJK_BIGRAM_TOKENIZER) {
+        ContentTokenizer::CjkBigram
+    } else {
+        ContentTokenizer::Standard
+    }
+}
+
+/// Folds `text` the way indexed terms are folded, without splitting it into tokens.
+pub fn fold(text: &str) -> String {
+    FoldedText::new(text).text
+}
+
+/// Folded copy of a text that maps byte ranges back to the original.
+///
+/// Lets substring matches on folded text be highlighted in the original.
+pub struct FoldedText {
+    text: String,
+    /// Original byte offset of the character each folded byte came from, plus a sentinel
+    /// holding the original length.
+    origins: Vec<usize>,
+}
+
+impl FoldedText {
+    pub fn new(original: &str) -> Self {
+        let mut char_folder = CharFolder::new();
+        let mut text = String::with_capacity(original.len());
+        let mut origins = Vec::with_capacity(original.len() + 1);
+
+        for (offset, ch) in original.char_indices() {
+            let before = text.len();
+            if ch.is_ascii() {
+                text.push(ch.to_ascii_lowercase());
+            } else {
+                text.push_str(char_folder.fold(ch));
+            }
+            origins.resize(origins.len() + (text.len() - before), offset);
+        }
+        origins.push(original.len());
+
+        Self { text, origins }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    /// Maps a non-empty byte range of the folded text to whole characters of the original.
+    pub fn original_range(&self, range: Range<usize>) -> Range<usize> {
+        let start = self.origins[range.start];
+        let last = self.origins[range.end.max(range.start + 1) - 1];
+        // The sentinel exceeds every character offset, so this stays in bounds.
+        let end = self.origins[self.origins.partition_point(|origin| *origin <= last)];
+        start..end
+    }
+}
+
+/// Folds single characters through the same filters as [`folding_analyzer`].
+struct CharFolder {
+    analyzer: TextAnalyzer,
+    cache: HashMap<char, String>,
+}
+
+impl CharFolder {
+    fn new() -> Self {
+        Self {
+            analyzer: TextAnalyzer::builder(RawTokenizer::default())
+                .filter(LowerCaser)
+                .filter(AsciiFoldingFilter)
+                .build(),
+            cache: HashMap::new(),
+        }
+    }
+
+    fn fold(&mut self, ch: char) -> &str {
+        let analyzer = &mut self.analyzer;
+        self.cache.entry(ch).or_insert_with(|| {
+            let mut folded = String::new();
+            let mut buffer = [0; 4];
+            let mut stream = analyzer.token_stream(ch.encode_utf8(&mut buffer));
+            stream.process(&mut |token| folded.push_str(&token.text));
+            folded
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(text: &str) -> Vec<String> {
+        let mut analyzer = folding_analyzer();
+        let mut tokens = Vec::new();
+        let mut stream = analyzer.token_stream(text);
+        stream.process(&mut |token| tokens.push(token.text.clone()));
+        tokens
+    }
+
+    #[test]
+    fn analyzer_lowercases_unicode_and_folds_diacritics() {
+        assert_eq!(
+            tokens("Müller ÉTÉ Straße"),
+            vec!["muller", "ete", "strasse"]
+        );
+        assert_eq!(fold("ÉTÉ à Köln"), "ete a koln");
+    }
+
+    #[test]
+    fn folded_text_maps_matches_back_to_original_bytes() {
+        let original = "Herr MÜLLER und die Straße";
+        let folded = FoldedText::new(original);
+
+        let start = folded.as_str().find("muller").expect("folded match");
+        let range = folded.original_range(start..start + "muller".len());
+        assert_eq!(&original[range], "MÜLLER");
+
+        // Part of an expanded character still covers the whole original character.
+        let start = folded.as_str().find("as").expect("folded match");
+        let range = folded.original_range(start..start + 2);
+        assert_eq!(&original[range], "aß");
+    }
+}
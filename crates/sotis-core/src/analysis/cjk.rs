@@ -0,0 +1,158 @@
+use tantivy::tokenizer::{Token, TokenStream, Tokenizer};
+
+/// Splits like tantivy's `SimpleTokenizer`, except that runs of Chinese, Japanese or
+/// Korean characters become overlapping bigrams, since those scripts do not separate
+/// words with spaces. A run of one such character stays a single token.
+#[derive(Clone, Default)]
+pub struct CjkBigramTokenizer;
+
+/// Token stream produced by [`CjkBigramTokenizer`].
+pub struct CjkBigramTokenStream {
+    tokens: Vec<Token>,
+    cursor: usize,
+}
+
+impl Tokenizer for CjkBigramTokenizer {
+    type TokenStream<'a> = CjkBigramTokenStream;
+
+    fn token_stream<'a>(&'a mut self, text: &'a str) -> CjkBigramTokenStream {
+        CjkBigramTokenStream {
+            tokens: tokenize(text),
+            cursor: 0,
+        }
+    }
+}
+
+impl TokenStream for CjkBigramTokenStream {
+    fn advance(&mut self) -> bool {
+        self.cursor += 1;
+        self.cursor <= self.tokens.len()
+    }
+
+    fn token(&self) -> &Token {
+        &self.tokens[self.cursor - 1]
+    }
+
+    fn token_mut(&mut self) -> &mut Token {
+        &mut self.tokens[self.cursor - 1]
+    }
+}
+
+/// Whether `ch` belongs to a script written without spaces between words.
+pub(crate) fn is_cjk_char(ch: char) -> bool {
+    matches!(
+        ch,
+        '\u{1100}'..='\u{11FF}'     // Hangul Jamo
+            | '\u{3040}'..='\u{30FF}' // Hiragana, Katakana
+            | '\u{3130}'..='\u{318F}' // Hangul Compatibility Jamo
+            | '\u{31F0}'..='\u{31FF}' // Katakana Phonetic Extensions
+            | '\u{3400}'..='\u{4DBF}' // CJK Extension A
+            | '\u{4E00}'..='\u{9FFF}' // CJK Unified Ideographs
+            | '\u{AC00}'..='\u{D7AF}' // Hangul Syllables
+            | '\u{F900}'..='\u{FAFF}' // CJK Compatibility Ideographs
+            | '\u{FF66}'..='\u{FF9F}' // Halfwidth Katakana
+            | '\u{20000}'..='\u{2FA1F}' // Supplementary ideographs
+    )
+}
+
+/// Whether `text` contains any character [`CjkBigramTokenizer`] splits into bigrams.
+pub(crate) fn contains_cjk(text: &str) -> bool {
+    text.chars().any(is_cjk_char)
+}
+
+/// Whether `text` is one character [`CjkBigramTokenizer`] would fold into bigrams, so
+/// the index holds it only inside them unless it stands alone.
+pub(crate) fn is_cjk_single_char(text: &str) -> bool {
+    let mut chars = text.chars();
+    matches!((chars.next(), chars.next()), (Some(ch), None) if is_cjk_char(ch))
+}
+
+fn tokenize(text: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut push = |offset_from: usize, offset_to: usize| {
+        tokens.push(Token {
+            offset_from,
+            offset_to,
+            position: tokens.len(),
+            text: text[offset_from..offset_to].to_string(),
+            position_length: 1,
+        });
+    };
+
+    let mut chars = text.char_indices().peekable();
+    while let Some((start, ch)) = chars.next() {
+        if is_cjk_char(ch) {
+            // Byte offsets of every character in the run, plus its end.
+            let mut bounds = vec![start];
+            while let Some(&(offset, next)) = chars.peek() {
+                if !is_cjk_char(next) {
+                    break;
+                }
+                bounds.push(offset);
+                chars.next();
+            }
+            let end = chars.peek().map_or(text.len(), |(offset, _)| *offset);
+            bounds.push(end);
+
+            if bounds.len() == 2 {
+                push(bounds[0], bounds[1]);
+            } else {
+                for window in bounds.windows(3) {
+                    push(window[0], window[2]);
+                }
+            }
+        } else if ch.is_alphanumeric() {
+            let mut end = start + ch.len_utf8();
+            while let Some(&(offset, next)) = chars.peek() {
+                if !next.is_alphanumeric() || is_cjk_char(next) {
+                    break;
+                }
+                end = offset + next.len_utf8();
+                chars.next();
+            }
+            push(start, end);
+        }
+    }
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(text: &str) -> Vec<(String, usize)> {
+        tokenize(text)
+            .into_iter()
+            .map(|token| (token.text, token.position))
+            .collect()
+    }
+
+    #[test]
+    fn cjk_runs_become_overlapping_bigrams() {
+        assert_eq!(
+            tokens("東京都の会議"),
+            vec![
+                ("東京".to_string(), 0),
+                ("京都".to_string(), 1),
+                ("都の".to_string(), 2),
+                ("の会".to_string(), 3),
+                ("会議".to_string(), 4),
+            ]
+        );
+        assert_eq!(tokens("東"), vec![("東".to_string(), 0)]);
+    }
+
+    #[test]
+    fn latin_words_split_as_before_in_mixed_text() {
+        let texts: Vec<String> = tokens("Meeting会議 notes, 한국어!")
+            .into_iter()
+            .map(|(text, _)| text)
+            .collect();
+        assert_eq!(texts, vec!["Meeting", "会議", "notes", "한국", "국어"]);
+        assert!(contains_cjk("notes 会議"));
+        assert!(!contains_cjk("Müller"));
+        assert!(is_cjk_single_char("議"));
+        assert!(!is_cjk_single_char("会議"));
+        assert!(!is_cjk_single_char("a"));
+    }
+}
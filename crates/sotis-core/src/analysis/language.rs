@@ -0,0 +1,133 @@
+use tantivy::tokenizer::{
+    AsciiFoldingFilter, Language, LowerCaser, RemoveLongFilter, SimpleTokenizer, Stemmer,
+    TextAnalyzer,
+};
+use whatlang::{Detector, Lang};
+
+use crate::analysis::MAX_TOKEN_BYTES;
+
+/// Languages with both detection and a Snowball stemmer, keyed by ISO 639-1 code.
+const SUPPORTED: [(&str, Lang, Language); 18] = [
+    ("ar", Lang::Ara, Language::Arabic),
+    ("da", Lang::Dan, Language::Danish),
+    ("de", Lang::Deu, Language::German),
+    ("el", Lang::Ell, Language::Greek),
+    ("en", Lang::Eng, Language::English),
+    ("es", Lang::Spa, Language::Spanish),
+    ("fi", Lang::Fin, Language::Finnish),
+    ("fr", Lang::Fra, Language::French),
+    ("hu", Lang::Hun, Language::Hungarian),
+    ("it", Lang::Ita, Language::Italian),
+    ("nl", Lang::Nld, Language::Dutch),
+    ("no", Lang::Nob, Language::Norwegian),
+    ("pt", Lang::Por, Language::Portuguese),
+    ("ro", Lang::Ron, Language::Romanian),
+    ("ru", Lang::Rus, Language::Russian),
+    ("sv", Lang::Swe, Language::Swedish),
+    ("ta", Lang::Tam, Language::Tamil),
+    ("tr", Lang::Tur, Language::Turkish),
+];
+
+/// Detection only looks at the start of long documents.
+const DETECTION_SAMPLE_BYTES: usize = 16 * 1024;
+const MIN_CONFIDENCE: f64 = 0.5;
+
+/// ISO 639-1 codes of every language that can be detected and stemmed.
+pub fn supported_languages() -> impl Iterator<Item = &'static str> {
+    SUPPORTED.iter().map(|(code, _, _)| *code)
+}
+
+/// Detects document languages, restricted to a configured set.
+pub struct LanguageDetector {
+    detector: Option<Detector>,
+}
+
+impl LanguageDetector {
+    /// Allows the given ISO 639-1 codes; an empty list allows every supported language.
+    ///
+    /// Unsupported codes are ignored.
+    pub fn new(languages: &[String]) -> Self {
+        let allowed: Vec<Lang> = SUPPORTED
+            .iter()
+            .filter(|(code, _, _)| {
+                languages.is_empty()
+                    || languages
+                        .iter()
+                        .any(|language| language.eq_ignore_ascii_case(code))
+            })
+            .map(|(_, lang, _)| *lang)
+            .collect();
+
+        Self {
+            detector: (!allowed.is_empty()).then(|| Detector::with_allowlist(allowed)),
+        }
+    }
+
+    /// ISO 639-1 code of the language `text` is written in, if detected with confidence.
+    pub fn detect(&self, text: &str) -> Option<&'static str> {
+        let mut sample_end = text.len().min(DETECTION_SAMPLE_BYTES);
+        while !text.is_char_boundary(sample_end) {
+            sample_end -= 1;
+        }
+
+        let info = self.detector.as_ref()?.detect(&text[..sample_end])?;
+        if info.confidence() < MIN_CONFIDENCE {
+            return None;
+        }
+        SUPPORTED
+            .iter()
+            .find(|(_, lang, _)| *lang == info.lang())
+            .map(|(code, _, _)| *code)
+    }
+}
+
+/// [`folding_analyzer`](crate::analysis::folding_analyzer) with a Snowball stemmer for
+/// `code`, applied before diacritics are folded.
+pub fn stemming_analyzer(code: &str) -> Option<TextAnalyzer> {
+    let (_, _, language) = SUPPORTED.iter().find(|(known, _, _)| *known == code)?;
+    Some(
+        TextAnalyzer::builder(SimpleTokenizer::default())
+            .filter(RemoveLongFilter::limit(MAX_TOKEN_BYTES))
+            .filter(LowerCaser)
+            .filter(Stemmer::new(*language))
+            .filter(AsciiFoldingFilter)
+            .build(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stems(code: &str, text: &str) -> Vec<String> {
+        let mut analyzer = stemming_analyzer(code).expect("supported language");
+        let mut stems = Vec::new();
+        let mut stream = analyzer.token_stream(text);
+        stream.process(&mut |token| stems.push(token.text.clone()));
+        stems
+    }
+
+    #[test]
+    fn detects_configured_languages_only() {
+        let german = "Die Kinder spielen jeden Nachmittag im Garten hinter dem alten Haus.";
+        let french = "Les enfants jouent tous les après-midi dans le jardin derrière la maison.";
+
+        let detector = LanguageDetector::new(&[]);
+        assert_eq!(detector.detect(german), Some("de"));
+        assert_eq!(detector.detect(french), Some("fr"));
+
+        let english_only = LanguageDetector::new(&["EN".to_string()]);
+        assert_ne!(english_only.detect(german), Some("de"));
+        assert_eq!(
+            LanguageDetector::new(&["xx".to_string()]).detect(german),
+            None
+        );
+    }
+
+    #[test]
+    fn stemming_analyzer_stems_before_folding() {
+        assert_eq!(stems("en", "Running runs"), vec!["run", "run"]);
+        assert_eq!(stems("de", "Häuser"), stems("de", "Haus"));
+        assert!(stemming_analyzer("xx").is_none());
+    }
+}
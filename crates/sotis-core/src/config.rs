@@ -6,13 +6,50 @@ use serde::{Deserialize, Serialize};
 use crate::error::{Error, Result};
 
 /// A folder to index.
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct FolderEntry {
     pub path: PathBuf,
     #[serde(default = "default_true")]
     pub recursive: bool,
     #[serde(default)]
     pub extensions: Vec<String>,
+    /// Score multiplier for files under this folder; 1.0 is neutral.
+    #[serde(default = "default_priority")]
+    pub priority: f32,
+}
+
+/// Named weights and boosts for ordering search results.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RankingProfile {
+    pub name: String,
+    /// Weight of the normalized content score in combined mode.
+    #[serde(default = "default_content_weight")]
+    pub content_weight: f32,
+    /// Weight of the normalized filename score in combined mode.
+    #[serde(default = "default_filename_weight")]
+    pub filename_weight: f32,
+    /// Extra score fraction for a file modified just now, halving every half-life.
+    #[serde(default)]
+    pub recency_boost: f32,
+    #[serde(default = "default_recency_half_life_days")]
+    pub recency_half_life_days: f32,
+    /// Extra score fraction for files directly inside an indexed folder, shrinking per level.
+    #[serde(default)]
+    pub depth_boost: f32,
+    /// Multiply scores by the matching folder's `priority`.
+    #[serde(default = "default_true")]
+    pub use_folder_priority: bool,
+}
+
+/// How document content is split into indexed terms.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContentTokenizer {
+    /// Splits on non-alphanumerics; unsegmented scripts become one term per run.
+    #[default]
+    Standard,
+    /// Like `Standard`, but Chinese, Japanese and Korean runs become overlapping bigrams.
+    CjkBigram,
 }
 
 /// General configuration.
@@ -24,15 +61,67 @@ pub struct GeneralConfig {
     pub ocr_enabled: bool,
     #[serde(default)]
     pub tessdata_path: Option<String>,
+    /// Keep extracted text in the index so results can carry highlighted snippets.
+    #[serde(default = "default_true")]
+    pub store_content: bool,
+    /// Name of the ranking profile used when a search does not pick one.
+    #[serde(default = "default_ranking_profile")]
+    pub ranking_profile: String,
+    /// ISO 639-1 codes of languages to detect for stemming, e.g. `["de", "fr"]`.
+    /// Empty detects every supported language.
+    #[serde(default)]
+    pub languages: Vec<String>,
+    /// Tokenizer for newly created indexes; changing it requires a rebuild.
+    #[serde(default)]
+    pub content_tokenizer: ContentTokenizer,
+    /// Typos tolerated per fuzzy query term, from 0 (exact) to 2; `cat~2` overrides it.
+    #[serde(default = "default_fuzzy_distance")]
+    pub fuzzy_distance: u8,
+    /// Whether fuzzy query terms also match longer words (`cat` finds `catalog`).
+    #[serde(default = "default_true")]
+    pub fuzzy_prefix: bool,
+    /// Files extracted in parallel while indexing; 0 uses one thread per CPU core.
+    #[serde(default)]
+    pub index_threads: usize,
+    /// Megabytes of extracted text waiting for the index writer, on top of its buffer. A
+    /// single larger document still goes through once nothing else is waiting.
+    #[serde(default = "default_index_memory_mb")]
+    pub index_memory_mb: u64,
 }
 
 /// Top-level application config.
-#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Config {
     #[serde(default)]
     pub general: GeneralConfig,
     #[serde(default)]
     pub folders: Vec<FolderEntry>,
+    #[serde(default = "default_ranking_profiles")]
+    pub ranking_profiles: Vec<RankingProfile>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            general: GeneralConfig::default(),
+            folders: Vec::new(),
+            ranking_profiles: default_ranking_profiles(),
+        }
+    }
+}
+
+impl Default for RankingProfile {
+    fn default() -> Self {
+        Self {
+            name: default_ranking_profile(),
+            content_weight: default_content_weight(),
+            filename_weight: default_filename_weight(),
+            recency_boost: 0.0,
+            recency_half_life_days: default_recency_half_life_days(),
+            depth_boost: 0.0,
+            use_folder_priority: true,
+        }
+    }
 }
 
 impl Default for GeneralConfig {
@@ -41,6 +130,14 @@ impl Default for GeneralConfig {
             max_file_size_mb: default_max_file_size(),
             ocr_enabled: false,
             tessdata_path: None,
+            store_content: true,
+            ranking_profile: default_ranking_profile(),
+            languages: Vec::new(),
+            content_tokenizer: ContentTokenizer::Standard,
+            fuzzy_distance: default_fuzzy_distance(),
+            fuzzy_prefix: true,
+            index_threads: 0,
+            index_memory_mb: default_index_memory_mb(),
         }
     }
 }
@@ -69,6 +166,15 @@ impl Config {
         Ok(default_config)
     }
 
+    /// Returns the named ranking profile, falling back to the built-in default.
+    pub fn ranking_profile(&self, name: &str) -> RankingProfile {
+        self.ranking_profiles
+            .iter()
+            .find(|profile| profile.name == name)
+            .cloned()
+            .unwrap_or_default()
+    }
+
     /// Save the app configuration to the resolved default path.
     pub fn save(&self) -> Result<()> {
         self.save_to_path(&config_path())
@@ -117,6 +223,53 @@ fn default_max_file_size() -> u64 {
     50
 }
 
+fn default_fuzzy_distance() -> u8 {
+    1
+}
+
+fn default_index_memory_mb() -> u64 {
+    256
+}
+
+fn default_priority() -> f32 {
+    1.0
+}
+
+fn default_ranking_profile() -> String {
+    "default".to_string()
+}
+
+fn default_content_weight() -> f32 {
+    0.7
+}
+
+fn default_filename_weight() -> f32 {
+    0.3
+}
+
+fn default_recency_half_life_days() -> f32 {
+    30.0
+}
+
+fn default_ranking_profiles() -> Vec<RankingProfile> {
+    vec![
+        RankingProfile::default(),
+        RankingProfile {
+            name: "recent".to_string(),
+            recency_boost: 1.0,
+            recency_half_life_days: 14.0,
+            depth_boost: 0.2,
+            ..RankingProfile::default()
+        },
+        RankingProfile {
+            name: "filenames".to_string(),
+            content_weight: 0.3,
+            filename_weight: 0.7,
+            ..RankingProfile::default()
+        },
+    ]
+}
+
 fn resolve_config_path_from_values(
     sotis_config: Option<PathBuf>,
     xdg_config_home: Option<PathBuf>,
@@ -170,7 +323,37 @@ mod tests {
         assert_eq!(config.general.max_file_size_mb, 50);
         assert!(!config.general.ocr_enabled);
         assert!(config.general.tessdata_path.is_none());
+        assert!(config.general.store_content);
+        assert_eq!(config.general.ranking_profile, "default");
+        assert!(config.general.languages.is_empty());
+        assert_eq!(config.general.content_tokenizer, ContentTokenizer::Standard);
+        assert_eq!(config.general.fuzzy_distance, 1);
+        assert!(config.general.fuzzy_prefix);
+        assert_eq!(config.general.index_threads, 0);
+        assert_eq!(config.general.index_memory_mb, 256);
         assert!(config.folders.is_empty());
+        assert_eq!(config.ranking_profile("default"), RankingProfile::default());
+    }
+
+    #[test]
+    fn ranking_profiles_fill_defaults_and_fall_back_by_name() {
+        let config: Config = toml::from_str(
+            r#"
+            [[folders]]
+            path = "/tmp/projects"
+
+            [[ranking_profiles]]
+            name = "fresh"
+            recency_boost = 2.0
+            "#,
+        )
+        .expect("parse config");
+
+        assert_eq!(config.folders[0].priority, 1.0);
+        let fresh = config.ranking_profile("fresh");
+        assert_eq!(fresh.recency_boost, 2.0);
+        assert_eq!(fresh.content_weight, 0.7);
+        assert_eq!(config.ranking_profile("missing"), RankingProfile::default());
     }
 
     #[test]
@@ -243,12 +426,22 @@ mod tests {
                 max_file_size_mb: 128,
                 ocr_enabled: true,
                 tessdata_path: Some("/tmp/tessdata".to_string()),
+                store_content: false,
+                ranking_profile: "recent".to_string(),
+                languages: vec!["de".to_string(), "fr".to_string()],
+                content_tokenizer: ContentTokenizer::CjkBigram,
+                fuzzy_distance: 2,
+                fuzzy_prefix: false,
+                index_threads: 4,
+                index_memory_mb: 64,
             },
             folders: vec![FolderEntry {
                 path: PathBuf::from("/tmp/projects"),
                 recursive: false,
                 extensions: vec![".rs".to_string(), ".md".to_string()],
+                priority: 2.5,
             }],
+            ranking_profiles: default_ranking_profiles(),
         };
 
         config
@@ -26,12 +26,24 @@ pub enum Error {
     #[error("search error: {0}")]
     Search(String),
 
+    /// Malformed query text; `position` is the byte offset of the offending token.
+    #[error("query syntax error at position {position}: {message}")]
+    QuerySyntax { position: usize, message: String },
+
     #[error("extraction error for {path}: {message}")]
     Extraction { path: PathBuf, message: String },
 
     #[error("scanner error: {0}")]
     Scanner(String),
 
+    /// A [`crate::search::PageToken`] outlived the index snapshot its first page came from.
+    #[error("the index changed since the first page; search again")]
+    StalePage,
+
+    /// The caller cancelled the operation before it finished.
+    #[error("operation cancelled")]
+    Cancelled,
+
     #[error("watcher error: {0}")]
     Watcher(String),
 
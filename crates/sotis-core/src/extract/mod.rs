@@ -17,6 +17,10 @@ use std::sync::OnceLock;
 use crate::config::{Config, GeneralConfig};
 use crate::error::{Error, Result};
 
+/// Version of the text the extractors produce; bump it when an extractor changes its
+/// output so indexed documents are extracted again.
+pub const EXTRACTOR_VERSION: u32 = 1;
+
 /// Trait for extracting text content from files.
 pub trait TextExtractor {
     /// Returns true if this extractor can handle the given file.
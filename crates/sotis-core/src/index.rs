@@ -2,40 +2,49 @@ use std::collections::HashSet;
 use std::fs;
 use std::io::ErrorKind;
 use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Sender};
+use std::thread;
 use std::time::UNIX_EPOCH;
 
-use tantivy::collector::TopDocs;
+use rayon::prelude::*;
+use tantivy::collector::{DocSetCollector, TopDocs};
 use tantivy::query::{AllQuery, TermQuery};
-use tantivy::schema::{Field, IndexRecordOption, Schema, Value, INDEXED, STORED, STRING, TEXT};
+use tantivy::schema::{IndexRecordOption, Value};
+use tantivy::tokenizer::PreTokenizedString;
 use tantivy::{doc, Index, IndexReader, IndexWriter, TantivyDocument, Term};
 
-use crate::config::{self, GeneralConfig};
+use crate::analysis::{self, LanguageDetector};
+use crate::cancel::CancellationToken;
+use crate::config::{self, ContentTokenizer, GeneralConfig};
 use crate::error::{Error, Result};
 use crate::extract;
 use crate::scanner::ScanResult;
+use crate::schema::{self, Fields, OpenedIndex};
 
-mod ocr_refresh;
-use ocr_refresh::should_force_ocr_sensitive_refresh;
+mod budget;
+mod fingerprint;
+mod progress;
+use budget::TextBudget;
+use fingerprint::{extraction_fingerprint, OcrSource};
+pub use progress::BuildProgress;
+use progress::{Outcome, ProgressReporter};
 
-const PDF_OCR_APPROVALS_FILE: &str = "pdf-ocr-approvals.txt";
+/// Memory the tantivy writer buffers documents in before flushing a segment.
+const WRITER_HEAP_BYTES: usize = 50_000_000;
+
+pub(crate) const PDF_OCR_APPROVALS_FILE: &str = "pdf-ocr-approvals.txt";
 
 /// Stats emitted by [`SearchIndex::build_from_scan`].
 #[derive(Debug, Default)]
 pub struct BuildStats {
     pub added: usize,
     pub skipped: usize,
+    /// Indexed files missing from the scan, such as deleted files or removed folders.
+    pub removed: usize,
     pub errors: Vec<(PathBuf, String)>,
     pub ocr_pending: Vec<PathBuf>,
-}
-
-#[derive(Clone, Copy)]
-struct Fields {
-    path: Field,
-    filename: Field,
-    content: Field,
-    modified: Field,
-    size: Field,
-    ext: Field,
+    /// Whether the build was cancelled; the files handled before that are committed.
+    pub cancelled: bool,
 }
 
 /// Manages the tantivy search index.
@@ -44,7 +53,10 @@ pub struct SearchIndex {
     index: Index,
     reader: IndexReader,
     fields: Fields,
+    /// Content tokenizer recorded in the index schema.
+    tokenizer: ContentTokenizer,
     pdf_ocr_approvals: HashSet<String>,
+    rebuilt: bool,
 }
 
 impl SearchIndex {
@@ -56,19 +68,20 @@ impl SearchIndex {
 
     /// Open or create an index at the given path.
     pub fn open(path: &Path) -> Result<Self> {
-        fs::create_dir_all(path).map_err(|source| {
-            Error::Index(format!(
-                "failed to create index directory {}: {source}",
-                path.display()
-            ))
-        })?;
+        let config = config::Config::load()
+            .map(|loaded| loaded.general)
+            .unwrap_or_default();
+        Self::open_with_config(path, &config)
+    }
 
-        let schema = Self::schema();
-        let directory = tantivy::directory::MmapDirectory::open(path)
-            .map_err(|err| Error::Index(format!("failed to open index directory: {err}")))?;
-        let index = Index::open_or_create(directory, schema)?;
+    /// Open or create an index, creating it with the configured content tokenizer.
+    pub fn open_with_config(path: &Path, config: &GeneralConfig) -> Result<Self> {
+        let OpenedIndex { index, rebuilt } =
+            schema::open_or_create(path, config.content_tokenizer)?;
         let reader = index.reader()?;
-        let fields = Self::fields(index.schema())?;
+        let schema = index.schema();
+        let fields = Fields::new(&schema)?;
+        let tokenizer = analysis::index_tokenizer(&schema);
         let pdf_ocr_approvals = Self::load_pdf_ocr_approvals(path)?;
 
         Ok(Self {
@@ -76,10 +89,18 @@ impl SearchIndex {
             index,
             reader,
             fields,
+            tokenizer,
             pdf_ocr_approvals,
+            rebuilt,
         })
     }
 
+    /// Whether opening replaced an index built with another schema version, leaving it
+    /// empty until the folders are indexed again.
+    pub fn was_rebuilt(&self) -> bool {
+        self.rebuilt
+    }
+
     /// Returns the on-disk index path.
     pub fn index_path(&self) -> &Path {
         &self.index_path
@@ -117,7 +138,7 @@ impl SearchIndex {
 
     /// Add a document to the index by extracting content from the given file path.
     pub fn add_document(&mut self, path: &Path) -> Result<()> {
-        let index_doc = IndexedDoc::from_path(path)?;
+        let index_doc = IndexedDoc::from_path(path, self.tokenizer)?;
         self.add_indexed_doc(index_doc)
     }
 
@@ -129,12 +150,13 @@ impl SearchIndex {
         pdf_ocr_approved: bool,
     ) -> Result<()> {
         let effective_approval = self.resolve_pdf_ocr_approval(path, pdf_ocr_approved);
-        let index_doc = IndexedDoc::from_path_with_config(path, config, effective_approval)?;
+        let index_doc =
+            IndexedDoc::from_path_with_config(path, config, self.tokenizer, effective_approval)?;
         self.add_indexed_doc(index_doc)
     }
 
     fn add_indexed_doc(&mut self, index_doc: IndexedDoc) -> Result<()> {
-        let mut writer: IndexWriter<TantivyDocument> = self.index.writer(50_000_000)?;
+        let mut writer: IndexWriter<TantivyDocument> = self.index.writer(WRITER_HEAP_BYTES)?;
         self.queue_indexed_doc(&mut writer, index_doc)?;
         writer.commit()?;
         self.reader.reload()?;
@@ -143,7 +165,7 @@ impl SearchIndex {
 
     /// Remove a document from the index by full file path.
     pub fn remove_document(&mut self, path: &Path) -> Result<()> {
-        let mut writer: IndexWriter<TantivyDocument> = self.index.writer(50_000_000)?;
+        let mut writer: IndexWriter<TantivyDocument> = self.index.writer(WRITER_HEAP_BYTES)?;
         self.delete_term_for_path(&mut writer, path);
         writer.commit()?;
         self.reader.reload()?;
@@ -166,23 +188,13 @@ impl SearchIndex {
         config: &GeneralConfig,
         pdf_ocr_approved: bool,
     ) -> Result<bool> {
-        let effective_approval = self.resolve_pdf_ocr_approval(path, pdf_ocr_approved);
-        if should_force_ocr_sensitive_refresh(path) {
-            self.remove_document(path)?;
-            let index_doc = IndexedDoc::from_path_with_config(path, config, effective_approval)?;
-            self.add_indexed_doc(index_doc)?;
-            return Ok(true);
-        }
-
-        let fs_modified = modified_secs(path)?;
-
-        if let Some(indexed_modified) = self.indexed_modified(path)? {
-            if indexed_modified >= fs_modified {
-                return Ok(false);
-            }
+        if !self.needs_update(path, config)? {
+            return Ok(false);
         }
 
-        let index_doc = IndexedDoc::from_path_with_config(path, config, effective_approval)?;
+        let effective_approval = self.resolve_pdf_ocr_approval(path, pdf_ocr_approved);
+        let index_doc =
+            IndexedDoc::from_path_with_config(path, config, self.tokenizer, effective_approval)?;
         self.remove_document(path)?;
         self.add_indexed_doc(index_doc)?;
         Ok(true)
@@ -193,40 +205,141 @@ impl SearchIndex {
         let config = config::Config::load()
             .map(|loaded| loaded.general)
             .unwrap_or_default();
-        self.build_from_scan_with_config(scan_result, &config, config.ocr_enabled)
+        self.build_from_scan_with_config(
+            scan_result,
+            &config,
+            config.ocr_enabled,
+            &CancellationToken::new(),
+            None,
+        )
     }
 
     /// Build or incrementally update the index from a scanner result with explicit OCR settings.
+    ///
+    /// Sends [`BuildProgress`] to `progress` as files are checked and extracted. Once
+    /// `cancel` fires, no further files are extracted and the ones already handled are
+    /// committed, with [`BuildStats::cancelled`] set.
     pub fn build_from_scan_with_config(
         &mut self,
         scan_result: &ScanResult,
         config: &GeneralConfig,
         pdf_ocr_approved: bool,
+        cancel: &CancellationToken,
+        progress: Option<&Sender<BuildProgress>>,
     ) -> Result<BuildStats> {
         let mut stats = BuildStats {
             errors: scan_result.errors.clone(),
             ..BuildStats::default()
         };
-        let mut writer: IndexWriter<TantivyDocument> = self.index.writer(50_000_000)?;
+        let mut reporter = ProgressReporter::new(scan_result.files.len(), progress);
+        let mut writer: IndexWriter<TantivyDocument> = self.index.writer(WRITER_HEAP_BYTES)?;
         let mut index_changed = false;
 
+        let mut stale = Vec::new();
         for file in &scan_result.files {
-            match self.update_document_with_writer(file, config, pdf_ocr_approved, &mut writer) {
-                Ok(true) => {
-                    stats.added += 1;
-                    index_changed = true;
+            if cancel.is_cancelled() {
+                stats.cancelled = true;
+                break;
+            }
+            reporter.scanned(file);
+            match self.needs_update(file, config) {
+                Ok(true) => stale.push(file),
+                Ok(false) => {
+                    stats.skipped += 1;
+                    reporter.record(file, Outcome::Skipped);
                 }
-                Ok(false) => stats.skipped += 1,
                 Err(err) => {
-                    if extract::is_pdf_ocr_approval_required_error(&err) {
-                        self.delete_term_for_path(&mut writer, file);
-                        stats.ocr_pending.push(file.clone());
+                    stats.errors.push((file.clone(), err.to_string()));
+                    reporter.record(file, Outcome::Failed);
+                }
+            }
+        }
+        reporter.extraction_started(stale.len());
+
+        // Workers extract in parallel and hand each document to this thread, which alone
+        // feeds the writer. Text waiting for the writer is capped by the memory budget.
+        let workers = rayon::ThreadPoolBuilder::new()
+            .num_threads(config.index_threads)
+            .build()
+            .map_err(|err| Error::Index(format!("failed to start extraction workers: {err}")))?;
+        let budget = TextBudget::new(config.index_memory_mb.saturating_mul(1024 * 1024));
+        let (sender, receiver) = mpsc::sync_channel(workers.current_num_threads());
+        let this = &*self;
+        let (workers, budget, stale) = (&workers, &budget, stale.as_slice());
+        let mut handled = 0;
+        let mut failures = Vec::new();
+        let mut ocr_pending = Vec::new();
+        thread::scope(|scope| {
+            scope.spawn(move || {
+                workers.install(|| {
+                    stale.par_iter().enumerate().for_each_with(
+                        sender,
+                        |sender, (position, file)| {
+                            // Files a worker reaches after cancellation are left out.
+                            if cancel.is_cancelled() {
+                                return;
+                            }
+                            let approval = this.resolve_pdf_ocr_approval(file, pdf_ocr_approved);
+                            let index_doc = IndexedDoc::from_path_with_config(
+                                file,
+                                config,
+                                this.tokenizer,
+                                approval,
+                            );
+                            let bytes = index_doc
+                                .as_ref()
+                                .map_or(0, |index_doc| index_doc.content.len() as u64);
+                            budget.acquire(bytes);
+                            // The receiver outlives every sender, so this cannot fail.
+                            let _ = sender.send((position, index_doc, bytes));
+                        },
+                    );
+                });
+            });
+
+            for (position, index_doc, bytes) in receiver {
+                let file = stale[position];
+                handled += 1;
+                let queued = index_doc.and_then(|index_doc| {
+                    this.delete_term_for_path(&mut writer, file);
+                    this.queue_indexed_doc(&mut writer, index_doc)
+                });
+                budget.release(bytes);
+                match queued {
+                    Ok(()) => {
+                        stats.added += 1;
                         index_changed = true;
-                    } else {
-                        stats.errors.push((file.clone(), err.to_string()));
+                        reporter.record(file, Outcome::Extracted);
+                    }
+                    Err(err) => {
+                        if extract::is_pdf_ocr_approval_required_error(&err) {
+                            this.delete_term_for_path(&mut writer, file);
+                            ocr_pending.push((position, file.clone()));
+                            index_changed = true;
+                            reporter.record(file, Outcome::Skipped);
+                        } else {
+                            failures.push((position, (file.clone(), err.to_string())));
+                            reporter.record(file, Outcome::Failed);
+                        }
                     }
                 }
             }
+        });
+        stats.cancelled |= handled < stale.len();
+        // Documents arrive in completion order; report them in scan order.
+        ocr_pending.sort_unstable_by_key(|(position, _)| *position);
+        stats
+            .ocr_pending
+            .extend(ocr_pending.into_iter().map(|(_, file)| file));
+        failures.sort_unstable_by_key(|(position, _)| *position);
+        stats
+            .errors
+            .extend(failures.into_iter().map(|(_, failure)| failure));
+        reporter.finish();
+
+        if !stats.cancelled {
+            stats.removed = self.remove_unscanned(scan_result, &mut writer)?;
+            index_changed |= stats.removed > 0;
         }
 
         if index_changed {
@@ -237,32 +350,58 @@ impl SearchIndex {
         Ok(stats)
     }
 
-    fn update_document_with_writer(
+    /// Deletes indexed files the scan did not find, returning how many.
+    ///
+    /// Files under a path the scan failed to read are kept, so an unreadable folder
+    /// does not empty its part of the index.
+    fn remove_unscanned(
         &self,
-        path: &Path,
-        config: &GeneralConfig,
-        pdf_ocr_approved: bool,
+        scan_result: &ScanResult,
         writer: &mut IndexWriter<TantivyDocument>,
-    ) -> Result<bool> {
-        let effective_approval = self.resolve_pdf_ocr_approval(path, pdf_ocr_approved);
-        if should_force_ocr_sensitive_refresh(path) {
-            let index_doc = IndexedDoc::from_path_with_config(path, config, effective_approval)?;
-            self.delete_term_for_path(writer, path);
-            self.queue_indexed_doc(writer, index_doc)?;
-            return Ok(true);
+    ) -> Result<usize> {
+        let scanned: HashSet<&Path> = scan_result.files.iter().map(PathBuf::as_path).collect();
+        let mut removed = 0;
+        for path in self.indexed_paths()? {
+            let unreadable = scan_result
+                .errors
+                .iter()
+                .any(|(failed, _)| path.starts_with(failed));
+            if !scanned.contains(path.as_path()) && !unreadable {
+                self.delete_term_for_path(writer, &path);
+                removed += 1;
+            }
         }
+        Ok(removed)
+    }
 
-        let fs_modified = modified_secs(path)?;
-        if let Some(indexed_modified) = self.indexed_modified(path)? {
-            if indexed_modified >= fs_modified {
-                return Ok(false);
+    fn indexed_paths(&self) -> Result<Vec<PathBuf>> {
+        self.reader.reload()?;
+        let searcher = self.reader.searcher();
+        let addresses = searcher.search(&AllQuery, &DocSetCollector)?;
+        let mut paths = Vec::with_capacity(addresses.len());
+        for address in addresses {
+            let document = searcher.doc::<TantivyDocument>(address)?;
+            if let Some(path) = document
+                .get_first(self.fields.path)
+                .and_then(|value| value.as_str())
+            {
+                paths.push(PathBuf::from(path));
             }
         }
+        Ok(paths)
+    }
 
-        let index_doc = IndexedDoc::from_path_with_config(path, config, effective_approval)?;
-        self.delete_term_for_path(writer, path);
-        self.queue_indexed_doc(writer, index_doc)?;
-        Ok(true)
+    /// Whether `path` is missing from the index, its mtime or size differ from the indexed
+    /// document, or its extraction fingerprint is no longer current.
+    fn needs_update(&self, path: &Path, config: &GeneralConfig) -> Result<bool> {
+        let size = fs::metadata(path)?.len();
+        let modified = modified_secs(path)?;
+        let Some(indexed) = self.indexed_state(path)? else {
+            return Ok(true);
+        };
+        Ok(indexed.modified != modified
+            || indexed.size != size
+            || !fingerprint::is_current(&indexed.fingerprint, path, config, self.tokenizer))
     }
 
     /// Returns whether OCR has been approved for this specific PDF path.
@@ -296,46 +435,43 @@ impl SearchIndex {
         writer: &mut IndexWriter<TantivyDocument>,
         index_doc: IndexedDoc,
     ) -> Result<()> {
-        writer.add_document(doc!(
+        let mut document = doc!(
             self.fields.path => index_doc.path,
             self.fields.filename => index_doc.filename,
-            self.fields.content => index_doc.content,
             self.fields.modified => index_doc.modified,
             self.fields.size => index_doc.size,
             self.fields.ext => index_doc.ext,
-        ))?;
-        Ok(())
-    }
-
-    fn schema() -> Schema {
-        let mut schema_builder = Schema::builder();
-        schema_builder.add_text_field("path", STRING | STORED);
-        schema_builder.add_text_field("filename", TEXT | STORED);
-        schema_builder.add_text_field("content", TEXT);
-        schema_builder.add_u64_field("modified", INDEXED | STORED);
-        schema_builder.add_u64_field("size", STORED);
-        schema_builder.add_text_field("ext", STRING | STORED);
-        schema_builder.build()
-    }
-
-    fn fields(schema: Schema) -> Result<Fields> {
-        let get = |name| {
-            schema.get_field(name).map_err(|err| {
-                Error::Index(format!("missing field '{name}' in index schema: {err}"))
-            })
-        };
+            self.fields.extraction => index_doc.fingerprint,
+        );
+        if index_doc.store_content {
+            document.add_text(self.fields.stored_content, &index_doc.content);
+        }
+        match index_doc.language.and_then(analysis::stemming_analyzer) {
+            Some(mut stemmer) => {
+                let mut tokens = Vec::new();
+                stemmer
+                    .token_stream(&index_doc.content)
+                    .process(&mut |token| tokens.push(token.clone()));
+                document.add_pre_tokenized_text(
+                    self.fields.content_stemmed,
+                    PreTokenizedString {
+                        text: String::new(),
+                        tokens,
+                    },
+                );
+            }
+            None => document.add_text(self.fields.content_stemmed, &index_doc.content),
+        }
+        if let Some(language) = index_doc.language {
+            document.add_text(self.fields.lang, language);
+        }
+        document.add_text(self.fields.content, index_doc.content);
 
-        Ok(Fields {
-            path: get("path")?,
-            filename: get("filename")?,
-            content: get("content")?,
-            modified: get("modified")?,
-            size: get("size")?,
-            ext: get("ext")?,
-        })
+        writer.add_document(document)?;
+        Ok(())
     }
 
-    fn indexed_modified(&self, path: &Path) -> Result<Option<u64>> {
+    fn indexed_state(&self, path: &Path) -> Result<Option<IndexedState>> {
         let searcher = self.reader.searcher();
         let path_text = path.to_string_lossy().into_owned();
         let query = TermQuery::new(
@@ -349,17 +485,27 @@ impl SearchIndex {
         };
 
         let document = searcher.doc::<TantivyDocument>(doc_address)?;
-        let modified_value = document
-            .get_first(self.fields.modified)
-            .and_then(|value| value.as_u64())
-            .ok_or_else(|| {
-                Error::Index(format!(
-                    "indexed document at {} is missing 'modified' field",
-                    path.display()
-                ))
-            })?;
+        let stored_u64 = |field, name| {
+            document
+                .get_first(field)
+                .and_then(|value| value.as_u64())
+                .ok_or_else(|| {
+                    Error::Index(format!(
+                        "indexed document at {} is missing '{name}' field",
+                        path.display()
+                    ))
+                })
+        };
 
-        Ok(Some(modified_value))
+        Ok(Some(IndexedState {
+            modified: stored_u64(self.fields.modified, "modified")?,
+            size: stored_u64(self.fields.size, "size")?,
+            fingerprint: document
+                .get_first(self.fields.extraction)
+                .and_then(|value| value.as_str())
+                .unwrap_or_default()
+                .to_string(),
+        }))
     }
 
     fn resolve_pdf_ocr_approval(&self, path: &Path, requested_approval: bool) -> bool {
@@ -420,30 +566,43 @@ impl SearchIndex {
     }
 }
 
+/// What the index recorded about a file when it was last extracted.
+struct IndexedState {
+    modified: u64,
+    size: u64,
+    fingerprint: String,
+}
+
 struct IndexedDoc {
     path: String,
     filename: String,
     content: String,
+    /// Detected language code, used to pick the stemmer.
+    language: Option<&'static str>,
+    store_content: bool,
     modified: u64,
     size: u64,
     ext: String,
+    /// See [`extraction_fingerprint`].
+    fingerprint: String,
 }
 
 impl IndexedDoc {
-    fn from_path(path: &Path) -> Result<Self> {
+    fn from_path(path: &Path, tokenizer: ContentTokenizer) -> Result<Self> {
         let config = config::Config::load()
             .map(|loaded| loaded.general)
             .unwrap_or_default();
-        Self::from_path_with_config(path, &config, config.ocr_enabled)
+        Self::from_path_with_config(path, &config, tokenizer, config.ocr_enabled)
     }
 
     fn from_path_with_config(
         path: &Path,
         config: &GeneralConfig,
+        tokenizer: ContentTokenizer,
         pdf_ocr_approved: bool,
     ) -> Result<Self> {
         let metadata = fs::metadata(path)?;
-        let content = extract::extract_text_with_pdf_ocr_approval(path, config, pdf_ocr_approved)?;
+        let (content, ocr_used) = extract_text(path, config, pdf_ocr_approved)?;
         let filename = path
             .file_name()
             .and_then(|name| name.to_str())
@@ -458,17 +617,44 @@ impl IndexedDoc {
             .map(str::to_ascii_lowercase)
             .unwrap_or_default();
 
+        let language = LanguageDetector::new(&config.languages).detect(&content);
+
         Ok(Self {
             path: path.to_string_lossy().into_owned(),
             filename,
             content,
+            language,
+            store_content: config.store_content,
             modified: modified_secs(path)?,
             size: metadata.len(),
             ext,
+            fingerprint: extraction_fingerprint(path, config, tokenizer, ocr_used),
         })
     }
 }
 
+/// Extracts `path`, also reporting whether OCR produced the text.
+///
+/// An approved PDF is first read without OCR, so its fingerprint records OCR only when
+/// the text layer was unusable; OCR dwarfs the repeated text-layer attempt.
+fn extract_text(
+    path: &Path,
+    config: &GeneralConfig,
+    pdf_ocr_approved: bool,
+) -> Result<(String, bool)> {
+    let extract = |approved| extract::extract_text_with_pdf_ocr_approval(path, config, approved);
+    match fingerprint::ocr_source(path) {
+        Some(OcrSource::Pdf) if pdf_ocr_approved => match extract(false) {
+            Err(err) if extract::is_pdf_ocr_approval_required_error(&err) => {
+                Ok((extract(true)?, true))
+            }
+            text => Ok((text?, false)),
+        },
+        Some(OcrSource::Image) => Ok((extract(pdf_ocr_approved)?, fingerprint::image_ocr(config))),
+        _ => Ok((extract(pdf_ocr_approved)?, false)),
+    }
+}
+
 fn modified_secs(path: &Path) -> Result<u64> {
     let modified = fs::metadata(path)?.modified().map_err(|source| {
         Error::Index(format!(
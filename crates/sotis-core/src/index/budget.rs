@@ -0,0 +1,38 @@
+use std::sync::{Condvar, Mutex, PoisonError};
+
+/// Caps the bytes of extracted text waiting for the index writer.
+pub(super) struct TextBudget {
+    limit: u64,
+    used: Mutex<u64>,
+    freed: Condvar,
+}
+
+impl TextBudget {
+    pub(super) fn new(limit: u64) -> Self {
+        Self {
+            limit,
+            used: Mutex::new(0),
+            freed: Condvar::new(),
+        }
+    }
+
+    /// Blocks until `bytes` more fit under the limit. Text larger than the whole limit
+    /// waits until nothing else is in flight instead of waiting forever.
+    pub(super) fn acquire(&self, bytes: u64) {
+        let mut used = self.used.lock().unwrap_or_else(PoisonError::into_inner);
+        while *used > 0 && used.saturating_add(bytes) > self.limit {
+            used = self
+                .freed
+                .wait(used)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+        *used += bytes;
+    }
+
+    /// Returns `bytes` taken by [`Self::acquire`] once the writer has them.
+    pub(super) fn release(&self, bytes: u64) {
+        let mut used = self.used.lock().unwrap_or_else(PoisonError::into_inner);
+        *used = used.saturating_sub(bytes);
+        self.freed.notify_all();
+    }
+}
@@ -0,0 +1,144 @@
+use std::path::Path;
+
+use crate::config::{ContentTokenizer, GeneralConfig};
+use crate::extract::EXTRACTOR_VERSION;
+
+/// Kind of file whose text OCR may produce.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(super) enum OcrSource {
+    /// OCR runs only when the text layer is unusable and the file is approved.
+    Pdf,
+    /// OCR runs whenever it is enabled.
+    Image,
+}
+
+/// Describes how a document's indexed text was produced: the extractor, the stemming
+/// languages, the index's content tokenizer and whether content is stored.
+///
+/// PDFs and images also record whether OCR produced their text, plus the tessdata path
+/// when it did, so other documents ignore OCR settings.
+pub(super) fn extraction_fingerprint(
+    path: &Path,
+    config: &GeneralConfig,
+    tokenizer: ContentTokenizer,
+    ocr_used: bool,
+) -> String {
+    let mut fingerprint = format!(
+        "extractor={EXTRACTOR_VERSION};languages={};tokenizer={tokenizer:?};stored={}",
+        config.languages.join(","),
+        config.store_content
+    );
+    if ocr_source(path).is_some() {
+        fingerprint.push_str(&format!(";ocr={ocr_used}"));
+        if ocr_used {
+            fingerprint.push_str(&format!(
+                ";tessdata={}",
+                config.tessdata_path.as_deref().unwrap_or_default()
+            ));
+        }
+    }
+    fingerprint
+}
+
+/// Whether a document indexed with the `stored` fingerprint would be extracted the same
+/// way under `config` today.
+///
+/// Whether a PDF needs OCR depends on its text layer, which only extraction can tell, so
+/// either outcome stays current and the caller's approval does not matter. Images follow
+/// [`image_ocr`].
+pub(super) fn is_current(
+    stored: &str,
+    path: &Path,
+    config: &GeneralConfig,
+    tokenizer: ContentTokenizer,
+) -> bool {
+    let produced_by =
+        |ocr_used| stored == extraction_fingerprint(path, config, tokenizer, ocr_used);
+    match ocr_source(path) {
+        Some(OcrSource::Pdf) => produced_by(false) || produced_by(true),
+        Some(OcrSource::Image) => produced_by(image_ocr(config)),
+        None => produced_by(false),
+    }
+}
+
+/// Whether images are OCRed under `config`.
+pub(super) fn image_ocr(config: &GeneralConfig) -> bool {
+    config.ocr_enabled && cfg!(feature = "ocr")
+}
+
+pub(super) fn ocr_source(path: &Path) -> Option<OcrSource> {
+    let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+    match ext.as_str() {
+        "pdf" => Some(OcrSource::Pdf),
+        "png" | "jpg" | "jpeg" | "tiff" | "tif" | "bmp" => Some(OcrSource::Image),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    const STANDARD: ContentTokenizer = ContentTokenizer::Standard;
+
+    #[test]
+    fn ocr_settings_only_change_fingerprints_of_ocred_files() {
+        let config = GeneralConfig::default();
+        let ocr = GeneralConfig {
+            ocr_enabled: true,
+            tessdata_path: Some("/usr/share/tessdata".to_string()),
+            ..GeneralConfig::default()
+        };
+        let text = Path::new("notes.txt");
+        let scan = Path::new("scan.PDF");
+
+        let indexed = extraction_fingerprint(text, &config, STANDARD, false);
+        assert!(is_current(&indexed, text, &ocr, STANDARD));
+
+        // A PDF read from its text layer stays current whatever the OCR settings.
+        let text_layer = extraction_fingerprint(scan, &config, STANDARD, false);
+        assert!(is_current(&text_layer, scan, &ocr, STANDARD));
+        // An OCRed PDF is redone only when the tessdata it was read with changes.
+        let ocred = extraction_fingerprint(scan, &ocr, STANDARD, true);
+        assert!(is_current(&ocred, scan, &ocr, STANDARD));
+        let other_tessdata = GeneralConfig {
+            tessdata_path: Some("/opt/tessdata".to_string()),
+            ..ocr.clone()
+        };
+        assert!(!is_current(&ocred, scan, &other_tessdata, STANDARD));
+
+        let photo = Path::new("photo.jpg");
+        let unread = extraction_fingerprint(photo, &config, STANDARD, false);
+        assert!(is_current(&unread, photo, &config, STANDARD));
+        assert_eq!(
+            is_current(&unread, photo, &ocr, STANDARD),
+            !cfg!(feature = "ocr")
+        );
+    }
+
+    #[test]
+    fn languages_tokenizer_and_stored_content_change_fingerprints() {
+        let config = GeneralConfig::default();
+        let text = Path::new("notes.txt");
+        let indexed = extraction_fingerprint(text, &config, STANDARD, false);
+
+        let german = GeneralConfig {
+            languages: vec!["de".to_string()],
+            ..GeneralConfig::default()
+        };
+        let unstored = GeneralConfig {
+            store_content: !config.store_content,
+            ..GeneralConfig::default()
+        };
+        assert!(!is_current(&indexed, text, &german, STANDARD));
+        assert!(!is_current(&indexed, text, &unstored, STANDARD));
+        assert!(!is_current(
+            &indexed,
+            text,
+            &config,
+            ContentTokenizer::CjkBigram
+        ));
+    }
+}
@@ -0,0 +1,117 @@
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
+use std::time::{Duration, Instant};
+
+/// Minimum time between progress reports, so large scans do not flood the receiver.
+const REPORT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Snapshot of a running [`super::SearchIndex::build_from_scan_with_config`].
+#[derive(Debug, Clone, Default)]
+pub struct BuildProgress {
+    /// Files in the scan.
+    pub total: usize,
+    /// Files checked against the index for changes.
+    pub scanned: usize,
+    /// Files extracted and queued for the index.
+    pub extracted: usize,
+    /// Files already current in the index, or awaiting OCR approval.
+    pub skipped: usize,
+    /// Files that could not be checked or extracted.
+    pub failed: usize,
+    /// File most recently checked or extracted.
+    pub current: Option<PathBuf>,
+    /// Estimated time until every changed file is extracted, once one has been.
+    pub eta: Option<Duration>,
+}
+
+impl BuildProgress {
+    /// Share of the scan's files that are done, from 0 to 1.
+    pub fn fraction(&self) -> f32 {
+        if self.total == 0 {
+            return 1.0;
+        }
+        (self.extracted + self.skipped + self.failed) as f32 / self.total as f32
+    }
+}
+
+/// How a file ended up in a build.
+pub(super) enum Outcome {
+    Extracted,
+    Skipped,
+    Failed,
+}
+
+/// Counts a build's files and sends [`BuildProgress`] to an optional sink.
+pub(super) struct ProgressReporter<'a> {
+    sink: Option<&'a Sender<BuildProgress>>,
+    progress: BuildProgress,
+    last_sent: Option<Instant>,
+    extraction_started: Option<Instant>,
+    to_extract: usize,
+    extraction_done: usize,
+}
+
+impl<'a> ProgressReporter<'a> {
+    pub(super) fn new(total: usize, sink: Option<&'a Sender<BuildProgress>>) -> Self {
+        Self {
+            sink,
+            progress: BuildProgress {
+                total,
+                ..BuildProgress::default()
+            },
+            last_sent: None,
+            extraction_started: None,
+            to_extract: 0,
+            extraction_done: 0,
+        }
+    }
+
+    pub(super) fn scanned(&mut self, path: &Path) {
+        self.progress.scanned += 1;
+        self.progress.current = Some(path.to_path_buf());
+        self.report(false);
+    }
+
+    /// Marks the end of the change checks, with `files` left to extract.
+    pub(super) fn extraction_started(&mut self, files: usize) {
+        self.extraction_started = Some(Instant::now());
+        self.to_extract = files;
+        self.report(true);
+    }
+
+    pub(super) fn record(&mut self, path: &Path, outcome: Outcome) {
+        match outcome {
+            Outcome::Extracted => self.progress.extracted += 1,
+            Outcome::Skipped => self.progress.skipped += 1,
+            Outcome::Failed => self.progress.failed += 1,
+        }
+        self.progress.current = Some(path.to_path_buf());
+        if let Some(started) = self.extraction_started {
+            self.extraction_done += 1;
+            let remaining = self.to_extract.saturating_sub(self.extraction_done);
+            self.progress.eta =
+                Some(started.elapsed() / self.extraction_done as u32 * remaining as u32);
+        }
+        self.report(false);
+    }
+
+    pub(super) fn finish(&mut self) {
+        self.progress.current = None;
+        self.progress.eta = Some(Duration::ZERO);
+        self.report(true);
+    }
+
+    fn report(&mut self, force: bool) {
+        let Some(sink) = self.sink else {
+            return;
+        };
+        let due = self
+            .last_sent
+            .is_none_or(|sent| sent.elapsed() >= REPORT_INTERVAL);
+        if force || due {
+            self.last_sent = Some(Instant::now());
+            // A receiver that went away only stops caring about progress.
+            let _ = sink.send(self.progress.clone());
+        }
+    }
+}
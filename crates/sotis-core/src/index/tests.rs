@@ -2,6 +2,7 @@ use std::fs;
 use std::path::Path;
 use std::path::PathBuf;
 use std::process;
+use std::sync::mpsc;
 use std::thread;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
@@ -150,6 +151,243 @@ fn build_from_scan_counts_added_skipped_and_errors() {
     cleanup_temp_dir(&base);
 }
 
+#[test]
+fn parallel_build_indexes_every_file_with_errors_in_scan_order() {
+    let base = unique_temp_dir();
+    let index_dir = base.join("index");
+    fs::create_dir_all(&base).expect("create temp dir");
+    let mut files = Vec::new();
+    for number in 0..12 {
+        let file = base.join(format!("batch-{number}.txt"));
+        fs::write(&file, format!("batch file {number}")).expect("write source file");
+        files.push(file);
+    }
+    files.insert(4, base.join("missing-a.txt"));
+    files.push(base.join("missing-b.txt"));
+
+    // A zero budget lets one extracted file at a time wait for the writer.
+    let config = GeneralConfig {
+        index_threads: 2,
+        index_memory_mb: 0,
+        ..GeneralConfig::default()
+    };
+    let mut index = SearchIndex::open_with_config(&index_dir, &config).expect("open index");
+    let scan = ScanResult {
+        files: files.clone(),
+        errors: Vec::new(),
+    };
+    let stats = index
+        .build_from_scan_with_config(&scan, &config, false, &CancellationToken::new(), None)
+        .expect("build from scan");
+
+    assert_eq!(stats.added, 12);
+    assert_eq!(index.doc_count(), 12);
+    let failed: Vec<&PathBuf> = stats.errors.iter().map(|(path, _)| path).collect();
+    assert_eq!(
+        failed,
+        vec![&base.join("missing-a.txt"), &base.join("missing-b.txt")]
+    );
+
+    cleanup_temp_dir(&base);
+}
+
+#[test]
+fn text_budget_holds_back_text_until_the_writer_frees_room() {
+    let budget = TextBudget::new(10);
+    budget.acquire(6);
+
+    let (sender, receiver) = mpsc::channel();
+    thread::scope(|scope| {
+        scope.spawn(|| {
+            budget.acquire(6);
+            sender.send(()).expect("signal acquired");
+        });
+        assert!(receiver.recv_timeout(Duration::from_millis(50)).is_err());
+        budget.release(6);
+        receiver
+            .recv_timeout(Duration::from_secs(5))
+            .expect("acquire after release");
+    });
+
+    // Text larger than the whole budget still goes through once nothing else waits.
+    budget.release(6);
+    budget.acquire(100);
+}
+
+#[test]
+fn build_reports_progress_and_stops_when_cancelled() {
+    let base = unique_temp_dir();
+    let index_dir = base.join("index");
+    fs::create_dir_all(&base).expect("create temp dir");
+    let first = base.join("first.txt");
+    let second = base.join("second.txt");
+    fs::write(&first, "first file").expect("write first file");
+    fs::write(&second, "second file").expect("write second file");
+    let config = GeneralConfig::default();
+    let mut index = SearchIndex::open_with_config(&index_dir, &config).expect("open index");
+
+    let cancel = CancellationToken::new();
+    cancel.cancel();
+    let scan = ScanResult {
+        files: vec![first.clone(), second.clone()],
+        errors: Vec::new(),
+    };
+    let stats = index
+        .build_from_scan_with_config(&scan, &config, false, &cancel, None)
+        .expect("cancelled build");
+    assert!(stats.cancelled);
+    assert_eq!(stats.added, 0);
+    assert_eq!(index.doc_count(), 0);
+
+    index.add_document(&first).expect("index first file");
+    let scan = ScanResult {
+        files: vec![first, second, base.join("missing.txt")],
+        errors: Vec::new(),
+    };
+    let (tx, rx) = mpsc::channel();
+    let stats = index
+        .build_from_scan_with_config(&scan, &config, false, &CancellationToken::new(), Some(&tx))
+        .expect("build with progress");
+    assert!(!stats.cancelled);
+
+    let last = rx.try_iter().last().expect("progress reported");
+    assert_eq!(last.total, 3);
+    assert_eq!(last.scanned, 3);
+    assert_eq!((last.extracted, last.skipped, last.failed), (1, 1, 1));
+    assert_eq!(last.fraction(), 1.0);
+    assert_eq!(last.eta, Some(Duration::ZERO));
+
+    cleanup_temp_dir(&base);
+}
+
+#[test]
+fn build_removes_indexed_files_missing_from_scan() {
+    let base = unique_temp_dir();
+    let index_dir = base.join("index");
+    let unreadable = base.join("unreadable");
+    fs::create_dir_all(&unreadable).expect("create temp dirs");
+    let kept = base.join("kept.txt");
+    let deleted = base.join("deleted.txt");
+    let hidden = unreadable.join("hidden.txt");
+    for file in [&kept, &deleted, &hidden] {
+        fs::write(file, "prune me maybe").expect("write source file");
+    }
+
+    let mut index = SearchIndex::open(&index_dir).expect("open index");
+    let first = index
+        .build_from_scan(&ScanResult {
+            files: vec![kept.clone(), deleted.clone(), hidden.clone()],
+            errors: Vec::new(),
+        })
+        .expect("first build");
+    assert_eq!((first.added, first.removed), (3, 0));
+
+    fs::remove_file(&deleted).expect("delete source file");
+    let second = index
+        .build_from_scan(&ScanResult {
+            files: vec![kept],
+            errors: vec![(unreadable, "permission denied".to_string())],
+        })
+        .expect("second build");
+
+    assert_eq!(second.removed, 1);
+    assert_eq!(index.doc_count(), 2);
+    let remaining = index.indexed_paths().expect("list indexed paths");
+    assert!(!remaining.contains(&deleted));
+    assert!(remaining.contains(&hidden));
+
+    cleanup_temp_dir(&base);
+}
+
+#[test]
+fn build_refreshes_files_only_when_size_or_extraction_fingerprint_change() {
+    let base = unique_temp_dir();
+    let index_dir = base.join("index");
+    fs::create_dir_all(&base).expect("create temp dir");
+    let file = base.join("notes.txt");
+    fs::write(&file, "stable notes").expect("write source file");
+    let config = GeneralConfig::default();
+    let mut index = SearchIndex::open_with_config(&index_dir, &config).expect("open index");
+    let scan = ScanResult {
+        files: vec![file.clone()],
+        errors: Vec::new(),
+    };
+    let mut build = |config: &GeneralConfig| {
+        index
+            .build_from_scan_with_config(&scan, config, false, &CancellationToken::new(), None)
+            .expect("build from scan")
+    };
+
+    assert_eq!(build(&config).added, 1);
+    assert_eq!(build(&config).skipped, 1);
+
+    let german = GeneralConfig {
+        languages: vec!["de".to_string()],
+        ..GeneralConfig::default()
+    };
+    assert_eq!(build(&german).added, 1);
+    assert_eq!(build(&german).skipped, 1);
+
+    // Same second, different size: the mtime alone would miss this edit.
+    let modified = fs::metadata(&file)
+        .and_then(|metadata| metadata.modified())
+        .expect("read mtime");
+    fs::write(&file, "stable notes, edited").expect("rewrite source file");
+    fs::File::options()
+        .write(true)
+        .open(&file)
+        .and_then(|handle| handle.set_modified(modified))
+        .expect("restore mtime");
+    assert_eq!(build(&german).added, 1);
+
+    cleanup_temp_dir(&base);
+}
+
+#[test]
+fn entry_points_share_fingerprints_whatever_ocr_approval_they_pass() {
+    let base = unique_temp_dir();
+    let index_dir = base.join("index");
+    fs::create_dir_all(&base).expect("create temp dir");
+    let file = base.join("report.pdf");
+    write_text_pdf(&file, "quarterly report");
+    let config = GeneralConfig {
+        ocr_enabled: true,
+        ..GeneralConfig::default()
+    };
+    let mut index = SearchIndex::open_with_config(&index_dir, &config).expect("open index");
+    let scan = ScanResult {
+        files: vec![file.clone()],
+        errors: Vec::new(),
+    };
+    let mut build = |config: &GeneralConfig, approved: bool| {
+        index
+            .build_from_scan_with_config(&scan, config, approved, &CancellationToken::new(), None)
+            .expect("build from scan")
+    };
+
+    // A scan build approves OCR; a GUI rebuild does not. The PDF has a text layer
+    // either way, so neither should re-extract what the other indexed.
+    assert_eq!(build(&config, true).added, 1);
+    assert_eq!(build(&config, false).skipped, 1);
+    assert_eq!(build(&config, true).skipped, 1);
+
+    let unstored = GeneralConfig {
+        store_content: false,
+        ..config.clone()
+    };
+    assert_eq!(build(&unstored, false).added, 1);
+
+    // The watcher passes no approval either.
+    assert!(!index
+        .update_document_with_config(&file, &unstored, false)
+        .expect("update document"));
+    assert!(index
+        .update_document_with_config(&file, &config, true)
+        .expect("update document"));
+
+    cleanup_temp_dir(&base);
+}
+
 #[test]
 fn indexed_extensions_returns_unique_non_empty_extensions() {
     let base = unique_temp_dir();
@@ -248,6 +486,42 @@ fn pdf_ocr_approval_persists_across_reopen() {
     cleanup_temp_dir(&base);
 }
 
+/// Writes a one-page PDF whose text layer reads `text`.
+fn write_text_pdf(path: &Path, text: &str) {
+    let stream = format!("BT /F1 12 Tf 72 720 Td ({text}) Tj ET");
+    let objects = [
+        "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+        "<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_string(),
+        "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 612 792] \
+         /Resources << /Font << /F1 5 0 R >> >> /Contents 4 0 R >>"
+            .to_string(),
+        format!(
+            "<< /Length {} >>\nstream\n{stream}\nendstream",
+            stream.len()
+        ),
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica >>".to_string(),
+    ];
+    let mut pdf = String::from("%PDF-1.4\n");
+    let mut offsets = Vec::new();
+    for (number, object) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        pdf.push_str(&format!("{} 0 obj\n{object}\nendobj\n", number + 1));
+    }
+    let xref = pdf.len();
+    pdf.push_str(&format!(
+        "xref\n0 {}\n0000000000 65535 f \n",
+        objects.len() + 1
+    ));
+    for offset in offsets {
+        pdf.push_str(&format!("{offset:010} 00000 n \n"));
+    }
+    pdf.push_str(&format!(
+        "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{xref}\n%%EOF\n",
+        objects.len() + 1
+    ));
+    fs::write(path, pdf).expect("write pdf");
+}
+
 fn unique_temp_dir() -> PathBuf {
     let nanos = SystemTime::now()
         .duration_since(UNIX_EPOCH)
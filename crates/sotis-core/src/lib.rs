@@ -1,7 +1,10 @@
+pub mod analysis;
+pub mod cancel;
 pub mod config;
 pub mod error;
 pub mod extract;
 pub mod index;
 pub mod scanner;
+pub mod schema;
 pub mod search;
 pub mod watcher;
@@ -109,6 +109,7 @@ mod tests {
             path: base.clone(),
             recursive: false,
             extensions: vec![],
+            priority: 1.0,
         }];
         let result = scan(&folders);
 
@@ -133,6 +134,7 @@ mod tests {
             path: base.clone(),
             recursive: true,
             extensions: vec![".rs".to_string(), "md".to_string()],
+            priority: 1.0,
         }];
         let result = scan(&folders);
 
@@ -165,6 +167,7 @@ mod tests {
             path: base.clone(),
             recursive: true,
             extensions: vec![],
+            priority: 1.0,
         }];
         let result = scan(&folders);
 
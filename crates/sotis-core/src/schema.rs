@@ -0,0 +1,242 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+use tantivy::directory::MmapDirectory;
+use tantivy::schema::{Field, Schema, FAST, INDEXED, STORED, STRING};
+use tantivy::{Index, IndexSettings};
+
+use crate::analysis;
+use crate::config::ContentTokenizer;
+use crate::error::{Error, Result};
+use crate::index::PDF_OCR_APPROVALS_FILE;
+
+/// Version of the index layout [`build`] describes.
+///
+/// Bump it whenever a field or its options change, so indexes made by older builds are
+/// rebuilt on open instead of failing on missing or mismatched fields.
+pub const SCHEMA_VERSION: u32 = 3;
+
+/// File in the index directory recording the [`SCHEMA_VERSION`] the index was built with.
+pub const SCHEMA_VERSION_FILE: &str = "schema-version";
+
+/// Files kept beside the index that a rebuild leaves in place.
+const PRESERVED_FILES: &[&str] = &[PDF_OCR_APPROVALS_FILE];
+
+/// The index schema, with `tokenizer` analyzing the content fields.
+pub(crate) fn build(tokenizer: ContentTokenizer) -> Schema {
+    let mut schema_builder = Schema::builder();
+    // Path, extension, size and modified are fast so the filename catalog reads them
+    // without decompressing stored documents.
+    schema_builder.add_text_field("path", STRING | STORED | FAST);
+    schema_builder.add_text_field(
+        "filename",
+        analysis::folded_text(ContentTokenizer::Standard).set_stored(),
+    );
+    // Positions are recorded, which phrase and NEAR queries depend on.
+    schema_builder.add_text_field("content", analysis::folded_text(tokenizer));
+    schema_builder.add_text_field("stored_content", STORED);
+    schema_builder.add_text_field("content_stemmed", analysis::stemmed_text(tokenizer));
+    schema_builder.add_u64_field("modified", INDEXED | STORED | FAST);
+    schema_builder.add_u64_field("size", INDEXED | STORED | FAST);
+    schema_builder.add_text_field("ext", STRING | STORED | FAST);
+    schema_builder.add_text_field("lang", STRING | STORED);
+    schema_builder.add_text_field("extraction", STRING | STORED);
+    schema_builder.build()
+}
+
+#[derive(Clone, Copy)]
+pub(crate) struct Fields {
+    pub(crate) path: Field,
+    pub(crate) filename: Field,
+    pub(crate) content: Field,
+    pub(crate) stored_content: Field,
+    /// Content stemmed in the document's detected language.
+    pub(crate) content_stemmed: Field,
+    pub(crate) modified: Field,
+    pub(crate) size: Field,
+    pub(crate) ext: Field,
+    /// ISO 639-1 code of the detected language; absent when none was detected.
+    pub(crate) lang: Field,
+    /// Fingerprint of the extraction settings that produced the indexed text.
+    pub(crate) extraction: Field,
+}
+
+impl Fields {
+    pub(crate) fn new(schema: &Schema) -> Result<Self> {
+        let get = |name| {
+            schema.get_field(name).map_err(|err| {
+                Error::Index(format!("missing field '{name}' in index schema: {err}"))
+            })
+        };
+
+        Ok(Self {
+            path: get("path")?,
+            filename: get("filename")?,
+            content: get("content")?,
+            stored_content: get("stored_content")?,
+            content_stemmed: get("content_stemmed")?,
+            modified: get("modified")?,
+            size: get("size")?,
+            ext: get("ext")?,
+            lang: get("lang")?,
+            extraction: get("extraction")?,
+        })
+    }
+}
+
+/// An index opened by [`open_or_create`].
+pub(crate) struct OpenedIndex {
+    pub(crate) index: Index,
+    /// Whether an incompatible index was replaced by an empty one.
+    pub(crate) rebuilt: bool,
+}
+
+/// Opens the index at `path`, or creates it with `tokenizer`.
+///
+/// An existing index keeps the content tokenizer it was created with, so changing the
+/// configured tokenizer only takes effect once the index is rebuilt. An index recorded
+/// with another schema version, or whose fields differ from [`build`], is replaced by
+/// an empty one. Indexes from before versioning are kept when their fields match.
+pub(crate) fn open_or_create(path: &Path, tokenizer: ContentTokenizer) -> Result<OpenedIndex> {
+    fs::create_dir_all(path).map_err(|source| {
+        Error::Index(format!(
+            "failed to create index directory {}: {source}",
+            path.display()
+        ))
+    })?;
+
+    let version = stored_version(path)?;
+    if version.is_none_or(|version| version == SCHEMA_VERSION) {
+        if let Some(index) = open_compatible(path)? {
+            if version.is_none() {
+                write_version(path)?;
+            }
+            return Ok(OpenedIndex {
+                index,
+                rebuilt: false,
+            });
+        }
+    }
+
+    let rebuilt = index_exists(&open_directory(path)?)?;
+    if rebuilt {
+        remove_index_files(path)?;
+    }
+    let index = Index::create(
+        open_directory(path)?,
+        build(tokenizer),
+        IndexSettings::default(),
+    )?;
+    analysis::register_tokenizers(&index);
+    write_version(path)?;
+    Ok(OpenedIndex { index, rebuilt })
+}
+
+/// The existing index at `path` if its fields match [`build`]; `None` if there is no
+/// index or it needs rebuilding.
+fn open_compatible(path: &Path) -> Result<Option<Index>> {
+    let directory = open_directory(path)?;
+    if !index_exists(&directory)? {
+        return Ok(None);
+    }
+    let index = Index::open(directory)?;
+    let schema = index.schema();
+    if schema != build(analysis::index_tokenizer(&schema)) {
+        return Ok(None);
+    }
+    analysis::register_tokenizers(&index);
+    Ok(Some(index))
+}
+
+fn open_directory(path: &Path) -> Result<MmapDirectory> {
+    MmapDirectory::open(path)
+        .map_err(|err| Error::Index(format!("failed to open index directory: {err}")))
+}
+
+fn index_exists(directory: &MmapDirectory) -> Result<bool> {
+    Index::exists(directory)
+        .map_err(|err| Error::Index(format!("failed to check for an index: {err}")))
+}
+
+fn stored_version(path: &Path) -> Result<Option<u32>> {
+    let version_path = path.join(SCHEMA_VERSION_FILE);
+    match fs::read_to_string(&version_path) {
+        // An unreadable version is treated as a mismatch, which rebuilds the index.
+        Ok(contents) => Ok(Some(contents.trim().parse().unwrap_or(0))),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(Error::Index(format!(
+            "failed to read {}: {err}",
+            version_path.display()
+        ))),
+    }
+}
+
+fn write_version(path: &Path) -> Result<()> {
+    let version_path = path.join(SCHEMA_VERSION_FILE);
+    fs::write(&version_path, format!("{SCHEMA_VERSION}\n"))
+        .map_err(|err| Error::Index(format!("failed to write {}: {err}", version_path.display())))
+}
+
+/// Deletes everything in the index directory except [`PRESERVED_FILES`].
+fn remove_index_files(path: &Path) -> Result<()> {
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        if PRESERVED_FILES
+            .iter()
+            .any(|preserved| entry.file_name() == *preserved)
+        {
+            continue;
+        }
+        if entry.file_type()?.is_dir() {
+            fs::remove_dir_all(entry.path())?;
+        } else {
+            fs::remove_file(entry.path())?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    use super::*;
+
+    #[test]
+    fn mismatched_versions_rebuild_and_legacy_indexes_are_stamped() {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("time should be after unix epoch")
+            .as_nanos();
+        let path =
+            std::env::temp_dir().join(format!("sotis-schema-tests-{}-{}", process::id(), nanos));
+
+        let created = open_or_create(&path, ContentTokenizer::Standard).expect("create index");
+        assert!(!created.rebuilt);
+        drop(created);
+
+        // An index from before versioning has no version file but current fields.
+        fs::remove_file(path.join(SCHEMA_VERSION_FILE)).expect("remove version file");
+        let legacy = open_or_create(&path, ContentTokenizer::Standard).expect("open index");
+        assert!(!legacy.rebuilt);
+        assert_eq!(
+            stored_version(&path).expect("read version"),
+            Some(SCHEMA_VERSION)
+        );
+        drop(legacy);
+
+        fs::write(path.join(PDF_OCR_APPROVALS_FILE), "/tmp/scan.pdf\n").expect("write approvals");
+        fs::write(path.join(SCHEMA_VERSION_FILE), "0\n").expect("write old version");
+        let rebuilt = open_or_create(&path, ContentTokenizer::Standard).expect("rebuild index");
+        assert!(rebuilt.rebuilt);
+        assert_eq!(
+            stored_version(&path).expect("read version"),
+            Some(SCHEMA_VERSION)
+        );
+        assert!(path.join(PDF_OCR_APPROVALS_FILE).exists());
+
+        let _ = fs::remove_dir_all(&path);
+    }
+}
@@ -1,18 +1,57 @@
 use std::cmp::Ordering;
-use std::collections::HashMap;
-use std::fs;
+use std::collections::{BTreeSet, HashMap};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::ops::Range;
 use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
 
-use nucleo_matcher::pattern::{CaseMatching, Normalization, Pattern};
-use nucleo_matcher::{Config, Matcher, Utf32Str};
+use nucleo_matcher::{Config, Matcher};
+use rayon::prelude::*;
 use regex::Regex;
-use tantivy::collector::TopDocs;
-use tantivy::query::{AllQuery, BooleanQuery, FuzzyTermQuery, Occur, Query, RegexQuery};
-use tantivy::schema::{Field, Schema, Value, INDEXED, STORED, STRING, TEXT};
+use tantivy::collector::{Count, DocSetCollector, TopDocs};
+use tantivy::query::{BooleanQuery, ConstScoreQuery, Occur, Query, RegexQuery, TermQuery};
+use tantivy::schema::{Field, IndexRecordOption, Value};
+use tantivy::tokenizer::TextAnalyzer;
 use tantivy::{DocAddress, Index, IndexReader, TantivyDocument, Term};
 
-use crate::config;
+use crate::analysis;
+use crate::cancel::CancellationToken;
+use crate::config::{self, GeneralConfig};
 use crate::error::{Error, Result};
+use crate::extract;
+use crate::schema::{self, Fields};
+
+mod catalog;
+mod explain;
+mod facets;
+mod grep;
+mod literal;
+mod query;
+mod ranking;
+mod request;
+mod similar;
+mod snippet;
+mod stream;
+mod suggest;
+use catalog::{CatalogSnapshot, DocCatalog};
+pub use explain::ScoreExplanation;
+pub use facets::{FacetFilters, Facets, ModifiedBucket};
+pub use grep::LineMatch;
+use literal::LiteralMatcher;
+use query::{ContentQueryBuilder, FilenameMatcher, QueryNode};
+use ranking::Ranker;
+pub use request::{
+    MatchOptions, PageToken, RangeFilter, SearchPage, SearchRequest, TermMatching,
+    DEFAULT_PAGE_SIZE, MAX_FUZZY_DISTANCE,
+};
+use snippet::Highlighter;
+pub use snippet::Snippet;
+use stream::Progress;
+pub use stream::{SearchEvent, STREAM_BATCH_SIZE};
+pub use suggest::TermSuggestion;
+
+/// Documents a content scan checks between cancellation checks and partial results.
+const SCAN_BATCH_DOCS: usize = 64;
 
 /// A single search result.
 #[derive(Debug, Clone)]
@@ -20,11 +59,16 @@ pub struct SearchResult {
     pub path: PathBuf,
     pub filename: String,
     pub score: f32,
-    pub snippet: Option<String>,
+    /// Highlighted content excerpts; empty when content was not stored or did not match.
+    pub snippets: Vec<Snippet>,
+    /// Byte ranges of matched characters within `filename`.
+    pub filename_highlights: Vec<Range<usize>>,
+    /// Every matching line of the document; filled in [`QueryMode::Grep`] only.
+    pub line_matches: Vec<LineMatch>,
 }
 
 /// Search mode selector.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum SearchMode {
     /// Search both content and filenames (default).
     Combined,
@@ -35,69 +79,118 @@ pub enum SearchMode {
 }
 
 /// Query interpretation mode for content search.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum QueryMode {
-    /// Fuzzy content matching with Tantivy `FuzzyTermQuery`.
+    /// Structured query syntax with fuzzy terms, e.g. `report AND NOT draft ext:pdf`.
+    ///
+    /// Supports `AND`/`OR`/`NOT` (or `-term`), parentheses, quoted phrases and the
+    /// field prefixes `ext:`, `path:`, `name:`, `modified:` and `size:`.
     Fuzzy,
     /// Regex content matching with Tantivy `RegexQuery`.
+    ///
+    /// Patterns match whole indexed terms, which are lowercased and diacritic-folded.
     Regex,
+    /// Line-oriented regex over the full document text, e.g. `ERR-\d{4}:` or `foo\s+bar`.
+    ///
+    /// The index narrows candidates by the pattern's literals; the `regex` crate then runs
+    /// over stored content, re-extracting files indexed without it.
+    Grep,
 }
 
-#[derive(Clone, Copy)]
-struct Fields {
-    path: Field,
-    filename: Field,
-    content: Field,
+/// Returns the non-negated free-text terms of a fuzzy query, for preview highlighting.
+///
+/// Falls back to whitespace splitting when the query does not parse.
+pub fn highlight_terms(query_text: &str) -> Vec<String> {
+    match query::parse(query_text) {
+        Ok(node) => node
+            .positive_terms()
+            .into_iter()
+            .map(ToOwned::to_owned)
+            .collect(),
+        Err(_) => query_text
+            .split_whitespace()
+            .map(ToOwned::to_owned)
+            .collect(),
+    }
+}
+
+/// When a [`SearchEngine`] picks up changes committed to its index.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ReloadPolicy {
+    /// Reload automatically shortly after each commit, from this or another process.
+    #[default]
+    OnCommit,
+    /// Keep searching the snapshot taken at open until [`SearchEngine::reload`] is called.
+    Manual,
 }
 
 /// Search service over the Tantivy index.
+///
+/// The engine is `Send + Sync`, so one instance can be shared across threads for the
+/// lifetime of a front end.
 pub struct SearchEngine {
-    _index: Index,
+    index: Index,
     reader: IndexReader,
     fields: Fields,
+    catalog: DocCatalog,
 }
 
 impl SearchEngine {
-    /// Open or create search index in the default XDG data location.
+    /// Open or create search index in the default XDG data location, creating it with
+    /// the configured content tokenizer.
     pub fn open_default() -> Result<Self> {
         let index_path = config::data_dir().join("index");
-        Self::open(&index_path)
+        let config = config::Config::load()
+            .map(|loaded| loaded.general)
+            .unwrap_or_default();
+        Self::open_with_config(&index_path, &config, ReloadPolicy::default())
     }
 
-    /// Open or create search index at the given path.
+    /// Open or create search index at the given path, reloading on commit.
     pub fn open(path: &Path) -> Result<Self> {
-        fs::create_dir_all(path).map_err(|source| {
-            Error::Search(format!(
-                "failed to create search index directory {}: {source}",
-                path.display()
-            ))
-        })?;
-
-        let directory = tantivy::directory::MmapDirectory::open(path)
-            .map_err(|err| Error::Search(format!("failed to open index directory: {err}")))?;
-        let index = Index::open_or_create(directory, schema())?;
-        let reader = index.reader()?;
-
-        let schema = index.schema();
-        let fields = Fields {
-            path: schema
-                .get_field("path")
-                .map_err(|err| Error::Search(format!("missing field 'path': {err}")))?,
-            filename: schema
-                .get_field("filename")
-                .map_err(|err| Error::Search(format!("missing field 'filename': {err}")))?,
-            content: schema
-                .get_field("content")
-                .map_err(|err| Error::Search(format!("missing field 'content': {err}")))?,
-        };
+        Self::open_with_policy(path, ReloadPolicy::default())
+    }
+
+    /// Open or create search index at the given path with the given reload policy.
+    ///
+    /// An existing index keeps the content tokenizer recorded in its schema; a new one
+    /// uses the default tokenizer.
+    pub fn open_with_policy(path: &Path, policy: ReloadPolicy) -> Result<Self> {
+        Self::open_with_config(path, &GeneralConfig::default(), policy)
+    }
+
+    /// Open or create search index at the given path, creating it with the configured
+    /// content tokenizer.
+    pub fn open_with_config(
+        path: &Path,
+        config: &GeneralConfig,
+        policy: ReloadPolicy,
+    ) -> Result<Self> {
+        let index = schema::open_or_create(path, config.content_tokenizer)?.index;
+        let reader = index
+            .reader_builder()
+            .reload_policy(match policy {
+                ReloadPolicy::OnCommit => tantivy::ReloadPolicy::OnCommitWithDelay,
+                ReloadPolicy::Manual => tantivy::ReloadPolicy::Manual,
+            })
+            .try_into()?;
+
+        let fields = Fields::new(&index.schema())?;
 
         Ok(Self {
-            _index: index,
+            index,
             reader,
             fields,
+            catalog: DocCatalog::default(),
         })
     }
 
+    /// Picks up the latest commit now, whatever the reload policy.
+    pub fn reload(&self) -> Result<()> {
+        self.reader.reload()?;
+        Ok(())
+    }
+
     /// Run a query and return ranked results.
     pub fn search(
         &self,
@@ -106,174 +199,771 @@ impl SearchEngine {
         search_mode: SearchMode,
         limit: usize,
     ) -> Result<Vec<SearchResult>> {
-        if query_text.trim().is_empty() || limit == 0 {
-            return Ok(Vec::new());
-        }
+        let request = SearchRequest::new(query_text, query_mode, search_mode).with_page(0, limit);
+        self.search_page(&request).map(|page| page.results)
+    }
 
-        self.reader.reload()?;
-        let searcher = self.reader.searcher();
+    /// Run a query and return one page of ranked results with the total hit count.
+    ///
+    /// Every hit is scored before the page is cut, so combined-mode ranking is the same
+    /// whichever page is requested.
+    pub fn search_page(&self, request: &SearchRequest) -> Result<SearchPage> {
+        self.run_search(request, &Progress::silent())
+    }
 
-        let mut docs: HashMap<PathBuf, Accumulator> = HashMap::new();
+    /// Like [`Self::search_page`], but reports progress on `events` as it goes.
+    ///
+    /// Content scans send [`SearchEvent::Partial`] hits as they are confirmed; then the
+    /// ranked page follows in [`SearchEvent::Ranked`] batches of [`STREAM_BATCH_SIZE`].
+    /// Fails with [`Error::Cancelled`] soon after `cancel` fires or the receiver of
+    /// `events` is dropped.
+    pub fn search_stream(
+        &self,
+        request: &SearchRequest,
+        cancel: &CancellationToken,
+        events: &Sender<SearchEvent>,
+    ) -> Result<SearchPage> {
+        self.run_search(request, &Progress::new(cancel, events))
+    }
 
-        if matches!(search_mode, SearchMode::Combined | SearchMode::ContentOnly) {
-            let content_scores = self.content_scores(&searcher, query_text, query_mode, limit)?;
-            apply_normalized_scores(&content_scores, &mut docs, ScoreChannel::Content);
+    fn run_search(&self, request: &SearchRequest, progress: &Progress) -> Result<SearchPage> {
+        let query_text = request.query_text.as_str();
+        let search_mode = request.search_mode;
+        if query_text.trim().is_empty() || request.limit == 0 {
+            return Ok(SearchPage::default());
         }
 
-        if matches!(search_mode, SearchMode::Combined | SearchMode::FilenameOnly) {
-            let filename_scores = self.filename_scores(&searcher, query_text, query_mode)?;
-            apply_normalized_scores(&filename_scores, &mut docs, ScoreChannel::Filename);
+        let parsed = parse_request(request)?;
+
+        let searcher = self.reader.searcher();
+        let snapshot = snapshot_id(&searcher);
+        request.check_snapshot(snapshot)?;
+        let catalog = self.catalog.snapshot(&searcher, &self.fields)?;
+
+        let now = ranking::unix_now();
+        let mut ranked = self.ranked_hits(&searcher, &catalog, request, &parsed, now, progress)?;
+        // Each facet counts what selecting it would add, so counts come from the query
+        // without the facet filters the hits were narrowed by.
+        let facets = if request.filters.is_empty() {
+            facets::count(&ranked, &request.filters, &request.folders, now)
+        } else {
+            let unfiltered = request.clone().with_filters(FacetFilters::default());
+            let hits = self.ranked_hits(
+                &searcher,
+                &catalog,
+                &unfiltered,
+                &parsed,
+                now,
+                &progress.quiet(),
+            )?;
+            facets::count(&hits, &request.filters, &request.folders, now)
+        };
+        ranked.sort_by(|(left_score, left), (right_score, right)| {
+            right_score
+                .partial_cmp(left_score)
+                .unwrap_or(Ordering::Equal)
+                .then_with(|| left.path.cmp(&right.path))
+        });
+
+        let total_hits = ranked.len();
+        let page_end = request.offset.saturating_add(request.limit).min(total_hits);
+        let next_page = (page_end < total_hits).then(|| request.token_at(page_end, snapshot));
+
+        let analyzer = self.analyzer(self.fields.content)?;
+        let literal_regex = parsed
+            .literal
+            .as_ref()
+            .and_then(LiteralMatcher::highlight_regex);
+        let mut highlighter = match (&parsed.node, &parsed.grep, literal_regex) {
+            (_, Some(regex), _) => Highlighter::grep(analyzer, regex.clone()),
+            (_, None, Some(regex)) => Highlighter::grep(analyzer, regex),
+            (Some(node), None, None) => Highlighter::fuzzy(
+                analyzer,
+                node,
+                request.term_matching,
+                self.stemmers(&searcher)?,
+            ),
+            (None, None, None) => Highlighter::regex(analyzer, query_text)?,
+        };
+        let page: Vec<(f32, Accumulator)> = ranked
+            .into_iter()
+            .skip(request.offset)
+            .take(request.limit)
+            .collect();
+        let mut results = Vec::with_capacity(page.len());
+        for batch in page.chunks(STREAM_BATCH_SIZE) {
+            progress.check()?;
+            let start = results.len();
+            for (score, acc) in batch {
+                results.push(self.build_result(
+                    &searcher,
+                    acc.clone(),
+                    *score,
+                    search_mode,
+                    &mut highlighter,
+                    parsed.grep.as_ref(),
+                )?);
+            }
+            progress.ranked(|| SearchPage {
+                results: results[start..].to_vec(),
+                total_hits,
+                next_page: next_page.clone(),
+                facets: facets.clone(),
+            })?;
         }
 
-        let mut results: Vec<SearchResult> = docs
-            .into_values()
-            .filter_map(|acc| {
-                let score = match search_mode {
-                    SearchMode::Combined => 0.7 * acc.content_score + 0.3 * acc.filename_score,
-                    SearchMode::FilenameOnly => acc.filename_score,
-                    SearchMode::ContentOnly => acc.content_score,
-                };
-
-                if score <= 0.0 {
-                    return None;
-                }
+        Ok(SearchPage {
+            results,
+            total_hits,
+            next_page,
+            facets,
+        })
+    }
+
+    /// Indexed content and filename terms closest to `word`, for "did you mean" hints.
+    ///
+    /// Closer terms come first, then those found in more documents.
+    pub fn suggest(&self, word: &str, limit: usize) -> Result<Vec<TermSuggestion>> {
+        let searcher = self.reader.searcher();
+        suggest::corrections(
+            &searcher,
+            &self.suggestion_fields(),
+            &analysis::fold(word),
+            limit,
+        )
+    }
+
+    /// Rewrites a fuzzy query with each word the index lacks replaced by its closest
+    /// indexed term; `None` if every word is indexed or has no close match.
+    pub fn did_you_mean(&self, query_text: &str) -> Result<Option<String>> {
+        let node = query::parse(query_text)?;
+        let searcher = self.reader.searcher();
+        suggest::corrected_query(
+            &searcher,
+            &self.suggestion_fields(),
+            &mut self.analyzer(self.fields.content)?,
+            &node,
+            query_text,
+        )
+    }
+
+    /// Indexed content and filename terms starting with `prefix`, most frequent first.
+    pub fn complete(&self, prefix: &str, limit: usize) -> Result<Vec<TermSuggestion>> {
+        let searcher = self.reader.searcher();
+        suggest::completions(
+            &searcher,
+            &self.suggestion_fields(),
+            &analysis::fold(prefix.trim()),
+            limit,
+        )
+    }
+
+    /// Files whose content resembles `path`, most similar first, excluding `path` itself.
+    ///
+    /// Uses the stored content of an indexed file, or extracts `path` when it is not
+    /// indexed or was indexed without content. Scores are relative to the best match.
+    pub fn similar_to(&self, path: &Path, limit: usize) -> Result<Vec<SearchResult>> {
+        let searcher = self.reader.searcher();
+        let catalog = self.catalog.snapshot(&searcher, &self.fields)?;
+
+        let source = TermQuery::new(
+            Term::from_field_text(self.fields.path, &path.to_string_lossy()),
+            IndexRecordOption::Basic,
+        );
+        let stored = match searcher.search(&source, &TopDocs::with_limit(1))?.first() {
+            Some((_, address)) => self.document_text(&searcher, *address, path, false)?,
+            None => None,
+        };
+        let text = match stored {
+            Some(text) => text,
+            None => extract::extract_text(path)?,
+        };
 
+        let query = similar::query(&self.fields, path, text);
+        let hits = searcher.search(&query, &TopDocs::with_limit(limit))?;
+        let best = hits.first().map_or(1.0, |(score, _)| *score);
+        Ok(hits
+            .into_iter()
+            .filter_map(|(score, address)| {
+                let doc = catalog.get(address)?;
                 Some(SearchResult {
-                    path: acc.path,
-                    filename: acc.filename,
-                    score,
-                    snippet: None,
+                    path: doc.path.clone(),
+                    filename: doc.filename.clone(),
+                    score: score / best,
+                    snippets: Vec::new(),
+                    filename_highlights: Vec::new(),
+                    line_matches: Vec::new(),
                 })
             })
-            .collect();
+            .collect())
+    }
 
-        results.sort_by(|left, right| {
-            right
-                .score
-                .partial_cmp(&left.score)
-                .unwrap_or(Ordering::Equal)
-                .then_with(|| left.path.cmp(&right.path))
-        });
-        results.truncate(limit);
+    fn suggestion_fields(&self) -> [Field; 2] {
+        [self.fields.content, self.fields.filename]
+    }
 
-        Ok(results)
+    /// Breaks down how `path` scores for `request`, or `None` if it is not indexed.
+    ///
+    /// Scores are normalized against the hits passing the request's facet filters, as
+    /// [`Self::search_page`] ranks them. A file the filters leave out is still explained,
+    /// from its own unfiltered raw scores.
+    pub fn explain(
+        &self,
+        request: &SearchRequest,
+        path: &Path,
+    ) -> Result<Option<ScoreExplanation>> {
+        if request.query_text.trim().is_empty() {
+            return Ok(None);
+        }
+        let parsed = parse_request(request)?;
+
+        let searcher = self.reader.searcher();
+        let catalog = self.catalog.snapshot(&searcher, &self.fields)?;
+        let Some(doc) = catalog.par_docs().find_any(|doc| doc.path == path) else {
+            return Ok(None);
+        };
+
+        let now = ranking::unix_now();
+        let progress = Progress::silent();
+        let ranked = self.channel_scores(&searcher, &catalog, request, &parsed, now, &progress)?;
+        let filtered_out = !request
+            .filters
+            .matches(&doc.ext, &doc.path, doc.modified, now);
+        let own = if filtered_out {
+            let alone = request
+                .clone()
+                .with_filters(FacetFilters::default())
+                .with_scopes(vec![doc.path.clone()]);
+            self.channel_scores(&searcher, &catalog, &alone, &parsed, now, &progress)?
+        } else {
+            ranked.clone()
+        };
+        let ranker = Ranker::new(&request.ranking, &request.folders, now);
+        Ok(Some(explain::explain(
+            doc,
+            request.search_mode,
+            &ranker,
+            &ranked,
+            &own,
+        )))
     }
 
-    fn content_scores(
+    /// Every hit passing the request's filters with its combined score, unordered.
+    fn ranked_hits(
         &self,
         searcher: &tantivy::Searcher,
-        query_text: &str,
-        query_mode: QueryMode,
-        limit: usize,
-    ) -> Result<Vec<(DocData, f32)>> {
-        let content_docs = match query_mode {
-            QueryMode::Fuzzy => {
-                let terms: Vec<String> = query_text
-                    .split_whitespace()
-                    .map(|term| term.to_ascii_lowercase())
-                    .filter(|term| !term.is_empty())
-                    .collect();
-
-                if terms.is_empty() {
-                    return Ok(Vec::new());
-                }
+        catalog: &CatalogSnapshot,
+        request: &SearchRequest,
+        parsed: &ParsedRequest,
+        now: u64,
+        progress: &Progress,
+    ) -> Result<Vec<(f32, Accumulator)>> {
+        let (content_scores, filename_scores) =
+            self.channel_scores(searcher, catalog, request, parsed, now, progress)?;
+        let mut docs: HashMap<PathBuf, Accumulator> = HashMap::new();
+        apply_normalized_scores(&content_scores, &mut docs, ScoreChannel::Content);
+        apply_normalized_scores(&filename_scores, &mut docs, ScoreChannel::Filename);
 
-                let clauses: Vec<(Occur, Box<dyn Query>)> = terms
-                    .iter()
-                    .map(|term| {
-                        let fuzzy = FuzzyTermQuery::new_prefix(
-                            Term::from_field_text(self.fields.content, term),
-                            1,
-                            true,
-                        );
-                        (Occur::Should, Box::new(fuzzy) as Box<dyn Query>)
-                    })
-                    .collect();
-                let query = BooleanQuery::new(clauses);
-                searcher.search(&query, &TopDocs::with_limit(limit))?
-            }
-            QueryMode::Regex => {
-                let query = RegexQuery::from_pattern(query_text, self.fields.content)?;
-                searcher.search(&query, &TopDocs::with_limit(limit))?
+        let ranker = Ranker::new(&request.ranking, &request.folders, now);
+        Ok(docs
+            .into_values()
+            .filter_map(|acc| {
+                let score = ranker.score(
+                    request.search_mode,
+                    acc.content_score,
+                    acc.filename_score,
+                    &acc.path,
+                    acc.modified,
+                );
+
+                (score > 0.0).then_some((score, acc))
+            })
+            .collect())
+    }
+
+    /// Raw content and filename scores of every hit, empty for channels the mode skips.
+    ///
+    /// `now` is the time modified-date facet filters count back from.
+    fn channel_scores(
+        &self,
+        searcher: &tantivy::Searcher,
+        catalog: &CatalogSnapshot,
+        request: &SearchRequest,
+        parsed: &ParsedRequest,
+        now: u64,
+        progress: &Progress,
+    ) -> Result<(ScoredDocs, ScoredDocs)> {
+        let search_mode = request.search_mode;
+        let content_scores = if search_mode == SearchMode::FilenameOnly {
+            Vec::new()
+        } else {
+            match &parsed.grep {
+                Some(regex) => {
+                    self.grep_scores(searcher, catalog, request, regex, now, progress)?
+                }
+                None => {
+                    let hits = self.content_scores(searcher, catalog, request, parsed, now)?;
+                    match &parsed.literal {
+                        Some(literal) => self.literal_hits(searcher, hits, literal, progress)?,
+                        None => hits,
+                    }
+                }
             }
         };
+        progress.check()?;
+        let filename_scores = if search_mode == SearchMode::ContentOnly {
+            Vec::new()
+        } else {
+            filename_scores(catalog, request, parsed, now, progress)?
+        };
+        progress.check()?;
+        Ok((content_scores, filename_scores))
+    }
 
-        content_docs
-            .into_iter()
-            .map(|(score, address)| self.load_doc(searcher, address).map(|doc| (doc, score)))
-            .collect()
+    fn build_result(
+        &self,
+        searcher: &tantivy::Searcher,
+        acc: Accumulator,
+        score: f32,
+        search_mode: SearchMode,
+        highlighter: &mut Highlighter,
+        grep_regex: Option<&Regex>,
+    ) -> Result<SearchResult> {
+        let (content, language) =
+            if acc.content_score > 0.0 && search_mode != SearchMode::FilenameOnly {
+                let document = searcher.doc::<TantivyDocument>(acc.address)?;
+                let language = document
+                    .get_first(self.fields.lang)
+                    .and_then(|value| value.as_str())
+                    .map(ToOwned::to_owned);
+                let content = self.stored_text(&document, &acc.path, grep_regex.is_some());
+                (content, language)
+            } else {
+                (None, None)
+            };
+        let snippets = content
+            .as_deref()
+            .map(|content| highlighter.content_snippets(content, language.as_deref()))
+            .unwrap_or_default();
+        let line_matches = match (grep_regex, content.as_deref()) {
+            (Some(regex), Some(content)) => grep::line_matches(regex, content),
+            _ => Vec::new(),
+        };
+
+        let filename_highlights = if search_mode == SearchMode::ContentOnly {
+            Vec::new()
+        } else {
+            highlighter.filename_ranges(&acc.filename)
+        };
+
+        Ok(SearchResult {
+            path: acc.path,
+            filename: acc.filename,
+            score,
+            snippets,
+            filename_highlights,
+            line_matches,
+        })
     }
 
-    fn filename_scores(
+    /// Stored content of a document, optionally re-extracting files indexed without it.
+    fn document_text(
         &self,
         searcher: &tantivy::Searcher,
-        query_text: &str,
-        query_mode: QueryMode,
+        address: DocAddress,
+        path: &Path,
+        extract_fallback: bool,
+    ) -> Result<Option<String>> {
+        let document = searcher.doc::<TantivyDocument>(address)?;
+        Ok(self.stored_text(&document, path, extract_fallback))
+    }
+
+    /// Stored content of a loaded document, optionally re-extracting `path` without it.
+    fn stored_text(
+        &self,
+        document: &TantivyDocument,
+        path: &Path,
+        extract_fallback: bool,
+    ) -> Option<String> {
+        if let Some(content) = document
+            .get_first(self.fields.stored_content)
+            .and_then(|value| value.as_str())
+        {
+            return Some(content.to_string());
+        }
+
+        extract_fallback
+            .then(|| extract::extract_text(path).ok())
+            .flatten()
+    }
+
+    /// Scores index candidates by their number of lines matching `regex`.
+    fn grep_scores(
+        &self,
+        searcher: &tantivy::Searcher,
+        catalog: &CatalogSnapshot,
+        request: &SearchRequest,
+        regex: &Regex,
+        now: u64,
+        progress: &Progress,
     ) -> Result<Vec<(DocData, f32)>> {
-        match query_mode {
-            QueryMode::Fuzzy => {
-                let mut matcher = Matcher::new(Config::DEFAULT.match_paths());
-                let pattern =
-                    Pattern::parse(query_text, CaseMatching::Ignore, Normalization::Smart);
-                let mut scratch = Vec::new();
-
-                let mut scored = Vec::new();
-                for address in all_doc_addresses(searcher)? {
-                    let doc = self.load_doc(searcher, address)?;
-                    let haystack = Utf32Str::new(&doc.filename, &mut scratch);
-                    if let Some(score) = pattern.score(haystack, &mut matcher) {
-                        scored.push((doc, score as f32));
+        let mut analyzer = self.analyzer(self.fields.content)?;
+        let candidates =
+            grep::candidate_query(&request.query_text, &mut analyzer, self.fields.content)?;
+        let candidates = self.with_request_filters(candidates, request, now)?;
+        let candidates: Vec<DocAddress> = searcher
+            .search(&candidates, &DocSetCollector)?
+            .into_iter()
+            .collect();
+
+        let mut hits = Vec::new();
+        for batch in candidates.chunks(SCAN_BATCH_DOCS) {
+            progress.check()?;
+            let found: Vec<(DocData, Vec<LineMatch>)> = batch
+                .par_iter()
+                .filter_map(|address| {
+                    let doc = catalog.get(*address)?;
+                    match self.document_text(searcher, *address, &doc.path, true) {
+                        Ok(text) => {
+                            let lines = grep::line_matches(regex, &text?);
+                            (!lines.is_empty()).then(|| Ok((doc.clone(), lines)))
+                        }
+                        Err(err) => Some(Err(err)),
                     }
-                }
+                })
+                .collect::<Result<_>>()?;
+            progress.partial(|| {
+                found
+                    .iter()
+                    .map(|(doc, lines)| partial_result(doc, lines.clone()))
+                    .collect()
+            })?;
+            hits.extend(
+                found
+                    .into_iter()
+                    .map(|(doc, lines)| (doc, lines.len() as f32)),
+            );
+        }
+        Ok(hits)
+    }
 
-                Ok(scored)
-            }
-            QueryMode::Regex => {
-                let regex = Regex::new(query_text)
-                    .map_err(|err| Error::Search(format!("invalid regex pattern: {err}")))?;
-
-                let mut scored = Vec::new();
-                for address in all_doc_addresses(searcher)? {
-                    let doc = self.load_doc(searcher, address)?;
-                    if regex.is_match(&doc.filename) {
-                        scored.push((doc, 1.0));
+    /// Keeps the hits whose original text satisfies `literal`, re-extracting files indexed
+    /// without content.
+    fn literal_hits(
+        &self,
+        searcher: &tantivy::Searcher,
+        hits: ScoredDocs,
+        literal: &LiteralMatcher,
+        progress: &Progress,
+    ) -> Result<ScoredDocs> {
+        let mut confirmed = Vec::new();
+        for batch in hits.chunks(SCAN_BATCH_DOCS) {
+            progress.check()?;
+            let found: ScoredDocs = batch
+                .par_iter()
+                .filter_map(|(doc, score)| {
+                    match self.document_text(searcher, doc.address, &doc.path, true) {
+                        Ok(text) => literal.is_match(&text?).then(|| Ok((doc.clone(), *score))),
+                        Err(err) => Some(Err(err)),
                     }
-                }
+                })
+                .collect::<Result<_>>()?;
+            progress.partial(|| {
+                found
+                    .iter()
+                    .map(|(doc, _)| partial_result(doc, Vec::new()))
+                    .collect()
+            })?;
+            confirmed.extend(found);
+        }
+        Ok(confirmed)
+    }
 
-                Ok(scored)
+    fn analyzer(&self, field: Field) -> Result<TextAnalyzer> {
+        self.index.tokenizer_for_field(field).map_err(Error::from)
+    }
+
+    fn content_scores(
+        &self,
+        searcher: &tantivy::Searcher,
+        catalog: &CatalogSnapshot,
+        request: &SearchRequest,
+        parsed: &ParsedRequest,
+        now: u64,
+    ) -> Result<Vec<(DocData, f32)>> {
+        let query: Box<dyn Query> = match &parsed.node {
+            Some(node) => ContentQueryBuilder {
+                fields: &self.fields,
+                content_analyzer: self.analyzer(self.fields.content)?,
+                filename_analyzer: self.analyzer(self.fields.filename)?,
+                stemmers: self
+                    .stemmers(searcher)?
+                    .into_iter()
+                    .map(|(_, stemmer)| stemmer)
+                    .collect(),
+                term_matching: request.term_matching,
             }
+            .build(node)?,
+            None => Box::new(RegexQuery::from_pattern(
+                &request.query_text,
+                self.fields.content,
+            )?),
+        };
+        let query = self.with_request_filters(query, request, now)?;
+
+        let hit_count = searcher.search(&query, &Count)?;
+        if hit_count == 0 {
+            return Ok(Vec::new());
         }
+
+        Ok(searcher
+            .search(&query, &TopDocs::with_limit(hit_count))?
+            .into_iter()
+            .filter_map(|(score, address)| catalog.get(address).map(|doc| (doc.clone(), score)))
+            .collect())
     }
 
-    fn load_doc(&self, searcher: &tantivy::Searcher, address: DocAddress) -> Result<DocData> {
-        let document = searcher.doc::<TantivyDocument>(address)?;
+    /// Stemming analyzers for the indexed languages that have one, with their codes.
+    fn stemmers(&self, searcher: &tantivy::Searcher) -> Result<Vec<(String, TextAnalyzer)>> {
+        Ok(self
+            .indexed_languages(searcher)?
+            .into_iter()
+            .filter_map(|language| {
+                let stemmer = analysis::stemming_analyzer(&language)?;
+                Some((language, stemmer))
+            })
+            .collect())
+    }
 
-        let path = document
-            .get_first(self.fields.path)
-            .and_then(|value| value.as_str())
-            .ok_or_else(|| Error::Search("indexed document missing string path".to_string()))?;
-        let filename = document
-            .get_first(self.fields.filename)
-            .and_then(|value| value.as_str())
-            .ok_or_else(|| Error::Search("indexed document missing string filename".to_string()))?;
+    /// Language codes of the documents in the index, read from the `lang` term dictionary.
+    fn indexed_languages(&self, searcher: &tantivy::Searcher) -> Result<BTreeSet<String>> {
+        let mut languages = BTreeSet::new();
+        for segment_reader in searcher.segment_readers() {
+            let inverted_index = segment_reader.inverted_index(self.fields.lang)?;
+            let mut terms = inverted_index.terms().stream()?;
+            while terms.advance() {
+                languages.insert(String::from_utf8_lossy(terms.key()).into_owned());
+            }
+        }
+        Ok(languages)
+    }
 
-        Ok(DocData {
-            path: PathBuf::from(path),
-            filename: filename.to_string(),
-        })
+    /// Requires the request's size and modified ranges, path scopes and facet selections
+    /// alongside `query`; modified-date buckets count back from `now`.
+    fn with_request_filters(
+        &self,
+        query: Box<dyn Query>,
+        request: &SearchRequest,
+        now: u64,
+    ) -> Result<Box<dyn Query>> {
+        let mut required: Vec<Box<dyn Query>> = Vec::new();
+        let filters = &request.filters;
+        for folders in [&request.scopes, &filters.folders] {
+            if folders.is_empty() {
+                continue;
+            }
+            let mut subtrees = Vec::with_capacity(folders.len());
+            for folder in folders {
+                let query = RegexQuery::from_pattern(&scope_pattern(folder), self.fields.path)?;
+                subtrees.push((Occur::Should, Box::new(query) as Box<dyn Query>));
+            }
+            required.push(Box::new(BooleanQuery::new(subtrees)));
+        }
+        if !filters.extensions.is_empty() {
+            let extensions = filters
+                .extensions
+                .iter()
+                .map(|ext| {
+                    let term = Term::from_field_text(self.fields.ext, ext);
+                    let query = TermQuery::new(term, IndexRecordOption::Basic);
+                    (Occur::Should, Box::new(query) as Box<dyn Query>)
+                })
+                .collect();
+            required.push(Box::new(BooleanQuery::new(extensions)));
+        }
+        if !filters.modified.is_empty() {
+            let buckets = filters
+                .modified
+                .iter()
+                .map(|bucket| {
+                    let query = bucket.range(now).to_query(self.fields.modified);
+                    (Occur::Should, query)
+                })
+                .collect();
+            required.push(Box::new(BooleanQuery::new(buckets)));
+        }
+        for (range, field) in [
+            (request.size, self.fields.size),
+            (request.modified, self.fields.modified),
+        ] {
+            if !range.is_unbounded() {
+                required.push(range.to_query(field));
+            }
+        }
+
+        if required.is_empty() {
+            return Ok(query);
+        }
+        // Filters only narrow the hits: scoring them at zero keeps raw scores the same
+        // whichever filters are set.
+        let mut clauses = vec![(Occur::Must, query)];
+        clauses.extend(required.into_iter().map(|filter| {
+            let filter: Box<dyn Query> = Box::new(ConstScoreQuery::new(filter, 0.0));
+            (Occur::Must, filter)
+        }));
+        Ok(Box::new(BooleanQuery::new(clauses)))
+    }
+}
+
+/// Identifies the segments and deletes `searcher` sees, which stay the same across
+/// reloads that found no new commit.
+fn snapshot_id(searcher: &tantivy::Searcher) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    searcher.generation().segments().hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Matches `scope` itself and every path below it, but not siblings sharing its prefix.
+fn scope_pattern(scope: &Path) -> String {
+    let separator = std::path::MAIN_SEPARATOR.to_string();
+    let scope = scope.to_string_lossy();
+    let prefix = scope.trim_end_matches(std::path::MAIN_SEPARATOR);
+    format!(
+        "{}({}.*)?",
+        regex::escape(prefix),
+        regex::escape(&separator)
+    )
+}
+
+/// A request's query, compiled for the index and for checks against the original text.
+struct ParsedRequest {
+    /// Parsed fuzzy query.
+    node: Option<QueryNode>,
+    /// Compiled grep pattern.
+    grep: Option<Regex>,
+    /// Case and whole-word checks of fuzzy and regex hits, when the request asks for them.
+    literal: Option<LiteralMatcher>,
+}
+
+/// Parses a fuzzy query or compiles a grep pattern, as the query mode requires.
+fn parse_request(request: &SearchRequest) -> Result<ParsedRequest> {
+    let query_text = request.query_text.as_str();
+    let options = request.match_options;
+    let node = match request.query_mode {
+        QueryMode::Fuzzy => Some(query::parse(query_text)?),
+        QueryMode::Regex | QueryMode::Grep => None,
+    };
+    let grep = match request.query_mode {
+        QueryMode::Grep => Some(grep::compile(query_text, options)?),
+        QueryMode::Fuzzy | QueryMode::Regex => None,
+    };
+    let literal = match (&node, request.query_mode) {
+        _ if !options.is_literal() => None,
+        (Some(node), _) => Some(LiteralMatcher::fuzzy(node, request.term_matching, options)?),
+        (None, QueryMode::Regex) => Some(LiteralMatcher::regex(query_text, options)?),
+        (None, _) => None,
+    };
+    Ok(ParsedRequest {
+        node,
+        grep,
+        literal,
+    })
+}
+
+/// Regex locating the request's matches in original text, for preview highlighting.
+///
+/// `None` for fuzzy and regex queries without [`MatchOptions`], whose matches depend on
+/// folding; use [`highlight_terms`] or the pattern itself for those.
+pub fn match_regex(request: &SearchRequest) -> Result<Option<Regex>> {
+    let parsed = parse_request(request)?;
+    Ok(parsed
+        .grep
+        .or_else(|| parsed.literal.and_then(|literal| literal.highlight_regex())))
+}
+
+/// Unranked result for a document a scan has confirmed, for [`SearchEvent::Partial`].
+fn partial_result(doc: &DocData, line_matches: Vec<LineMatch>) -> SearchResult {
+    SearchResult {
+        path: doc.path.clone(),
+        filename: doc.filename.clone(),
+        score: 0.0,
+        snippets: Vec::new(),
+        filename_highlights: Vec::new(),
+        line_matches,
+    }
+}
+
+fn filename_scores(
+    catalog: &CatalogSnapshot,
+    request: &SearchRequest,
+    parsed: &ParsedRequest,
+    now: u64,
+    progress: &Progress,
+) -> Result<Vec<(DocData, f32)>> {
+    // Cancelled scans skip the remaining documents; the caller then reports the cancel.
+    let in_range = |doc: &DocData| {
+        !progress.is_cancelled()
+            && request.size.contains(doc.size)
+            && request.modified.contains(doc.modified)
+            && request.in_scope(&doc.path)
+            && request
+                .filters
+                .matches(&doc.ext, &doc.path, doc.modified, now)
+    };
+    match &parsed.node {
+        Some(node) => {
+            let filename_matcher = FilenameMatcher::new(node, request.term_matching);
+            let literal_match = |doc: &DocData| {
+                parsed
+                    .literal
+                    .as_ref()
+                    .is_none_or(|literal| literal.is_match(&doc.filename))
+            };
+            Ok(catalog
+                .par_docs()
+                .filter(|doc| in_range(doc) && literal_match(doc))
+                .map_init(
+                    || (Matcher::new(Config::DEFAULT.match_paths()), Vec::new()),
+                    |(matcher, scratch), doc| {
+                        filename_matcher
+                            .score(doc, matcher, scratch)
+                            .map(|score| (doc.clone(), score))
+                    },
+                )
+                .flatten()
+                .collect())
+        }
+        None => {
+            let regex = literal::pattern_regex(&request.query_text, request.match_options)?;
+            Ok(catalog
+                .par_docs()
+                .filter(|doc| in_range(doc) && regex.is_match(&doc.filename))
+                .map(|doc| (doc.clone(), 1.0))
+                .collect())
+        }
     }
 }
 
 #[derive(Debug, Clone)]
 struct DocData {
+    address: DocAddress,
     path: PathBuf,
     filename: String,
+    ext: String,
+    modified: u64,
+    size: u64,
 }
 
+/// Documents with their raw score from one channel.
+type ScoredDocs = Vec<(DocData, f32)>;
+
 #[derive(Debug, Clone)]
 struct Accumulator {
+    address: DocAddress,
     path: PathBuf,
     filename: String,
+    ext: String,
+    modified: u64,
     content_score: f32,
     filename_score: f32,
 }
@@ -289,24 +979,18 @@ fn apply_normalized_scores(
     accumulators: &mut HashMap<PathBuf, Accumulator>,
     channel: ScoreChannel,
 ) {
-    if source.is_empty() {
-        return;
-    }
-
-    let max = source
-        .iter()
-        .map(|(_, score)| *score)
-        .fold(0.0_f32, f32::max)
-        .max(1.0);
-
+    let max = normalization_divisor(source);
     for (doc, raw_score) in source {
         let normalized = (*raw_score / max).clamp(0.0, 1.0);
 
         let entry = accumulators
             .entry(doc.path.clone())
             .or_insert_with(|| Accumulator {
+                address: doc.address,
                 path: doc.path.clone(),
                 filename: doc.filename.clone(),
+                ext: doc.ext.clone(),
+                modified: doc.modified,
                 content_score: 0.0,
                 filename_score: 0.0,
             });
@@ -318,26 +1002,13 @@ fn apply_normalized_scores(
     }
 }
 
-fn all_doc_addresses(searcher: &tantivy::Searcher) -> Result<Vec<DocAddress>> {
-    let limit = searcher.num_docs() as usize;
-    if limit == 0 {
-        return Ok(Vec::new());
-    }
-
-    let all = AllQuery;
-    let hits = searcher.search(&all, &TopDocs::with_limit(limit))?;
-    Ok(hits.into_iter().map(|(_, address)| address).collect())
-}
-
-fn schema() -> Schema {
-    let mut schema_builder = Schema::builder();
-    schema_builder.add_text_field("path", STRING | STORED);
-    schema_builder.add_text_field("filename", TEXT | STORED);
-    schema_builder.add_text_field("content", TEXT);
-    schema_builder.add_u64_field("modified", INDEXED | STORED);
-    schema_builder.add_u64_field("size", STORED);
-    schema_builder.add_text_field("ext", STRING | STORED);
-    schema_builder.build()
+/// Best raw score of a channel, but at least 1 so weak matches stay weak.
+fn normalization_divisor(source: &[(DocData, f32)]) -> f32 {
+    source
+        .iter()
+        .map(|(_, score)| *score)
+        .fold(0.0_f32, f32::max)
+        .max(1.0)
 }
 
 #[cfg(test)]
@@ -0,0 +1,156 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+
+use rayon::prelude::*;
+use tantivy::columnar::StrColumn;
+use tantivy::index::SegmentId;
+use tantivy::schema::Field;
+use tantivy::{DocAddress, Searcher, SegmentReader};
+
+use crate::error::{Error, Result};
+use crate::schema::Fields;
+use crate::search::DocData;
+
+/// In-memory filename and metadata table, kept in step with the reader's segments.
+///
+/// Filename matching walks this table instead of loading every stored document per query.
+#[derive(Default)]
+pub(crate) struct DocCatalog {
+    segments: Mutex<HashMap<SegmentId, CachedSegment>>,
+}
+
+struct CachedSegment {
+    delete_opstamp: Option<u64>,
+    docs: Arc<Vec<DocData>>,
+}
+
+impl DocCatalog {
+    /// Brings the table up to date with `searcher` and returns a view of its documents.
+    ///
+    /// Only segments new since the last call are read, from their fast fields. Segments with new
+    /// deletions are filtered in place, and segments no longer searched are dropped.
+    pub(crate) fn snapshot(&self, searcher: &Searcher, fields: &Fields) -> Result<CatalogSnapshot> {
+        let mut segments = self.segments.lock().unwrap_or_else(PoisonError::into_inner);
+
+        let mut refreshed = HashMap::with_capacity(searcher.segment_readers().len());
+        let mut ordered = Vec::with_capacity(searcher.segment_readers().len());
+        for (segment_ord, reader) in searcher.segment_readers().iter().enumerate() {
+            let segment_ord = segment_ord as u32;
+            let docs = match segments.get(&reader.segment_id()) {
+                Some(cached) => refresh_segment(cached, segment_ord, reader),
+                None => Arc::new(load_segment(segment_ord, reader, fields)?),
+            };
+            ordered.push(Arc::clone(&docs));
+            refreshed.insert(
+                reader.segment_id(),
+                CachedSegment {
+                    delete_opstamp: reader.delete_opstamp(),
+                    docs,
+                },
+            );
+        }
+        *segments = refreshed;
+
+        Ok(CatalogSnapshot { segments: ordered })
+    }
+}
+
+/// Catalog documents for one searcher generation, indexed by segment ordinal.
+pub(crate) struct CatalogSnapshot {
+    segments: Vec<Arc<Vec<DocData>>>,
+}
+
+impl CatalogSnapshot {
+    /// Looks up a live document; per-segment lists are kept sorted by doc id.
+    pub(crate) fn get(&self, address: DocAddress) -> Option<&DocData> {
+        let docs = self.segments.get(address.segment_ord as usize)?;
+        docs.binary_search_by_key(&address.doc_id, |doc| doc.address.doc_id)
+            .ok()
+            .map(|index| &docs[index])
+    }
+
+    pub(crate) fn par_docs(&self) -> impl ParallelIterator<Item = &DocData> {
+        self.segments.par_iter().flat_map(|docs| docs.par_iter())
+    }
+}
+
+fn refresh_segment(
+    cached: &CachedSegment,
+    segment_ord: u32,
+    reader: &SegmentReader,
+) -> Arc<Vec<DocData>> {
+    let mut docs = Arc::clone(&cached.docs);
+    let deletes_changed = cached.delete_opstamp != reader.delete_opstamp();
+    let reordered = docs
+        .first()
+        .is_some_and(|doc| doc.address.segment_ord != segment_ord);
+
+    if deletes_changed || reordered {
+        let docs = Arc::make_mut(&mut docs);
+        docs.retain(|doc| !reader.is_deleted(doc.address.doc_id));
+        for doc in docs.iter_mut() {
+            doc.address.segment_ord = segment_ord;
+        }
+    }
+
+    docs
+}
+
+/// Reads the catalog fields of a segment's live documents from its fast fields, so no
+/// stored document, and none of its stored content, is decompressed.
+fn load_segment(segment_ord: u32, reader: &SegmentReader, fields: &Fields) -> Result<Vec<DocData>> {
+    let schema = reader.schema();
+    let fast_fields = reader.fast_fields();
+    let str_column = |field: Field| fast_fields.str(schema.get_field_name(field));
+    let u64_column = |field: Field| fast_fields.u64(schema.get_field_name(field));
+
+    let paths = str_column(fields.path)?;
+    let path_terms = column_terms(paths.as_ref())?;
+    let exts = str_column(fields.ext)?;
+    let ext_terms = column_terms(exts.as_ref())?;
+    let modified = u64_column(fields.modified)?;
+    let size = u64_column(fields.size)?;
+
+    reader
+        .doc_ids_alive()
+        .map(|doc_id| {
+            let path = paths
+                .as_ref()
+                .and_then(|column| column.term_ords(doc_id).next())
+                .and_then(|ord| path_terms.get(ord as usize))
+                .ok_or_else(|| Error::Search("indexed document missing string path".to_string()))?;
+            let filename = Path::new(path)
+                .file_name()
+                .and_then(|name| name.to_str())
+                .ok_or_else(|| Error::Search(format!("indexed path has no filename: {path}")))?;
+            let ext = exts
+                .as_ref()
+                .and_then(|column| column.term_ords(doc_id).next())
+                .and_then(|ord| ext_terms.get(ord as usize))
+                .map_or("", String::as_str);
+
+            Ok(DocData {
+                address: DocAddress::new(segment_ord, doc_id),
+                path: PathBuf::from(path),
+                filename: filename.to_string(),
+                ext: ext.to_string(),
+                modified: modified.first(doc_id).unwrap_or_default(),
+                size: size.first(doc_id).unwrap_or_default(),
+            })
+        })
+        .collect()
+}
+
+/// Every term of a string fast field, indexed by term ordinal.
+fn column_terms(column: Option<&StrColumn>) -> Result<Vec<String>> {
+    let Some(column) = column else {
+        return Ok(Vec::new());
+    };
+    let mut terms = Vec::with_capacity(column.num_terms());
+    let mut stream = column.dictionary().stream()?;
+    while stream.advance() {
+        terms.push(String::from_utf8_lossy(stream.key()).into_owned());
+    }
+    Ok(terms)
+}
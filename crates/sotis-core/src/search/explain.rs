@@ -0,0 +1,84 @@
+use std::path::PathBuf;
+
+use crate::search::ranking::Ranker;
+use crate::search::{normalization_divisor, DocData, ScoredDocs, SearchMode};
+
+/// How one file's score was put together for a query.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScoreExplanation {
+    pub path: PathBuf,
+    /// Raw tantivy score of the content query; `None` if the content did not match.
+    pub content_raw: Option<f32>,
+    /// Raw content scores are divided by this: the best among the hits passing the
+    /// request's filters, but at least 1.
+    pub content_divisor: f32,
+    /// Raw nucleo score of the filename; `None` if the filename did not match.
+    pub filename_raw: Option<f32>,
+    /// Raw filename scores are divided by this: the best among the hits passing the
+    /// request's filters, but at least 1.
+    pub filename_divisor: f32,
+    /// Weights of the normalized scores in the search mode.
+    pub content_weight: f32,
+    pub filename_weight: f32,
+    /// Multiplier from the ranking profile's recency boost.
+    pub recency_factor: f32,
+    /// Multiplier from folder depth and priority.
+    pub folder_factor: f32,
+    /// Final score; zero means the file does not match.
+    pub score: f32,
+}
+
+impl ScoreExplanation {
+    /// Content score after normalization, between 0 and 1.
+    pub fn content_score(&self) -> f32 {
+        normalize(self.content_raw, self.content_divisor)
+    }
+
+    /// Filename score after normalization, between 0 and 1.
+    pub fn filename_score(&self) -> f32 {
+        normalize(self.filename_raw, self.filename_divisor)
+    }
+}
+
+/// Explains `doc` as `search_page` ranks it: divisors come from the raw channel scores
+/// of the ranked hits, and `doc`'s raw scores from the second pair, which the caller
+/// fetches unfiltered when the request's filters leave `doc` out.
+pub(crate) fn explain(
+    doc: &DocData,
+    search_mode: SearchMode,
+    ranker: &Ranker,
+    (content_scores, filename_scores): &(ScoredDocs, ScoredDocs),
+    (own_content, own_filename): &(ScoredDocs, ScoredDocs),
+) -> ScoreExplanation {
+    let raw_score = |scores: &[(DocData, f32)]| {
+        scores
+            .iter()
+            .find(|(hit, _)| hit.path == doc.path)
+            .map(|(_, score)| *score)
+    };
+    let (content_weight, filename_weight) = ranker.weights(search_mode);
+    let mut explanation = ScoreExplanation {
+        path: doc.path.clone(),
+        content_raw: raw_score(own_content),
+        content_divisor: normalization_divisor(content_scores),
+        filename_raw: raw_score(own_filename),
+        filename_divisor: normalization_divisor(filename_scores),
+        content_weight,
+        filename_weight,
+        recency_factor: ranker.recency_factor(doc.modified),
+        folder_factor: ranker.folder_factor(&doc.path),
+        score: 0.0,
+    };
+    explanation.score = ranker.score(
+        search_mode,
+        explanation.content_score(),
+        explanation.filename_score(),
+        &doc.path,
+        doc.modified,
+    );
+    explanation
+}
+
+fn normalize(raw: Option<f32>, divisor: f32) -> f32 {
+    raw.map_or(0.0, |raw| (raw / divisor).clamp(0.0, 1.0))
+}
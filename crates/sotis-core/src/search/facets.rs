@@ -0,0 +1,256 @@
+use std::collections::BTreeMap;
+use std::ops::Bound;
+use std::path::{Path, PathBuf};
+
+use crate::config::FolderEntry;
+use crate::search::query::U64Range;
+use crate::search::Accumulator;
+
+const SECONDS_PER_DAY: u64 = 86_400;
+
+/// Modification-date bucket, measured back from the start of the current UTC day.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ModifiedBucket {
+    Today,
+    /// The 7 days before today.
+    PastWeek,
+    /// Up to 30 days before today.
+    PastMonth,
+    /// Up to 365 days before today.
+    PastYear,
+    Older,
+}
+
+impl ModifiedBucket {
+    pub const ALL: [Self; 5] = [
+        Self::Today,
+        Self::PastWeek,
+        Self::PastMonth,
+        Self::PastYear,
+        Self::Older,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Today => "Today",
+            Self::PastWeek => "Past week",
+            Self::PastMonth => "Past month",
+            Self::PastYear => "Past year",
+            Self::Older => "Older",
+        }
+    }
+
+    fn of(modified: u64, now: u64) -> Self {
+        Self::ALL
+            .into_iter()
+            .find(|bucket| bucket.range(now).contains(modified))
+            .unwrap_or(Self::Older)
+    }
+
+    /// The modification times in the bucket, as an index range query needs them.
+    pub(crate) fn range(self, now: u64) -> U64Range {
+        let today = now - now % SECONDS_PER_DAY;
+        let days_before = |days: u64| today.saturating_sub(days * SECONDS_PER_DAY);
+
+        let (lower, upper) = match self {
+            Self::Today => (Bound::Included(today), Bound::Unbounded),
+            Self::PastWeek => (Bound::Included(days_before(7)), Bound::Excluded(today)),
+            Self::PastMonth => (
+                Bound::Included(days_before(30)),
+                Bound::Excluded(days_before(7)),
+            ),
+            Self::PastYear => (
+                Bound::Included(days_before(365)),
+                Bound::Excluded(days_before(30)),
+            ),
+            Self::Older => (Bound::Unbounded, Bound::Excluded(days_before(365))),
+        };
+        U64Range { lower, upper }
+    }
+}
+
+/// Facet selections narrowing a search; an empty list leaves its dimension unfiltered.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct FacetFilters {
+    /// Lowercase extensions without the leading dot.
+    pub extensions: Vec<String>,
+    /// Top-level configured folders.
+    pub folders: Vec<PathBuf>,
+    pub modified: Vec<ModifiedBucket>,
+}
+
+impl FacetFilters {
+    pub fn is_empty(&self) -> bool {
+        self.extensions.is_empty() && self.folders.is_empty() && self.modified.is_empty()
+    }
+
+    /// Whether a file passes every selection, as the index query built from them decides.
+    pub(crate) fn matches(&self, ext: &str, path: &Path, modified: u64, now: u64) -> bool {
+        (self.extensions.is_empty() || self.extensions.iter().any(|wanted| wanted == ext))
+            && (self.folders.is_empty()
+                || self.folders.iter().any(|folder| path.starts_with(folder)))
+            && (self.modified.is_empty()
+                || self
+                    .modified
+                    .iter()
+                    .any(|bucket| bucket.range(now).contains(modified)))
+    }
+}
+
+/// Hit counts over the whole result set, not just the returned page.
+///
+/// Each dimension counts hits that pass the other dimensions' filters, so unselected
+/// values keep showing how many results selecting them would add.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Facets {
+    pub extensions: BTreeMap<String, usize>,
+    /// Hits per top-level configured folder; files outside every folder are not counted.
+    pub folders: BTreeMap<PathBuf, usize>,
+    pub modified: BTreeMap<ModifiedBucket, usize>,
+}
+
+struct DocFacets<'a> {
+    ext: &'a str,
+    folder: Option<&'a Path>,
+    modified: ModifiedBucket,
+}
+
+/// Counts facets over `hits`, the ranked hits of the query without facet filters.
+pub(crate) fn count(
+    hits: &[(f32, Accumulator)],
+    filters: &FacetFilters,
+    folders: &[FolderEntry],
+    now: u64,
+) -> Facets {
+    let mut facets = Facets::default();
+
+    for (_, acc) in hits {
+        let doc = DocFacets {
+            ext: &acc.ext,
+            folder: top_level_folder(&acc.path, folders),
+            modified: ModifiedBucket::of(acc.modified, now),
+        };
+        let ext_ok =
+            filters.extensions.is_empty() || filters.extensions.iter().any(|ext| ext == doc.ext);
+        let folder_ok = filters.folders.is_empty()
+            || doc
+                .folder
+                .is_some_and(|folder| filters.folders.iter().any(|wanted| wanted == folder));
+        let modified_ok = filters.modified.is_empty() || filters.modified.contains(&doc.modified);
+
+        if folder_ok && modified_ok && !doc.ext.is_empty() {
+            *facets.extensions.entry(doc.ext.to_string()).or_default() += 1;
+        }
+        if ext_ok && modified_ok {
+            if let Some(folder) = doc.folder {
+                *facets.folders.entry(folder.to_path_buf()).or_default() += 1;
+            }
+        }
+        if ext_ok && folder_ok {
+            *facets.modified.entry(doc.modified).or_default() += 1;
+        }
+    }
+
+    facets
+}
+
+/// The outermost configured folder containing `path`.
+fn top_level_folder<'a>(path: &Path, folders: &'a [FolderEntry]) -> Option<&'a Path> {
+    folders
+        .iter()
+        .map(|folder| folder.path.as_path())
+        .filter(|folder| path.starts_with(folder))
+        .min_by_key(|folder| folder.components().count())
+}
+
+#[cfg(test)]
+mod tests {
+    use tantivy::DocAddress;
+
+    use super::*;
+
+    const NOW: u64 = 1_000 * SECONDS_PER_DAY + 3_600;
+
+    fn hit(path: &str, ext: &str, days_ago: u64) -> (f32, Accumulator) {
+        let path = PathBuf::from(path);
+        (
+            1.0,
+            Accumulator {
+                address: DocAddress::new(0, 0),
+                filename: path.file_name().unwrap().to_string_lossy().into_owned(),
+                path,
+                ext: ext.to_string(),
+                modified: NOW - days_ago * SECONDS_PER_DAY,
+                content_score: 1.0,
+                filename_score: 0.0,
+            },
+        )
+    }
+
+    fn folder(path: &str) -> FolderEntry {
+        FolderEntry {
+            path: PathBuf::from(path),
+            recursive: true,
+            extensions: Vec::new(),
+            priority: 1.0,
+        }
+    }
+
+    #[test]
+    fn modified_buckets_follow_utc_day_boundaries() {
+        assert_eq!(ModifiedBucket::of(NOW - 3_600, NOW), ModifiedBucket::Today);
+        assert_eq!(
+            ModifiedBucket::of(NOW - 3_601, NOW),
+            ModifiedBucket::PastWeek
+        );
+        assert_eq!(
+            ModifiedBucket::of(NOW - 20 * SECONDS_PER_DAY, NOW),
+            ModifiedBucket::PastMonth
+        );
+        assert_eq!(
+            ModifiedBucket::of(NOW - 400 * SECONDS_PER_DAY, NOW),
+            ModifiedBucket::Older
+        );
+    }
+
+    #[test]
+    fn filters_match_selected_values_in_every_dimension() {
+        let filters = FacetFilters {
+            extensions: vec!["pdf".to_string()],
+            folders: vec![PathBuf::from("/docs")],
+            modified: vec![ModifiedBucket::Today, ModifiedBucket::PastYear],
+        };
+        let day = SECONDS_PER_DAY;
+
+        assert!(filters.matches("pdf", Path::new("/docs/a.pdf"), NOW, NOW));
+        assert!(filters.matches("pdf", Path::new("/docs/x/a.pdf"), NOW - 100 * day, NOW));
+        assert!(!filters.matches("pdf", Path::new("/docs/a.pdf"), NOW - 3 * day, NOW));
+        assert!(!filters.matches("txt", Path::new("/docs/a.txt"), NOW, NOW));
+        assert!(!filters.matches("pdf", Path::new("/docs-old/a.pdf"), NOW, NOW));
+        assert!(FacetFilters::default().matches("", Path::new("/x"), 0, NOW));
+    }
+
+    #[test]
+    fn facets_count_other_dimensions() {
+        let folders = [folder("/docs"), folder("/docs/nested"), folder("/work")];
+        let hits = vec![
+            hit("/docs/a.pdf", "pdf", 0),
+            hit("/docs/nested/b.pdf", "pdf", 20),
+            hit("/work/c.txt", "txt", 0),
+            hit("/elsewhere/d.txt", "txt", 400),
+        ];
+        let filters = FacetFilters {
+            extensions: vec!["pdf".to_string()],
+            ..FacetFilters::default()
+        };
+
+        let facets = count(&hits, &filters, &folders, NOW);
+
+        assert_eq!(facets.extensions.get("pdf"), Some(&2));
+        assert_eq!(facets.extensions.get("txt"), Some(&2));
+        assert_eq!(facets.folders.get(Path::new("/docs")), Some(&2));
+        assert_eq!(facets.folders.get(Path::new("/work")), None);
+        assert_eq!(facets.modified.get(&ModifiedBucket::Today), Some(&1));
+        assert_eq!(facets.modified.get(&ModifiedBucket::PastMonth), Some(&1));
+    }
+}
@@ -0,0 +1,255 @@
+use std::collections::BTreeSet;
+use std::ops::Range;
+
+use regex::{Regex, RegexBuilder};
+use regex_syntax::hir::literal::{ExtractKind, Extractor, Seq};
+use regex_syntax::ParserBuilder;
+use tantivy::query::{AllQuery, BooleanQuery, Occur, Query, RegexQuery};
+use tantivy::schema::Field;
+use tantivy::tokenizer::TextAnalyzer;
+
+use crate::analysis::MAX_TOKEN_BYTES;
+use crate::error::{Error, Result};
+use crate::search::MatchOptions;
+
+const MAX_EXPANDED_CLASS: usize = 4;
+
+/// A line of document text containing at least one grep match.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineMatch {
+    /// 1-based line number.
+    pub line_number: usize,
+    /// 1-based character column of the first match on the line.
+    pub column: usize,
+    /// Line text without its terminator.
+    pub line: String,
+    /// Byte ranges of matched spans within `line`.
+    pub ranges: Vec<Range<usize>>,
+}
+
+/// Compiles a grep pattern with the flags of `options`; `^` and `$` anchor at line
+/// boundaries.
+///
+/// Like the other modes, the pattern ignores case unless `options.case_sensitive` is set;
+/// an inline `(?-i)` also restores case for the pattern.
+pub(crate) fn compile(pattern: &str, options: MatchOptions) -> Result<Regex> {
+    let pattern = if options.whole_word {
+        format!(r"\b(?:{pattern})\b")
+    } else {
+        pattern.to_string()
+    };
+    RegexBuilder::new(&pattern)
+        .multi_line(true)
+        .case_insensitive(!options.case_sensitive)
+        .build()
+        .map_err(|err| Error::Search(format!("invalid regex pattern: {err}")))
+}
+
+/// Builds an index query selecting documents that may contain a match of `pattern`.
+///
+/// Literals every match must start (or end) with are split into indexed tokens and each
+/// token is required as a substring of some term. Patterns without usable literals select
+/// every document.
+pub(crate) fn candidate_query(
+    pattern: &str,
+    analyzer: &mut TextAnalyzer,
+    field: Field,
+) -> Result<Box<dyn Query>> {
+    let hir = ParserBuilder::new()
+        .multi_line(true)
+        .build()
+        .parse(pattern)
+        .map_err(|err| Error::Search(format!("invalid regex pattern: {err}")))?;
+
+    for kind in [ExtractKind::Prefix, ExtractKind::Suffix] {
+        // Small classes such as case variants expand; digit or word classes end the literal.
+        let literals = Extractor::new()
+            .kind(kind)
+            .limit_class(MAX_EXPANDED_CLASS)
+            .extract(&hir);
+        if let Some(query) = literal_query(&literals, analyzer, field)? {
+            return Ok(query);
+        }
+    }
+
+    Ok(Box::new(AllQuery))
+}
+
+fn literal_query(
+    literals: &Seq,
+    analyzer: &mut TextAnalyzer,
+    field: Field,
+) -> Result<Option<Box<dyn Query>>> {
+    let Some(literals) = literals.literals() else {
+        return Ok(None);
+    };
+
+    // Case variants of one literal analyze to the same lowercase tokens.
+    let mut token_sets = BTreeSet::new();
+    for literal in literals {
+        let Ok(text) = std::str::from_utf8(literal.as_bytes()) else {
+            return Ok(None);
+        };
+
+        let mut tokens = Vec::new();
+        let mut stream = analyzer.token_stream(text);
+        stream.process(&mut |token| {
+            if token.text.len() < MAX_TOKEN_BYTES {
+                tokens.push(token.text.clone());
+            }
+        });
+        if tokens.is_empty() {
+            return Ok(None);
+        }
+        token_sets.insert(tokens);
+    }
+
+    let mut alternatives = Vec::with_capacity(token_sets.len());
+    for tokens in token_sets {
+        let mut clauses = Vec::with_capacity(tokens.len());
+        for token in tokens {
+            let contains = format!(".*{}.*", regex::escape(&token));
+            let query: Box<dyn Query> = Box::new(RegexQuery::from_pattern(&contains, field)?);
+            clauses.push((Occur::Must, query));
+        }
+        alternatives.push((Occur::Should, single_or_boolean(clauses)));
+    }
+
+    Ok((!alternatives.is_empty()).then(|| single_or_boolean(alternatives)))
+}
+
+fn single_or_boolean(mut clauses: Vec<(Occur, Box<dyn Query>)>) -> Box<dyn Query> {
+    if clauses.len() == 1 {
+        clauses.remove(0).1
+    } else {
+        Box::new(BooleanQuery::new(clauses))
+    }
+}
+
+/// Returns every line touched by a non-empty match of `regex` in `text`.
+///
+/// Matches spanning line breaks contribute a range to each line they cover.
+pub(crate) fn line_matches(regex: &Regex, text: &str) -> Vec<LineMatch> {
+    let line_starts: Vec<usize> = std::iter::once(0)
+        .chain(text.match_indices('\n').map(|(index, _)| index + 1))
+        .collect();
+    let line_end = |line_index: usize| {
+        let end = line_starts
+            .get(line_index + 1)
+            .map_or(text.len(), |next| next - 1);
+        if text[..end].ends_with('\r') {
+            end - 1
+        } else {
+            end
+        }
+    };
+
+    let mut lines: Vec<LineMatch> = Vec::new();
+    for found in regex.find_iter(text).filter(|found| !found.is_empty()) {
+        let mut line_index = line_starts.partition_point(|start| *start <= found.start()) - 1;
+        while line_index < line_starts.len() && line_starts[line_index] < found.end() {
+            let line_start = line_starts[line_index];
+            let end = line_end(line_index);
+            let range =
+                found.start().max(line_start) - line_start..found.end().min(end) - line_start;
+
+            if !range.is_empty() {
+                match lines.last_mut() {
+                    Some(last) if last.line_number == line_index + 1 => last.ranges.push(range),
+                    _ => {
+                        let line = &text[line_start..end];
+                        lines.push(LineMatch {
+                            line_number: line_index + 1,
+                            column: line[..range.start].chars().count() + 1,
+                            line: line.to_string(),
+                            ranges: vec![range],
+                        });
+                    }
+                }
+            }
+            line_index += 1;
+        }
+    }
+
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::folding_analyzer;
+    use tantivy::schema::{Schema, TEXT};
+
+    fn analyzer() -> TextAnalyzer {
+        folding_analyzer()
+    }
+
+    #[test]
+    fn line_matches_report_line_numbers_columns_and_ranges() {
+        let regex = compile(r"ERR-\d{4}:", MatchOptions::default()).expect("compile");
+        let text = "ok\r\nfirst ERR-1001: disk\nfine\nÉ ERR-2002: net ERR-3003: dns";
+        let lines = line_matches(&regex, text);
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].line_number, 2);
+        assert_eq!(lines[0].column, 7);
+        assert_eq!(lines[0].line, "first ERR-1001: disk");
+        assert_eq!(lines[0].ranges, vec![6..15]);
+        assert_eq!(lines[1].line_number, 4);
+        assert_eq!(lines[1].column, 3);
+        assert_eq!(lines[1].ranges.len(), 2);
+    }
+
+    #[test]
+    fn matches_spanning_lines_mark_each_line() {
+        let regex = compile(r"foo\s+bar", MatchOptions::default()).expect("compile");
+        let lines = line_matches(&regex, "x foo\nbar y");
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].ranges, vec![2..5]);
+        assert_eq!(lines[1].ranges, vec![0..3]);
+    }
+
+    #[test]
+    fn patterns_ignore_case_unless_case_sensitive() {
+        let text = "id = 1\nID = 2";
+
+        let lines = line_matches(
+            &compile("ID", MatchOptions::default()).expect("compile"),
+            text,
+        );
+        assert_eq!(lines.len(), 2);
+
+        let case_sensitive = MatchOptions {
+            case_sensitive: true,
+            ..MatchOptions::default()
+        };
+        let lines = line_matches(&compile("ID", case_sensitive).expect("compile"), text);
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0].line_number, 2);
+
+        let lines = line_matches(
+            &compile("(?-i)ID", MatchOptions::default()).expect("compile"),
+            text,
+        );
+        assert_eq!(lines.len(), 1);
+    }
+
+    #[test]
+    fn candidate_query_requires_literal_tokens() {
+        let mut schema_builder = Schema::builder();
+        let content = schema_builder.add_text_field("content", TEXT);
+
+        let query = candidate_query(r"ERR-\d{4}:", &mut analyzer(), content).expect("build");
+        assert!(query.is::<RegexQuery>());
+
+        let query = candidate_query(r"(?i)alpha gamma", &mut analyzer(), content).expect("build");
+        let boolean = query
+            .downcast_ref::<BooleanQuery>()
+            .expect("token conjunction");
+        assert_eq!(boolean.clauses().len(), 2);
+
+        let query = candidate_query(r"\w+\s*=", &mut analyzer(), content).expect("build");
+        assert!(query.is::<AllQuery>());
+    }
+}
@@ -0,0 +1,179 @@
+use regex::Regex;
+
+use crate::error::{Error, Result};
+use crate::search::query::QueryNode;
+use crate::search::{MatchOptions, TermMatching};
+
+/// Checks hits of the folded index against the original text, for case-sensitive and
+/// whole-word search.
+pub(crate) enum LiteralMatcher {
+    Pattern(Regex),
+    /// Field filters, which the index query and the catalog already enforce.
+    Any,
+    And(Vec<LiteralMatcher>),
+    Or(Vec<LiteralMatcher>),
+    Not(Box<LiteralMatcher>),
+}
+
+impl LiteralMatcher {
+    /// Literal form of a fuzzy query's free-text clauses.
+    ///
+    /// Terms match as typed instead of within an edit distance, starting a word. Terms
+    /// allowing prefixes may run on into a longer word unless `options.whole_word` is set.
+    pub(crate) fn fuzzy(
+        node: &QueryNode,
+        matching: TermMatching,
+        options: MatchOptions,
+    ) -> Result<Self> {
+        let case_sensitive = options.case_sensitive;
+        Ok(match node {
+            QueryNode::Term { text, modifiers } => {
+                let whole_word = options.whole_word || !matching.with_modifiers(*modifiers).prefix;
+                Self::Pattern(words_regex(text, case_sensitive, whole_word)?)
+            }
+            QueryNode::Phrase { text, slop: 0 } => {
+                Self::Pattern(words_regex(text, case_sensitive, true)?)
+            }
+            QueryNode::Phrase { text, .. } => Self::And(
+                text.split_whitespace()
+                    .map(|word| words_regex(word, case_sensitive, true).map(Self::Pattern))
+                    .collect::<Result<_>>()?,
+            ),
+            QueryNode::Near { left, right, .. } => Self::And(vec![
+                Self::Pattern(words_regex(left, case_sensitive, true)?),
+                Self::Pattern(words_regex(right, case_sensitive, true)?),
+            ]),
+            QueryNode::Field(_) => Self::Any,
+            QueryNode::And(children) => Self::And(
+                children
+                    .iter()
+                    .map(|child| Self::fuzzy(child, matching, options))
+                    .collect::<Result<_>>()?,
+            ),
+            QueryNode::Or(children) => Self::Or(
+                children
+                    .iter()
+                    .map(|child| Self::fuzzy(child, matching, options))
+                    .collect::<Result<_>>()?,
+            ),
+            QueryNode::Not(inner) => Self::Not(Box::new(Self::fuzzy(inner, matching, options)?)),
+        })
+    }
+
+    /// Literal form of a regex-mode pattern, which always matches whole words.
+    pub(crate) fn regex(pattern: &str, options: MatchOptions) -> Result<Self> {
+        let whole_words = MatchOptions {
+            whole_word: true,
+            ..options
+        };
+        pattern_regex(pattern, whole_words).map(Self::Pattern)
+    }
+
+    pub(crate) fn is_match(&self, text: &str) -> bool {
+        match self {
+            Self::Pattern(regex) => regex.is_match(text),
+            Self::Any => true,
+            Self::And(children) => children.iter().all(|child| child.is_match(text)),
+            Self::Or(children) => children.iter().any(|child| child.is_match(text)),
+            Self::Not(inner) => !inner.is_match(text),
+        }
+    }
+
+    /// One regex matching any non-negated pattern, for highlighting; `None` when every
+    /// pattern is negated.
+    pub(crate) fn highlight_regex(&self) -> Option<Regex> {
+        let mut patterns = Vec::new();
+        self.collect_positive_patterns(&mut patterns);
+        if patterns.is_empty() {
+            return None;
+        }
+        // Each pattern scopes its own case flag, so the alternation keeps them apart.
+        Regex::new(&patterns.join("|")).ok()
+    }
+
+    fn collect_positive_patterns<'a>(&'a self, patterns: &mut Vec<&'a str>) {
+        match self {
+            Self::Pattern(regex) => patterns.push(regex.as_str()),
+            Self::And(children) | Self::Or(children) => children
+                .iter()
+                .for_each(|child| child.collect_positive_patterns(patterns)),
+            Self::Any | Self::Not(_) => {}
+        }
+    }
+}
+
+/// Compiles a user regex with the case and whole-word flags of `options`.
+pub(crate) fn pattern_regex(pattern: &str, options: MatchOptions) -> Result<Regex> {
+    let body = if options.whole_word {
+        format!(r"\b(?:{pattern})\b")
+    } else {
+        pattern.to_string()
+    };
+    Regex::new(&scoped_case(&body, options.case_sensitive))
+        .map_err(|err| Error::Search(format!("invalid regex pattern: {err}")))
+}
+
+/// Matches the words of `text` in order, separated by anything but word characters.
+fn words_regex(text: &str, case_sensitive: bool, whole_word: bool) -> Result<Regex> {
+    let body = text
+        .split_whitespace()
+        .map(regex::escape)
+        .collect::<Vec<_>>()
+        .join(r"\W+");
+    let is_word_char = |ch: char| ch.is_alphanumeric() || ch == '_';
+    let mut bounded = String::new();
+    if text.trim_start().starts_with(is_word_char) {
+        bounded.push_str(r"\b");
+    }
+    bounded.push_str(&body);
+    if whole_word && text.trim_end().ends_with(is_word_char) {
+        bounded.push_str(r"\b");
+    }
+    Regex::new(&scoped_case(&bounded, case_sensitive))
+        .map_err(|err| Error::Search(format!("invalid literal pattern: {err}")))
+}
+
+fn scoped_case(body: &str, case_sensitive: bool) -> String {
+    let flag = if case_sensitive { "-i" } else { "i" };
+    format!("(?{flag}:{body})")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::query::parse;
+
+    fn fuzzy(query_text: &str, case_sensitive: bool, whole_word: bool) -> LiteralMatcher {
+        let node = parse(query_text).expect("parse query");
+        let options = MatchOptions {
+            case_sensitive,
+            whole_word,
+        };
+        LiteralMatcher::fuzzy(&node, TermMatching::default(), options).expect("build matcher")
+    }
+
+    #[test]
+    fn case_and_whole_word_flags_constrain_terms() {
+        let text = "user ID and IDs, id_token";
+        assert!(fuzzy("ID", true, false).is_match(text));
+        assert!(!fuzzy("Id", true, false).is_match(text));
+        assert!(fuzzy("Id", false, false).is_match(text));
+        assert!(fuzzy("IDs", true, true).is_match(text));
+        assert!(!fuzzy("token", false, true).is_match(text));
+        assert!(!fuzzy("ID -IDs", true, false).is_match(text));
+        assert!(fuzzy("\"user id\"", false, true).is_match(text));
+    }
+
+    #[test]
+    fn highlight_regex_skips_negated_terms() {
+        let regex = fuzzy("ID -user", true, true)
+            .highlight_regex()
+            .expect("highlight regex");
+        let found: Vec<&str> = regex
+            .find_iter("user ID id")
+            .map(|found| found.as_str())
+            .collect();
+        assert_eq!(found, vec!["ID"]);
+        assert!(fuzzy("-ID", true, false).highlight_regex().is_none());
+    }
+}
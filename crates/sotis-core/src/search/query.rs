@@ -0,0 +1,474 @@
+use std::ops::{Bound, RangeBounds};
+
+use nucleo_matcher::pattern::{CaseMatching, Normalization, Pattern};
+use nucleo_matcher::{Matcher, Utf32Str};
+use tantivy::query::{
+    AllQuery, BooleanQuery, BoostQuery, EmptyQuery, FuzzyTermQuery, Occur, PhraseQuery, Query,
+    RangeQuery, RegexQuery, TermQuery,
+};
+use tantivy::schema::{Field, IndexRecordOption};
+use tantivy::tokenizer::TextAnalyzer;
+use tantivy::Term;
+
+use crate::analysis::{contains_cjk, fold, is_cjk_single_char};
+use crate::error::Result;
+use crate::schema::Fields;
+use crate::search::{DocData, TermMatching};
+
+mod parser;
+pub(crate) use parser::parse;
+
+const ADJACENCY_BOOST: f32 = 2.0;
+
+/// Parsed fuzzy-mode query.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum QueryNode {
+    /// Free-text term, fuzzy-matched against content and filenames.
+    Term {
+        text: String,
+        modifiers: TermModifiers,
+    },
+    /// Quoted phrase matched in order; `slop` allows that many extra positions (`"a b"~2`).
+    Phrase {
+        text: String,
+        slop: u32,
+    },
+    /// `left NEAR/distance right`: both sides within `distance` words, in either order.
+    Near {
+        left: String,
+        right: String,
+        distance: u32,
+    },
+    Field(FieldFilter),
+    And(Vec<QueryNode>),
+    Or(Vec<QueryNode>),
+    Not(Box<QueryNode>),
+}
+
+/// Per-term overrides of the request's [`TermMatching`]; `None` keeps its default.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct TermModifiers {
+    /// Set by `term~n`, or to 0 by `=term`.
+    pub(crate) distance: Option<u8>,
+    /// Enabled by `term*`, disabled by `=term`.
+    pub(crate) prefix: Option<bool>,
+}
+
+impl TermModifiers {
+    pub(crate) const EXACT: Self = Self {
+        distance: Some(0),
+        prefix: Some(false),
+    };
+}
+
+/// Field-prefixed clause such as `ext:pdf` or `size:<5mb`.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum FieldFilter {
+    Ext(String),
+    /// Case-sensitive substring of the full path.
+    Path(String),
+    /// Fuzzy term over the filename only.
+    Name(String),
+    Modified(U64Range),
+    Size(U64Range),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct U64Range {
+    pub(crate) lower: Bound<u64>,
+    pub(crate) upper: Bound<u64>,
+}
+
+impl U64Range {
+    pub(crate) fn contains(&self, value: u64) -> bool {
+        (self.lower.as_ref(), self.upper.as_ref()).contains(&value)
+    }
+
+    pub(crate) fn to_query(&self, field: Field) -> Box<dyn Query> {
+        if matches!(
+            (&self.lower, &self.upper),
+            (Bound::Unbounded, Bound::Unbounded)
+        ) {
+            return Box::new(AllQuery);
+        }
+
+        let to_term = |value: &u64| Term::from_field_u64(field, *value);
+        Box::new(RangeQuery::new(
+            self.lower.as_ref().map(to_term),
+            self.upper.as_ref().map(to_term),
+        ))
+    }
+}
+
+impl QueryNode {
+    /// Free-text terms that should be highlighted, skipping negated clauses.
+    pub(crate) fn positive_terms(&self) -> Vec<&str> {
+        self.positive_terms_with_modifiers()
+            .into_iter()
+            .map(|(text, _)| text)
+            .collect()
+    }
+
+    /// [`Self::positive_terms`] with the overrides each term carries; phrase and
+    /// `NEAR` words always match exactly.
+    pub(crate) fn positive_terms_with_modifiers(&self) -> Vec<(&str, TermModifiers)> {
+        let mut terms = Vec::new();
+        self.collect_positive_terms(&mut terms);
+        terms
+    }
+
+    fn collect_positive_terms<'a>(&'a self, terms: &mut Vec<(&'a str, TermModifiers)>) {
+        match self {
+            Self::Term { text, modifiers } => terms.push((text, *modifiers)),
+            Self::Phrase { text, .. } => terms.push((text, TermModifiers::EXACT)),
+            Self::Near { left, right, .. } => {
+                terms.push((left, TermModifiers::EXACT));
+                terms.push((right, TermModifiers::EXACT));
+            }
+            Self::And(children) | Self::Or(children) => children
+                .iter()
+                .for_each(|child| child.collect_positive_terms(terms)),
+            Self::Field(_) | Self::Not(_) => {}
+        }
+    }
+}
+
+/// Translates a [`QueryNode`] into a Tantivy query over the indexed fields.
+pub(crate) struct ContentQueryBuilder<'a> {
+    pub(crate) fields: &'a Fields,
+    pub(crate) content_analyzer: TextAnalyzer,
+    pub(crate) filename_analyzer: TextAnalyzer,
+    /// Stemming analyzers for the languages present in the index.
+    pub(crate) stemmers: Vec<TextAnalyzer>,
+    pub(crate) term_matching: TermMatching,
+}
+
+impl ContentQueryBuilder<'_> {
+    pub(crate) fn build(&mut self, node: &QueryNode) -> Result<Box<dyn Query>> {
+        Ok(match node {
+            QueryNode::Term { text, modifiers } => {
+                let matching = self.term_matching.with_modifiers(*modifiers);
+                let terms = analyze(&mut self.content_analyzer, self.fields.content, text);
+                let query = word_query(terms, matching)?;
+                // Stems are ANDed rather than positioned, which would undo CJK adjacency.
+                if matching.is_exact() || contains_cjk(text) {
+                    query
+                } else {
+                    self.with_stemmed_alternatives(query, text)
+                }
+            }
+            QueryNode::Phrase { text, slop } => {
+                let mut terms = analyze(&mut self.content_analyzer, self.fields.content, text);
+                match terms.len() {
+                    0 => Box::new(EmptyQuery),
+                    1 => exact_term_query(terms.remove(0))?,
+                    _ => proximity_query(vec![terms], *slop),
+                }
+            }
+            QueryNode::Near {
+                left,
+                right,
+                distance,
+            } => {
+                let left = analyze(&mut self.content_analyzer, self.fields.content, left);
+                let right = analyze(&mut self.content_analyzer, self.fields.content, right);
+                if left.is_empty() || right.is_empty() {
+                    Box::new(EmptyQuery)
+                } else {
+                    let forward = [left.as_slice(), right.as_slice()].concat();
+                    let backward = [right.as_slice(), left.as_slice()].concat();
+                    proximity_query(vec![forward, backward], *distance)
+                }
+            }
+            QueryNode::Field(filter) => self.build_filter(filter)?,
+            QueryNode::And(children) => {
+                let mut clauses = Vec::with_capacity(children.len());
+                for child in children {
+                    clauses.push(match child {
+                        QueryNode::Not(inner) => (Occur::MustNot, self.build(inner)?),
+                        _ => (Occur::Must, self.build(child)?),
+                    });
+                }
+                if clauses.iter().all(|(occur, _)| *occur == Occur::MustNot) {
+                    clauses.push((Occur::Must, Box::new(AllQuery)));
+                }
+                Box::new(BooleanQuery::new(clauses))
+            }
+            QueryNode::Or(children) => {
+                let mut clauses = Vec::with_capacity(children.len());
+                for child in children {
+                    clauses.push((Occur::Should, self.build(child)?));
+                }
+                Box::new(BooleanQuery::new(clauses))
+            }
+            QueryNode::Not(inner) => Box::new(BooleanQuery::new(vec![
+                (Occur::Must, Box::new(AllQuery) as Box<dyn Query>),
+                (Occur::MustNot, self.build(inner)?),
+            ])),
+        })
+    }
+
+    /// Also matches `text` by its stems in each indexed language.
+    fn with_stemmed_alternatives(&mut self, query: Box<dyn Query>, text: &str) -> Box<dyn Query> {
+        let mut stem_sets: Vec<Vec<Term>> = Vec::new();
+        for stemmer in &mut self.stemmers {
+            let stems = analyze(stemmer, self.fields.content_stemmed, text);
+            if !stems.is_empty() && !stem_sets.contains(&stems) {
+                stem_sets.push(stems);
+            }
+        }
+        if stem_sets.is_empty() {
+            return query;
+        }
+
+        let mut clauses = vec![(Occur::Should, query)];
+        for stems in stem_sets {
+            let mut required: Vec<(Occur, Box<dyn Query>)> = stems
+                .into_iter()
+                .map(|stem| {
+                    let query = TermQuery::new(stem, IndexRecordOption::WithFreqs);
+                    (Occur::Must, Box::new(query) as Box<dyn Query>)
+                })
+                .collect();
+            let stemmed = if required.len() == 1 {
+                required.remove(0).1
+            } else {
+                Box::new(BooleanQuery::new(required))
+            };
+            clauses.push((Occur::Should, stemmed));
+        }
+        Box::new(BooleanQuery::new(clauses))
+    }
+
+    fn build_filter(&mut self, filter: &FieldFilter) -> Result<Box<dyn Query>> {
+        Ok(match filter {
+            FieldFilter::Ext(ext) => Box::new(TermQuery::new(
+                Term::from_field_text(self.fields.ext, ext),
+                IndexRecordOption::Basic,
+            )),
+            FieldFilter::Path(fragment) => Box::new(RegexQuery::from_pattern(
+                &format!(".*{}.*", regex::escape(fragment)),
+                self.fields.path,
+            )?),
+            FieldFilter::Name(text) => {
+                let terms = analyze(&mut self.filename_analyzer, self.fields.filename, text);
+                fuzzy_conjunction(terms, self.term_matching)?
+            }
+            FieldFilter::Modified(range) => range.to_query(self.fields.modified),
+            FieldFilter::Size(range) => range.to_query(self.fields.size),
+        })
+    }
+}
+
+fn analyze(analyzer: &mut TextAnalyzer, field: Field, text: &str) -> Vec<Term> {
+    let mut terms = Vec::new();
+    let mut stream = analyzer.token_stream(text);
+    stream.process(&mut |token| terms.push(Term::from_field_text(field, &token.text)));
+    terms
+}
+
+/// Matches any of the term sequences within `slop` positions and boosts exact adjacency,
+/// so tight matches outrank scattered ones.
+fn proximity_query(sequences: Vec<Vec<Term>>, slop: u32) -> Box<dyn Query> {
+    let phrases = |slop: u32| -> Box<dyn Query> {
+        let clauses = sequences
+            .iter()
+            .map(|terms| {
+                let positioned = terms.iter().cloned().enumerate().collect();
+                let phrase = PhraseQuery::new_with_offset_and_slop(positioned, slop);
+                (Occur::Should, Box::new(phrase) as Box<dyn Query>)
+            })
+            .collect();
+        Box::new(BooleanQuery::new(clauses))
+    };
+
+    if slop == 0 && sequences.len() == 1 {
+        return phrases(0);
+    }
+
+    Box::new(BooleanQuery::new(vec![
+        (Occur::Must, phrases(slop)),
+        (
+            Occur::Should,
+            Box::new(BoostQuery::new(phrases(0), ADJACENCY_BOOST)),
+        ),
+    ]))
+}
+
+/// Requires every term of one query word. Consecutive CJK bigrams must also be adjacent,
+/// as in the text they came from, so `東京都` does not match `東京` and `京都` apart.
+fn word_query(terms: Vec<Term>, matching: TermMatching) -> Result<Box<dyn Query>> {
+    let mut words = Vec::new();
+    let mut runs: Vec<Vec<Term>> = Vec::new();
+    let mut in_run = false;
+    for term in terms {
+        let cjk = term.value().as_str().is_some_and(contains_cjk);
+        match runs.last_mut() {
+            Some(run) if cjk && in_run => run.push(term),
+            _ if cjk => runs.push(vec![term]),
+            _ => words.push(term),
+        }
+        in_run = cjk;
+    }
+
+    let mut clauses = Vec::new();
+    for mut run in runs {
+        if run.len() == 1 {
+            words.push(run.remove(0));
+        } else {
+            clauses.push((Occur::Must, proximity_query(vec![run], 0)));
+        }
+    }
+    if !words.is_empty() || clauses.is_empty() {
+        clauses.push((Occur::Must, fuzzy_conjunction(words, matching)?));
+    }
+
+    Ok(if clauses.len() == 1 {
+        clauses.remove(0).1
+    } else {
+        Box::new(BooleanQuery::new(clauses))
+    })
+}
+
+/// Requires every term, allowing the typos and prefixes `matching` permits except on CJK
+/// bigrams, where a single edit would match unrelated words.
+fn fuzzy_conjunction(terms: Vec<Term>, matching: TermMatching) -> Result<Box<dyn Query>> {
+    let mut clauses: Vec<(Occur, Box<dyn Query>)> = Vec::with_capacity(terms.len());
+    for term in terms {
+        let exact = matching.is_exact() || term.value().as_str().is_some_and(contains_cjk);
+        let query: Box<dyn Query> = if exact {
+            exact_term_query(term)?
+        } else if matching.prefix {
+            Box::new(FuzzyTermQuery::new_prefix(term, matching.distance, true))
+        } else {
+            Box::new(FuzzyTermQuery::new(term, matching.distance, true))
+        };
+        clauses.push((Occur::Must, query));
+    }
+
+    Ok(match clauses.len() {
+        0 => Box::new(EmptyQuery),
+        1 => clauses.remove(0).1,
+        _ => Box::new(BooleanQuery::new(clauses)),
+    })
+}
+
+/// Matches `term` as is, except that a lone CJK character also matches the bigrams of
+/// longer runs containing it.
+fn exact_term_query(term: Term) -> Result<Box<dyn Query>> {
+    match term
+        .value()
+        .as_str()
+        .filter(|text| is_cjk_single_char(text))
+    {
+        Some(text) => {
+            let ch = regex::escape(text);
+            let pattern = format!("{ch}.?|.{ch}");
+            Ok(Box::new(RegexQuery::from_pattern(&pattern, term.field())?))
+        }
+        None => Ok(Box::new(TermQuery::new(term, IndexRecordOption::WithFreqs))),
+    }
+}
+
+/// Evaluates a [`QueryNode`] against filename and stored metadata with nucleo scoring.
+pub(crate) enum FilenameMatcher {
+    Pattern(Pattern),
+    /// Folded words that must all appear whole in the filename, for exact terms.
+    Words(Vec<String>),
+    Filter(FieldFilter),
+    And(Vec<FilenameMatcher>),
+    Or(Vec<FilenameMatcher>),
+    Not(Box<FilenameMatcher>),
+}
+
+impl FilenameMatcher {
+    pub(crate) fn new(node: &QueryNode, matching: TermMatching) -> Self {
+        match node {
+            QueryNode::Term { text, modifiers } => {
+                if matching.with_modifiers(*modifiers).is_exact() {
+                    Self::Words(filename_words(text))
+                } else {
+                    Self::Pattern(pattern(text))
+                }
+            }
+            QueryNode::Phrase { text, .. } => Self::Pattern(pattern(text)),
+            QueryNode::Near { left, right, .. } => Self::And(vec![
+                Self::Pattern(pattern(left)),
+                Self::Pattern(pattern(right)),
+            ]),
+            QueryNode::Field(FieldFilter::Name(text)) => Self::Pattern(pattern(text)),
+            QueryNode::Field(filter) => Self::Filter(filter.clone()),
+            QueryNode::And(children) => Self::And(
+                children
+                    .iter()
+                    .map(|child| Self::new(child, matching))
+                    .collect(),
+            ),
+            QueryNode::Or(children) => Self::Or(
+                children
+                    .iter()
+                    .map(|child| Self::new(child, matching))
+                    .collect(),
+            ),
+            QueryNode::Not(inner) => Self::Not(Box::new(Self::new(inner, matching))),
+        }
+    }
+
+    /// Returns the filename score, or `None` when the document does not match.
+    ///
+    /// Filters and negations contribute a constant score of 1 so that filter-only
+    /// queries still list every matching file.
+    pub(crate) fn score(
+        &self,
+        doc: &DocData,
+        matcher: &mut Matcher,
+        scratch: &mut Vec<char>,
+    ) -> Option<f32> {
+        match self {
+            Self::Pattern(pattern) => {
+                let haystack = Utf32Str::new(&doc.filename, scratch);
+                pattern.score(haystack, matcher).map(|score| score as f32)
+            }
+            Self::Words(words) => {
+                let filename = filename_words(&doc.filename);
+                let matched = !words.is_empty() && words.iter().all(|word| filename.contains(word));
+                matched.then_some(1.0)
+            }
+            Self::Filter(filter) => filter_matches(filter, doc).then_some(1.0),
+            Self::And(children) => children
+                .iter()
+                .map(|child| child.score(doc, matcher, scratch))
+                .sum(),
+            Self::Or(children) => {
+                let scores: Vec<f32> = children
+                    .iter()
+                    .filter_map(|child| child.score(doc, matcher, scratch))
+                    .collect();
+                (!scores.is_empty()).then(|| scores.iter().sum())
+            }
+            Self::Not(inner) => inner.score(doc, matcher, scratch).is_none().then_some(1.0),
+        }
+    }
+}
+
+fn pattern(text: &str) -> Pattern {
+    Pattern::parse(text, CaseMatching::Ignore, Normalization::Smart)
+}
+
+fn filename_words(text: &str) -> Vec<String> {
+    fold(text)
+        .split(|ch: char| !ch.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn filter_matches(filter: &FieldFilter, doc: &DocData) -> bool {
+    match filter {
+        FieldFilter::Ext(ext) => doc.ext == *ext,
+        FieldFilter::Path(fragment) => doc.path.to_string_lossy().contains(fragment.as_str()),
+        FieldFilter::Name(_) => true,
+        FieldFilter::Modified(range) => range.contains(doc.modified),
+        FieldFilter::Size(range) => range.contains(doc.size),
+    }
+}
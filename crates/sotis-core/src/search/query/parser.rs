@@ -0,0 +1,697 @@
+use std::ops::Bound;
+
+use crate::error::{Error, Result};
+use crate::search::query::{FieldFilter, QueryNode, TermModifiers, U64Range};
+use crate::search::MAX_FUZZY_DISTANCE;
+
+const SECONDS_PER_DAY: u64 = 86_400;
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    Word(String),
+    Phrase { text: String, slop: u32 },
+    Field { name: String, value: String },
+    And,
+    Or,
+    Not,
+    Near(u32),
+    LeftParen,
+    RightParen,
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    position: usize,
+}
+
+/// Parse a fuzzy-mode query string into a [`QueryNode`] tree.
+///
+/// Grammar, loosest binding first: `OR`, then `AND`, then juxtaposition, then
+/// `NEAR/n`, then `NOT`/`-`. Juxtaposed free-text items are alternatives (matching the
+/// historic whitespace-OR behaviour), while juxtaposed field filters and negations are
+/// required.
+///
+/// Free-text words take matching modifiers: `=word` is exact, `word~n` allows `n` typos
+/// and `word*` also matches longer words.
+pub(crate) fn parse(query_text: &str) -> Result<QueryNode> {
+    let tokens = tokenize(query_text)?;
+    let mut parser = Parser {
+        tokens,
+        cursor: 0,
+        end: query_text.len(),
+    };
+
+    let node = parser.parse_or()?;
+    if let Some(token) = parser.peek() {
+        return Err(syntax_error(
+            token.position,
+            "unmatched closing parenthesis",
+        ));
+    }
+    Ok(node)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    cursor: usize,
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.cursor)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.cursor).cloned();
+        self.cursor += 1;
+        token
+    }
+
+    fn position(&self) -> usize {
+        self.peek().map_or(self.end, |token| token.position)
+    }
+
+    fn parse_or(&mut self) -> Result<QueryNode> {
+        let mut items = vec![self.parse_and()?];
+        while self.peek().is_some_and(|token| token.kind == TokenKind::Or) {
+            self.next();
+            items.push(self.parse_and()?);
+        }
+        Ok(collapse(items, QueryNode::Or))
+    }
+
+    fn parse_and(&mut self) -> Result<QueryNode> {
+        let mut items = vec![self.parse_sequence()?];
+        while self
+            .peek()
+            .is_some_and(|token| token.kind == TokenKind::And)
+        {
+            self.next();
+            items.push(self.parse_sequence()?);
+        }
+        Ok(collapse(items, QueryNode::And))
+    }
+
+    fn parse_sequence(&mut self) -> Result<QueryNode> {
+        let mut items = Vec::new();
+        while let Some(token) = self.peek() {
+            if matches!(
+                token.kind,
+                TokenKind::And | TokenKind::Or | TokenKind::RightParen
+            ) {
+                break;
+            }
+            items.push(self.parse_near()?);
+        }
+
+        if items.is_empty() {
+            return Err(syntax_error(self.position(), "expected a search term"));
+        }
+
+        let (required, text): (Vec<QueryNode>, Vec<QueryNode>) = items
+            .into_iter()
+            .partition(|node| matches!(node, QueryNode::Field(_) | QueryNode::Not(_)));
+        let mut clauses = required;
+        if !text.is_empty() {
+            clauses.push(collapse(text, QueryNode::Or));
+        }
+        Ok(collapse(clauses, QueryNode::And))
+    }
+
+    fn parse_near(&mut self) -> Result<QueryNode> {
+        let left = self.parse_unary()?;
+        let Some(&Token {
+            kind: TokenKind::Near(distance),
+            position,
+        }) = self.peek()
+        else {
+            return Ok(left);
+        };
+        self.next();
+
+        if self
+            .peek()
+            .is_none_or(|token| matches!(token.kind, TokenKind::RightParen))
+        {
+            return Err(syntax_error(position, "expected a term after NEAR"));
+        }
+        let right = self.parse_unary()?;
+        if self
+            .peek()
+            .is_some_and(|token| matches!(token.kind, TokenKind::Near(_)))
+        {
+            return Err(syntax_error(
+                self.position(),
+                "NEAR cannot be chained; use parentheses and AND",
+            ));
+        }
+
+        match (left, right) {
+            (
+                QueryNode::Term { text: left, .. } | QueryNode::Phrase { text: left, .. },
+                QueryNode::Term { text: right, .. } | QueryNode::Phrase { text: right, .. },
+            ) => Ok(QueryNode::Near {
+                left,
+                right,
+                distance,
+            }),
+            _ => Err(syntax_error(
+                position,
+                "NEAR operands must be terms or quoted phrases",
+            )),
+        }
+    }
+
+    fn parse_unary(&mut self) -> Result<QueryNode> {
+        if self
+            .peek()
+            .is_some_and(|token| token.kind == TokenKind::Not)
+        {
+            let not = self.next().expect("peeked token");
+            if self
+                .peek()
+                .is_none_or(|token| matches!(token.kind, TokenKind::RightParen))
+            {
+                return Err(syntax_error(not.position, "expected a term after NOT"));
+            }
+            return Ok(QueryNode::Not(Box::new(self.parse_unary()?)));
+        }
+
+        self.parse_primary()
+    }
+
+    fn parse_primary(&mut self) -> Result<QueryNode> {
+        let position = self.position();
+        let Some(token) = self.next() else {
+            return Err(syntax_error(position, "expected a search term"));
+        };
+
+        match token.kind {
+            TokenKind::Word(text) => parse_term(text, token.position),
+            TokenKind::Phrase { text, slop } => Ok(QueryNode::Phrase { text, slop }),
+            TokenKind::Field { name, value } => {
+                parse_field(&name, &value, token.position).map(QueryNode::Field)
+            }
+            TokenKind::LeftParen => {
+                let inner = self.parse_or()?;
+                match self.next() {
+                    Some(Token {
+                        kind: TokenKind::RightParen,
+                        ..
+                    }) => Ok(inner),
+                    _ => Err(syntax_error(token.position, "missing closing parenthesis")),
+                }
+            }
+            TokenKind::And | TokenKind::Or => Err(syntax_error(
+                token.position,
+                "operator is missing a left-hand term",
+            )),
+            TokenKind::Near(_) => Err(syntax_error(
+                token.position,
+                "NEAR is missing a left-hand term",
+            )),
+            TokenKind::Not | TokenKind::RightParen => {
+                Err(syntax_error(token.position, "expected a search term"))
+            }
+        }
+    }
+}
+
+fn collapse(mut items: Vec<QueryNode>, combine: fn(Vec<QueryNode>) -> QueryNode) -> QueryNode {
+    if items.len() == 1 {
+        items.remove(0)
+    } else {
+        combine(items)
+    }
+}
+
+fn tokenize(query_text: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = query_text.char_indices().peekable();
+
+    while let Some(&(position, ch)) = chars.peek() {
+        if ch.is_whitespace() {
+            chars.next();
+            continue;
+        }
+
+        let kind = match ch {
+            '(' => {
+                chars.next();
+                TokenKind::LeftParen
+            }
+            ')' => {
+                chars.next();
+                TokenKind::RightParen
+            }
+            '"' => {
+                let text = read_quoted(query_text, position, &mut chars)?;
+                let slop = read_phrase_slop(query_text, &mut chars)?;
+                TokenKind::Phrase { text, slop }
+            }
+            '-' if query_text[position + 1..]
+                .chars()
+                .next()
+                .is_some_and(|next| !next.is_whitespace() && next != ')') =>
+            {
+                chars.next();
+                TokenKind::Not
+            }
+            _ => read_word(query_text, position, &mut chars)?,
+        };
+        tokens.push(Token { kind, position });
+    }
+
+    Ok(tokens)
+}
+
+fn read_quoted(
+    query_text: &str,
+    start: usize,
+    chars: &mut std::iter::Peekable<std::str::CharIndices<'_>>,
+) -> Result<String> {
+    chars.next();
+    for (index, ch) in chars.by_ref() {
+        if ch == '"' {
+            return Ok(query_text[start + 1..index].to_string());
+        }
+    }
+    Err(syntax_error(start, "unterminated quoted phrase"))
+}
+
+/// Reads an optional `~n` suffix directly after a closing quote.
+fn read_phrase_slop(
+    query_text: &str,
+    chars: &mut std::iter::Peekable<std::str::CharIndices<'_>>,
+) -> Result<u32> {
+    let Some(&(tilde, '~')) = chars.peek() else {
+        return Ok(0);
+    };
+    chars.next();
+
+    let mut end = query_text.len();
+    while let Some(&(index, ch)) = chars.peek() {
+        if !ch.is_ascii_digit() {
+            end = index;
+            break;
+        }
+        chars.next();
+    }
+
+    query_text[tilde + 1..end]
+        .parse()
+        .map_err(|_| syntax_error(tilde, "expected a number after '~'"))
+}
+
+fn read_word(
+    query_text: &str,
+    start: usize,
+    chars: &mut std::iter::Peekable<std::str::CharIndices<'_>>,
+) -> Result<TokenKind> {
+    let mut end = query_text.len();
+    while let Some(&(index, ch)) = chars.peek() {
+        if ch.is_whitespace() || matches!(ch, '(' | ')' | '"') {
+            end = index;
+            break;
+        }
+
+        if ch == ':' && is_field_name(&query_text[start..index]) {
+            let name = query_text[start..index].to_ascii_lowercase();
+            chars.next();
+            let value = match chars.peek() {
+                Some(&(quote_start, '"')) => read_quoted(query_text, quote_start, chars)?,
+                _ => match read_word(query_text, index + 1, chars)? {
+                    TokenKind::Word(value) => value,
+                    _ => {
+                        return Err(syntax_error(index + 1, "invalid field value"));
+                    }
+                },
+            };
+            return Ok(TokenKind::Field { name, value });
+        }
+
+        chars.next();
+    }
+
+    let word = &query_text[start..end];
+    if let Some(distance) = word.strip_prefix("NEAR/") {
+        return distance
+            .parse()
+            .map(TokenKind::Near)
+            .map_err(|_| syntax_error(start + 5, "expected a number after 'NEAR/'"));
+    }
+
+    Ok(match word {
+        "AND" | "&&" => TokenKind::And,
+        "OR" | "||" => TokenKind::Or,
+        "NOT" => TokenKind::Not,
+        _ => TokenKind::Word(word.to_string()),
+    })
+}
+
+/// Splits `=`, `~n` and `*` modifiers off a free-text word.
+fn parse_term(word: String, position: usize) -> Result<QueryNode> {
+    let mut text = word.as_str();
+    let mut modifiers = TermModifiers::default();
+
+    if let Some(rest) = text.strip_prefix('=').filter(|rest| !rest.is_empty()) {
+        text = rest;
+        modifiers = TermModifiers::EXACT;
+    } else if let Some(rest) = text.strip_suffix('*').filter(|rest| !rest.is_empty()) {
+        text = rest;
+        modifiers.prefix = Some(true);
+    } else if let Some((rest, distance)) =
+        text.rsplit_once('~').filter(|(rest, _)| !rest.is_empty())
+    {
+        let tilde = position + rest.len();
+        let distance: u8 = distance
+            .parse()
+            .map_err(|_| syntax_error(tilde, "expected a number after '~'"))?;
+        if distance > MAX_FUZZY_DISTANCE {
+            return Err(syntax_error(
+                tilde,
+                &format!("edit distance cannot exceed {MAX_FUZZY_DISTANCE}"),
+            ));
+        }
+        text = rest;
+        modifiers.distance = Some(distance);
+    }
+
+    Ok(QueryNode::Term {
+        text: text.to_string(),
+        modifiers,
+    })
+}
+
+fn is_field_name(prefix: &str) -> bool {
+    matches!(
+        prefix.to_ascii_lowercase().as_str(),
+        "ext" | "path" | "name" | "modified" | "size"
+    )
+}
+
+fn parse_field(name: &str, value: &str, position: usize) -> Result<FieldFilter> {
+    let value_position = position + name.len() + 1;
+    if value.trim().is_empty() {
+        return Err(syntax_error(
+            value_position,
+            &format!("'{name}:' needs a value"),
+        ));
+    }
+
+    match name {
+        "ext" => Ok(FieldFilter::Ext(
+            value.trim_start_matches('.').to_ascii_lowercase(),
+        )),
+        "path" => Ok(FieldFilter::Path(value.to_string())),
+        "name" => Ok(FieldFilter::Name(value.to_string())),
+        "modified" => {
+            parse_range(value, value_position, parse_date_range).map(FieldFilter::Modified)
+        }
+        "size" => parse_range(value, value_position, parse_size_range).map(FieldFilter::Size),
+        _ => Err(syntax_error(position, &format!("unknown field '{name}'"))),
+    }
+}
+
+/// Parses an optional comparison operator followed by a value that covers `[start, end)`.
+fn parse_range(
+    value: &str,
+    position: usize,
+    parse_value: fn(&str) -> Option<(u64, u64)>,
+) -> Result<U64Range> {
+    let (operator, operand) = [">=", "<=", ">", "<", "="]
+        .iter()
+        .find_map(|operator| value.strip_prefix(operator).map(|rest| (*operator, rest)))
+        .unwrap_or(("", value));
+
+    let Some((start, end)) = parse_value(operand) else {
+        return Err(syntax_error(
+            position + operator.len(),
+            &format!("invalid or out-of-range value '{operand}'"),
+        ));
+    };
+
+    let (lower, upper) = match operator {
+        ">" => (Bound::Included(end), Bound::Unbounded),
+        ">=" => (Bound::Included(start), Bound::Unbounded),
+        "<" => (Bound::Unbounded, Bound::Excluded(start)),
+        "<=" => (Bound::Unbounded, Bound::Excluded(end)),
+        _ => (Bound::Included(start), Bound::Excluded(end)),
+    };
+    Ok(U64Range { lower, upper })
+}
+
+/// Parses `YYYY-MM-DD` into the unix-seconds span of that UTC day.
+fn parse_date_range(value: &str) -> Option<(u64, u64)> {
+    let mut parts = value.splitn(3, '-');
+    let year: i64 = parts.next()?.parse().ok()?;
+    let month: u32 = parts.next()?.parse().ok()?;
+    let day: u32 = parts.next()?.parse().ok()?;
+    if !(1970..=9999).contains(&year) || !(1..=12).contains(&month) || day == 0 {
+        return None;
+    }
+    if day > days_in_month(year, month) {
+        return None;
+    }
+
+    let start = u64::try_from(days_from_civil(year, month, day)).ok()? * SECONDS_PER_DAY;
+    Some((start, start + SECONDS_PER_DAY))
+}
+
+/// Parses sizes like `512`, `10kb`, `1.5mb` or `2g` (binary units) into a byte span;
+/// `None` for sizes too large to represent.
+fn parse_size_range(value: &str) -> Option<(u64, u64)> {
+    let lower = value.to_ascii_lowercase();
+    let split = lower
+        .find(|ch: char| !(ch.is_ascii_digit() || ch == '.'))
+        .unwrap_or(lower.len());
+    let (number, unit) = lower.split_at(split);
+    let multiplier: u64 = match unit {
+        "" | "b" => 1,
+        "k" | "kb" => 1 << 10,
+        "m" | "mb" => 1 << 20,
+        "g" | "gb" => 1 << 30,
+        _ => return None,
+    };
+
+    let amount: f64 = number.parse().ok()?;
+    if !amount.is_finite() || amount < 0.0 {
+        return None;
+    }
+    // Sizes past u64 would saturate to u64::MAX and overflow the span's end.
+    let bytes = amount * multiplier as f64;
+    if bytes >= u64::MAX as f64 {
+        return None;
+    }
+    let bytes = bytes as u64;
+    Some((bytes, bytes.checked_add(1)?))
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if (year % 4 == 0 && year % 100 != 0) || year % 400 == 0 => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days since 1970-01-01 for a proleptic Gregorian date (Howard Hinnant's algorithm).
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = i64::from(month);
+    let day_of_year =
+        (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+fn syntax_error(position: usize, message: &str) -> Error {
+    Error::QuerySyntax {
+        position,
+        message: message.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn term(text: &str) -> QueryNode {
+        term_with(text, TermModifiers::default())
+    }
+
+    fn term_with(text: &str, modifiers: TermModifiers) -> QueryNode {
+        QueryNode::Term {
+            text: text.to_string(),
+            modifiers,
+        }
+    }
+
+    fn error_position(query_text: &str) -> usize {
+        match parse(query_text) {
+            Err(Error::QuerySyntax { position, .. }) => position,
+            other => panic!("expected syntax error for {query_text:?}, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn juxtaposed_terms_are_alternatives() {
+        assert_eq!(
+            parse("alpha beta").expect("parse"),
+            QueryNode::Or(vec![term("alpha"), term("beta")])
+        );
+    }
+
+    #[test]
+    fn operators_follow_precedence_and_parentheses() {
+        assert_eq!(
+            parse("a OR b AND NOT c").expect("parse"),
+            QueryNode::Or(vec![
+                term("a"),
+                QueryNode::And(vec![term("b"), QueryNode::Not(Box::new(term("c")))]),
+            ])
+        );
+        assert_eq!(
+            parse("(a OR b) AND -c").expect("parse"),
+            QueryNode::And(vec![
+                QueryNode::Or(vec![term("a"), term("b")]),
+                QueryNode::Not(Box::new(term("c"))),
+            ])
+        );
+    }
+
+    #[test]
+    fn field_filters_are_required_next_to_free_text() {
+        assert_eq!(
+            parse("report invoice ext:.PDF").expect("parse"),
+            QueryNode::And(vec![
+                QueryNode::Field(FieldFilter::Ext("pdf".to_string())),
+                QueryNode::Or(vec![term("report"), term("invoice")]),
+            ])
+        );
+        assert_eq!(
+            parse(r#"path:"my docs/" name:report"#).expect("parse"),
+            QueryNode::And(vec![
+                QueryNode::Field(FieldFilter::Path("my docs/".to_string())),
+                QueryNode::Field(FieldFilter::Name("report".to_string())),
+            ])
+        );
+    }
+
+    #[test]
+    fn quoted_phrases_and_unknown_prefixes_stay_text() {
+        assert_eq!(
+            parse(r#""quarterly revenue" 10:30"#).expect("parse"),
+            QueryNode::Or(vec![
+                QueryNode::Phrase {
+                    text: "quarterly revenue".to_string(),
+                    slop: 0,
+                },
+                term("10:30"),
+            ])
+        );
+    }
+
+    #[test]
+    fn near_operator_and_phrase_slop_parse() {
+        assert_eq!(
+            parse(r#"budget NEAR/3 "revenue forecast" "profit margin"~2"#).expect("parse"),
+            QueryNode::Or(vec![
+                QueryNode::Near {
+                    left: "budget".to_string(),
+                    right: "revenue forecast".to_string(),
+                    distance: 3,
+                },
+                QueryNode::Phrase {
+                    text: "profit margin".to_string(),
+                    slop: 2,
+                },
+            ])
+        );
+        assert_eq!(error_position("a NEAR/x b"), 7);
+        assert_eq!(error_position("a NEAR/2 ext:pdf"), 2);
+        assert_eq!(error_position("a NEAR/2 b NEAR/2 c"), 11);
+        assert_eq!(error_position(r#""a b"~z"#), 5);
+    }
+
+    #[test]
+    fn term_modifiers_set_exactness_distance_and_prefix() {
+        assert_eq!(
+            parse("=cat cat~2 cat* = cat~0").expect("parse"),
+            QueryNode::Or(vec![
+                term_with("cat", TermModifiers::EXACT),
+                term_with(
+                    "cat",
+                    TermModifiers {
+                        distance: Some(2),
+                        prefix: None,
+                    }
+                ),
+                term_with(
+                    "cat",
+                    TermModifiers {
+                        distance: None,
+                        prefix: Some(true),
+                    }
+                ),
+                term("="),
+                term_with(
+                    "cat",
+                    TermModifiers {
+                        distance: Some(0),
+                        prefix: None,
+                    }
+                ),
+            ])
+        );
+        assert_eq!(error_position("cat~3"), 3);
+        assert_eq!(error_position("alpha cat~"), 9);
+    }
+
+    #[test]
+    fn modified_and_size_filters_parse_to_ranges() {
+        let QueryNode::Field(FieldFilter::Modified(range)) =
+            parse("modified:>2025-01-01").expect("parse")
+        else {
+            panic!("expected modified filter");
+        };
+        assert_eq!(range.lower, Bound::Included(1_735_776_000));
+        assert_eq!(range.upper, Bound::Unbounded);
+
+        let QueryNode::Field(FieldFilter::Size(range)) = parse("size:<5mb").expect("parse") else {
+            panic!("expected size filter");
+        };
+        assert_eq!(range.lower, Bound::Unbounded);
+        assert_eq!(range.upper, Bound::Excluded(5 * 1_048_576));
+    }
+
+    #[test]
+    fn syntax_errors_point_at_the_offending_position() {
+        assert_eq!(error_position("(alpha beta"), 0);
+        assert_eq!(error_position("alpha)"), 5);
+        assert_eq!(error_position("alpha AND"), 9);
+        assert_eq!(error_position("OR alpha"), 0);
+        assert_eq!(error_position(r#"alpha "beta"#), 6);
+        assert_eq!(error_position("modified:2025-13-01"), 9);
+        assert_eq!(error_position("size:>=lots"), 7);
+        assert_eq!(error_position("ext: pdf"), 4);
+    }
+
+    #[test]
+    fn oversized_sizes_are_syntax_errors_instead_of_overflowing() {
+        assert_eq!(error_position("size:99999999999999999999gb"), 5);
+        assert_eq!(error_position("report size:>99999999999999999999gb"), 13);
+        assert_eq!(error_position("size:<=18446744073709551616"), 7);
+
+        let QueryNode::Field(FieldFilter::Size(range)) = parse("size:<=16777215gb").expect("parse")
+        else {
+            panic!("expected size filter");
+        };
+        assert_eq!(range.upper, Bound::Excluded(16_777_215 * (1 << 30) + 1));
+    }
+}
@@ -0,0 +1,188 @@
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::{FolderEntry, RankingProfile};
+use crate::search::SearchMode;
+
+const SECONDS_PER_DAY: f32 = 86_400.0;
+
+/// Smallest folder priority applied, so files in a folder at priority 0 still rank last.
+const MIN_FOLDER_PRIORITY: f32 = f32::EPSILON;
+
+pub(crate) fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+/// Combines normalized channel scores using a [`RankingProfile`] and folder priorities.
+pub(crate) struct Ranker<'a> {
+    profile: &'a RankingProfile,
+    folders: &'a [FolderEntry],
+    now: u64,
+}
+
+impl<'a> Ranker<'a> {
+    /// Ranks with recency measured back from `now`, the same clock the query's
+    /// modified-date filters and facets use.
+    pub(crate) fn new(profile: &'a RankingProfile, folders: &'a [FolderEntry], now: u64) -> Self {
+        Self {
+            profile,
+            folders,
+            now,
+        }
+    }
+
+    /// Final score for a document; zero means the document did not match.
+    pub(crate) fn score(
+        &self,
+        search_mode: SearchMode,
+        content_score: f32,
+        filename_score: f32,
+        path: &Path,
+        modified: u64,
+    ) -> f32 {
+        let (content_weight, filename_weight) = self.weights(search_mode);
+        let base = content_weight * content_score + filename_weight * filename_score;
+        if base <= 0.0 {
+            return 0.0;
+        }
+
+        base * self.recency_factor(modified) * self.folder_factor(path)
+    }
+
+    /// Content and filename weights; outside combined mode only the searched channel counts.
+    pub(crate) fn weights(&self, search_mode: SearchMode) -> (f32, f32) {
+        match search_mode {
+            SearchMode::Combined => (self.profile.content_weight, self.profile.filename_weight),
+            SearchMode::FilenameOnly => (0.0, 1.0),
+            SearchMode::ContentOnly => (1.0, 0.0),
+        }
+    }
+
+    pub(crate) fn recency_factor(&self, modified: u64) -> f32 {
+        let profile = self.profile;
+        if profile.recency_boost <= 0.0 || profile.recency_half_life_days <= 0.0 {
+            return 1.0;
+        }
+
+        let age_days = self.now.saturating_sub(modified) as f32 / SECONDS_PER_DAY;
+        1.0 + profile.recency_boost * 0.5_f32.powf(age_days / profile.recency_half_life_days)
+    }
+
+    pub(crate) fn folder_factor(&self, path: &Path) -> f32 {
+        // Nested folder entries: the most specific one wins.
+        let Some(folder) = self
+            .folders
+            .iter()
+            .filter(|folder| path.starts_with(&folder.path))
+            .max_by_key(|folder| folder.path.components().count())
+        else {
+            return 1.0;
+        };
+
+        let mut factor = 1.0;
+        if self.profile.depth_boost > 0.0 {
+            let depth = path.strip_prefix(&folder.path).map_or(0, |relative| {
+                relative.components().count().saturating_sub(1)
+            });
+            factor *= 1.0 + self.profile.depth_boost / (1 + depth) as f32;
+        }
+        if self.profile.use_folder_priority {
+            // Priorities demote rather than hide: a zero factor would fail the positive
+            // score check and drop every hit in the folder.
+            factor *= folder.priority.max(MIN_FOLDER_PRIORITY);
+        }
+        factor
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    fn folder(path: &str, priority: f32) -> FolderEntry {
+        FolderEntry {
+            path: PathBuf::from(path),
+            recursive: true,
+            extensions: Vec::new(),
+            priority,
+        }
+    }
+
+    #[test]
+    fn default_profile_keeps_historic_weights() {
+        let profile = RankingProfile::default();
+        let ranker = Ranker::new(&profile, &[], 0);
+        let score = ranker.score(SearchMode::Combined, 1.0, 0.5, Path::new("/x/a.txt"), 0);
+
+        assert!((score - 0.85).abs() < 1e-6);
+    }
+
+    #[test]
+    fn recency_boost_halves_each_half_life() {
+        let profile = RankingProfile {
+            recency_boost: 1.0,
+            recency_half_life_days: 10.0,
+            ..RankingProfile::default()
+        };
+        let ranker = Ranker::new(&profile, &[], 100 * SECONDS_PER_DAY as u64);
+
+        let path = Path::new("/x/a.txt");
+        let fresh = ranker.score(SearchMode::ContentOnly, 1.0, 0.0, path, ranker.now);
+        let older = ranker.score(SearchMode::ContentOnly, 1.0, 0.0, path, 90 * 86_400);
+
+        assert!((fresh - 2.0).abs() < 1e-6);
+        assert!((older - 1.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn non_positive_priorities_demote_without_hiding() {
+        let profile = RankingProfile::default();
+        let folders = [folder("/zero", 0.0), folder("/negative", -2.0)];
+        let ranker = Ranker::new(&profile, &folders, 0);
+
+        for path in ["/zero/a.txt", "/negative/b.txt"] {
+            let score = ranker.score(SearchMode::ContentOnly, 1.0, 0.0, Path::new(path), 0);
+            assert!(score > 0.0 && score < 1.0, "{path} scored {score}");
+        }
+    }
+
+    #[test]
+    fn folder_priority_and_depth_use_most_specific_folder() {
+        let profile = RankingProfile {
+            depth_boost: 1.0,
+            ..RankingProfile::default()
+        };
+        let folders = [folder("/docs", 1.0), folder("/docs/work", 3.0)];
+        let ranker = Ranker::new(&profile, &folders, 0);
+
+        let shallow = ranker.score(
+            SearchMode::ContentOnly,
+            1.0,
+            0.0,
+            Path::new("/docs/a.txt"),
+            0,
+        );
+        let work = ranker.score(
+            SearchMode::ContentOnly,
+            1.0,
+            0.0,
+            Path::new("/docs/work/sub/b.txt"),
+            0,
+        );
+        let outside = ranker.score(
+            SearchMode::ContentOnly,
+            1.0,
+            0.0,
+            Path::new("/tmp/c.txt"),
+            0,
+        );
+
+        assert!((shallow - 2.0).abs() < 1e-6);
+        assert!((work - 4.5).abs() < 1e-6);
+        assert!((outside - 1.0).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,312 @@
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::ops::Bound;
+use std::path::{Path, PathBuf};
+
+use tantivy::query::Query;
+use tantivy::schema::Field;
+
+use crate::config::{FolderEntry, GeneralConfig, RankingProfile};
+use crate::error::{Error, Result};
+use crate::search::query::{TermModifiers, U64Range};
+use crate::search::{FacetFilters, Facets, QueryMode, SearchMode, SearchResult};
+
+/// Page size used when a [`SearchRequest`] does not set one.
+pub const DEFAULT_PAGE_SIZE: usize = 100;
+
+/// Largest edit distance a fuzzy term may allow.
+pub const MAX_FUZZY_DISTANCE: u8 = 2;
+
+/// A query plus the page of ranked results to return.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchRequest {
+    pub query_text: String,
+    pub query_mode: QueryMode,
+    pub search_mode: SearchMode,
+    /// Number of ranked results to skip.
+    pub offset: usize,
+    /// Maximum number of results in the page.
+    pub limit: usize,
+    /// Weights and boosts used to order results.
+    pub ranking: RankingProfile,
+    /// Indexed folders, for folder facets, priorities and depth boosts.
+    pub folders: Vec<FolderEntry>,
+    /// Facet selections, applied in the index query; facet counts ignore them.
+    pub filters: FacetFilters,
+    /// File size bounds in bytes, applied in the index query.
+    pub size: RangeFilter,
+    /// Modification time bounds in unix seconds, applied in the index query.
+    pub modified: RangeFilter,
+    /// Folders results must lie under, applied in the index query; empty searches the
+    /// whole index.
+    pub scopes: Vec<PathBuf>,
+    /// Default typo and prefix tolerance of free-text terms in fuzzy mode.
+    pub term_matching: TermMatching,
+    /// Case and whole-word constraints checked against the original text.
+    pub match_options: MatchOptions,
+    /// Index snapshot the page must come from, once resumed from a [`PageToken`].
+    snapshot: Option<u64>,
+}
+
+impl SearchRequest {
+    /// First page of [`DEFAULT_PAGE_SIZE`] results.
+    pub fn new(
+        query_text: impl Into<String>,
+        query_mode: QueryMode,
+        search_mode: SearchMode,
+    ) -> Self {
+        Self {
+            query_text: query_text.into(),
+            query_mode,
+            search_mode,
+            offset: 0,
+            limit: DEFAULT_PAGE_SIZE,
+            ranking: RankingProfile::default(),
+            folders: Vec::new(),
+            filters: FacetFilters::default(),
+            size: RangeFilter::default(),
+            modified: RangeFilter::default(),
+            scopes: Vec::new(),
+            term_matching: TermMatching::default(),
+            match_options: MatchOptions::default(),
+            snapshot: None,
+        }
+    }
+
+    /// Moves the request to an explicit page of whatever the index holds now.
+    pub fn with_page(mut self, offset: usize, limit: usize) -> Self {
+        self.offset = offset;
+        self.limit = limit;
+        self.snapshot = None;
+        self
+    }
+
+    pub fn with_ranking(mut self, ranking: RankingProfile, folders: &[FolderEntry]) -> Self {
+        self.ranking = ranking;
+        self.folders = folders.to_vec();
+        self
+    }
+
+    pub fn with_filters(mut self, filters: FacetFilters) -> Self {
+        self.filters = filters;
+        self
+    }
+
+    pub fn with_size(mut self, size: RangeFilter) -> Self {
+        self.size = size;
+        self
+    }
+
+    pub fn with_modified(mut self, modified: RangeFilter) -> Self {
+        self.modified = modified;
+        self
+    }
+
+    /// Limits results to files under any of `scopes`, such as configured folders or
+    /// subdirectories of them.
+    pub fn with_scopes(mut self, scopes: Vec<PathBuf>) -> Self {
+        self.scopes = scopes;
+        self
+    }
+
+    pub(crate) fn in_scope(&self, path: &Path) -> bool {
+        self.scopes.is_empty() || self.scopes.iter().any(|scope| path.starts_with(scope))
+    }
+
+    pub fn with_term_matching(mut self, term_matching: TermMatching) -> Self {
+        self.term_matching = term_matching;
+        self
+    }
+
+    pub fn with_match_options(mut self, match_options: MatchOptions) -> Self {
+        self.match_options = match_options;
+        self
+    }
+
+    /// Moves the request to the page `token` points at.
+    ///
+    /// Fails when the token was issued for a different query, mode, ranking profile,
+    /// folder setup, filter selection or range. Searching the resumed request fails with
+    /// [`Error::StalePage`] once the index has changed since the token's page.
+    pub fn after(mut self, token: &PageToken) -> Result<Self> {
+        if token.fingerprint != self.fingerprint() {
+            return Err(Error::Search(
+                "page token does not belong to this query".to_string(),
+            ));
+        }
+
+        self.offset = token.offset;
+        self.limit = token.limit;
+        self.snapshot = Some(token.snapshot);
+        Ok(self)
+    }
+
+    /// Fails with [`Error::StalePage`] when the request was resumed from a token issued
+    /// against another index snapshot.
+    pub(crate) fn check_snapshot(&self, snapshot: u64) -> Result<()> {
+        match self.snapshot {
+            Some(expected) if expected != snapshot => Err(Error::StalePage),
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn token_at(&self, offset: usize, snapshot: u64) -> PageToken {
+        PageToken {
+            offset,
+            limit: self.limit,
+            fingerprint: self.fingerprint(),
+            snapshot,
+        }
+    }
+
+    fn fingerprint(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        (
+            &self.query_text,
+            self.query_mode,
+            self.search_mode,
+            &self.filters,
+            self.size,
+            self.modified,
+            &self.scopes,
+            self.term_matching,
+            self.match_options,
+        )
+            .hash(&mut hasher);
+        hash_ranking(&self.ranking, &mut hasher);
+        for folder in &self.folders {
+            (&folder.path, folder.recursive, &folder.extensions).hash(&mut hasher);
+            folder.priority.to_bits().hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+}
+
+/// Feeds every field of `ranking` to `hasher`; the weights are floats, so it cannot
+/// derive [`Hash`].
+fn hash_ranking(ranking: &RankingProfile, hasher: &mut DefaultHasher) {
+    ranking.name.hash(hasher);
+    for weight in [
+        ranking.content_weight,
+        ranking.filename_weight,
+        ranking.recency_boost,
+        ranking.recency_half_life_days,
+        ranking.depth_boost,
+    ] {
+        weight.to_bits().hash(hasher);
+    }
+    ranking.use_folder_priority.hash(hasher);
+}
+
+/// How free-text terms tolerate typos and longer words, unless a term overrides it
+/// with `=term`, `term~n` or `term*`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TermMatching {
+    /// Maximum edit distance, from 0 (exact) to [`MAX_FUZZY_DISTANCE`].
+    pub distance: u8,
+    /// Whether a term also matches words that start with it.
+    pub prefix: bool,
+}
+
+impl Default for TermMatching {
+    fn default() -> Self {
+        Self {
+            distance: 1,
+            prefix: true,
+        }
+    }
+}
+
+impl TermMatching {
+    /// The configured defaults, with the distance capped at [`MAX_FUZZY_DISTANCE`].
+    pub fn from_config(general: &GeneralConfig) -> Self {
+        Self {
+            distance: general.fuzzy_distance.min(MAX_FUZZY_DISTANCE),
+            prefix: general.fuzzy_prefix,
+        }
+    }
+
+    /// Whether only the term itself matches.
+    pub fn is_exact(&self) -> bool {
+        self.distance == 0 && !self.prefix
+    }
+
+    pub(crate) fn with_modifiers(self, modifiers: TermModifiers) -> Self {
+        Self {
+            distance: modifiers
+                .distance
+                .unwrap_or(self.distance)
+                .min(MAX_FUZZY_DISTANCE),
+            prefix: modifiers.prefix.unwrap_or(self.prefix),
+        }
+    }
+}
+
+/// Constraints the folded index cannot answer, checked against the original text.
+///
+/// With either flag set, fuzzy terms match as typed rather than within an edit distance.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct MatchOptions {
+    /// Match letter case exactly, so `ID` does not find `id`.
+    pub case_sensitive: bool,
+    /// Match whole words only, so `id` does not find `identity`.
+    pub whole_word: bool,
+}
+
+impl MatchOptions {
+    /// Whether hits need checking against the original text.
+    pub fn is_literal(&self) -> bool {
+        self.case_sensitive || self.whole_word
+    }
+}
+
+/// Inclusive bounds on an indexed numeric field; `None` leaves that side open.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct RangeFilter {
+    pub min: Option<u64>,
+    pub max: Option<u64>,
+}
+
+impl RangeFilter {
+    pub fn new(min: Option<u64>, max: Option<u64>) -> Self {
+        Self { min, max }
+    }
+
+    pub fn is_unbounded(&self) -> bool {
+        self.min.is_none() && self.max.is_none()
+    }
+
+    pub fn contains(&self, value: u64) -> bool {
+        self.min.is_none_or(|min| value >= min) && self.max.is_none_or(|max| value <= max)
+    }
+
+    pub(crate) fn to_query(self, field: Field) -> Box<dyn Query> {
+        U64Range {
+            lower: self.min.map_or(Bound::Unbounded, Bound::Included),
+            upper: self.max.map_or(Bound::Unbounded, Bound::Included),
+        }
+        .to_query(field)
+    }
+}
+
+/// Opaque cursor to the page following a [`SearchPage`].
+///
+/// Tokens are tied to the query that produced them and to the index snapshot it ran
+/// against, so offsets never shift between pages; they are valid for the running process.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PageToken {
+    offset: usize,
+    limit: usize,
+    fingerprint: u64,
+    snapshot: u64,
+}
+
+/// One page of ranked results.
+#[derive(Debug, Clone, Default)]
+pub struct SearchPage {
+    pub results: Vec<SearchResult>,
+    /// Number of matching documents across all pages.
+    pub total_hits: usize,
+    /// Cursor for the next page; `None` on the last page.
+    pub next_page: Option<PageToken>,
+    pub facets: Facets,
+}
@@ -0,0 +1,34 @@
+use std::path::Path;
+
+use tantivy::query::{BooleanQuery, MoreLikeThisQuery, Occur, Query, TermQuery};
+use tantivy::schema::{IndexRecordOption, OwnedValue};
+use tantivy::Term;
+
+use crate::schema::Fields;
+
+/// Terms shorter than this carry little topical weight.
+const MIN_WORD_CHARS: usize = 3;
+const MAX_QUERY_TERMS: usize = 25;
+
+/// Builds a more-like-this query from the top weighted terms of `text`, excluding the
+/// document at `source`.
+///
+/// Unlike tantivy's defaults, terms found once in one document still count, since
+/// personal collections rarely repeat a topic five times over.
+pub(crate) fn query(fields: &Fields, source: &Path, text: String) -> Box<dyn Query> {
+    let similar = MoreLikeThisQuery::builder()
+        .with_min_doc_frequency(1)
+        .with_min_term_frequency(1)
+        .with_min_word_length(MIN_WORD_CHARS)
+        .with_max_query_terms(MAX_QUERY_TERMS)
+        .with_document_fields(vec![(fields.content, vec![OwnedValue::Str(text)])]);
+    let source = TermQuery::new(
+        Term::from_field_text(fields.path, &source.to_string_lossy()),
+        IndexRecordOption::Basic,
+    );
+
+    Box::new(BooleanQuery::new(vec![
+        (Occur::Must, Box::new(similar) as Box<dyn Query>),
+        (Occur::MustNot, Box::new(source)),
+    ]))
+}
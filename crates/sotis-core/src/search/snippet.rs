@@ -0,0 +1,448 @@
+use std::ops::Range;
+
+use nucleo_matcher::pattern::{CaseMatching, Normalization, Pattern};
+use nucleo_matcher::{Config, Matcher, Utf32Str};
+use regex::Regex;
+use tantivy::tokenizer::TextAnalyzer;
+
+use crate::analysis::{contains_cjk, is_cjk_single_char};
+use crate::error::{Error, Result};
+use crate::search::query::QueryNode;
+use crate::search::TermMatching;
+
+const MAX_SNIPPETS: usize = 3;
+const SNIPPET_CONTEXT_BYTES: usize = 60;
+const MAX_SNIPPET_BYTES: usize = 240;
+
+/// A highlighted excerpt of a document's content.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snippet {
+    /// Excerpt text with line breaks flattened to spaces.
+    pub text: String,
+    /// Byte offset of `text` within the full document content.
+    pub offset: usize,
+    /// Byte ranges of matched spans within `text`.
+    pub highlights: Vec<Range<usize>>,
+}
+
+enum ContentMatcher {
+    /// Analyzed terms with the matching each allows.
+    Fuzzy(Vec<(String, TermMatching)>),
+    Regex(Regex),
+    /// Matches the raw text rather than individual tokens.
+    Grep(Regex),
+}
+
+/// Stems of the query's terms in one language, which the content query also matches.
+struct LanguageStems {
+    language: String,
+    stemmer: TextAnalyzer,
+    stems: Vec<String>,
+}
+
+/// Locates query matches in stored content and filenames for result highlighting.
+pub(crate) struct Highlighter {
+    analyzer: TextAnalyzer,
+    content: ContentMatcher,
+    stems: Vec<LanguageStems>,
+    filename_patterns: Vec<Pattern>,
+    filename_regex: Option<Regex>,
+}
+
+impl Highlighter {
+    /// Highlights the non-negated free-text terms of a parsed fuzzy query, with
+    /// `matching` as the default for terms without modifiers.
+    ///
+    /// Like the content query, inexact non-CJK terms also match words sharing their stem
+    /// in a document's detected language, using `stemmers` keyed by language code.
+    pub(crate) fn fuzzy(
+        mut analyzer: TextAnalyzer,
+        node: &QueryNode,
+        matching: TermMatching,
+        stemmers: Vec<(String, TextAnalyzer)>,
+    ) -> Self {
+        let positive_terms = node.positive_terms_with_modifiers();
+        let terms = positive_terms
+            .iter()
+            .flat_map(|(text, modifiers)| {
+                let term_matching = matching.with_modifiers(*modifiers);
+                analyzed_terms(&mut analyzer, text)
+                    .into_iter()
+                    .map(move |term| (term, term_matching))
+            })
+            .collect();
+        let filename_patterns = positive_terms
+            .iter()
+            .map(|(text, _)| Pattern::parse(text, CaseMatching::Ignore, Normalization::Smart))
+            .collect();
+
+        let stemmed_terms: Vec<&str> = positive_terms
+            .iter()
+            .filter(|(text, modifiers)| {
+                !matching.with_modifiers(*modifiers).is_exact() && !contains_cjk(text)
+            })
+            .map(|(text, _)| *text)
+            .collect();
+        let stems = stemmers
+            .into_iter()
+            .filter_map(|(language, mut stemmer)| {
+                let mut stems: Vec<String> = stemmed_terms
+                    .iter()
+                    .flat_map(|text| analyzed_terms(&mut stemmer, text))
+                    .collect();
+                stems.sort_unstable();
+                stems.dedup();
+                (!stems.is_empty()).then_some(LanguageStems {
+                    language,
+                    stemmer,
+                    stems,
+                })
+            })
+            .collect();
+
+        Self {
+            analyzer,
+            content: ContentMatcher::Fuzzy(terms),
+            stems,
+            filename_patterns,
+            filename_regex: None,
+        }
+    }
+
+    /// Highlights tokens matching a regex query.
+    pub(crate) fn regex(analyzer: TextAnalyzer, pattern: &str) -> Result<Self> {
+        // RegexQuery matches whole terms, so anchor the pattern the same way.
+        let token_regex = Regex::new(&format!("^(?:{pattern})$"))
+            .map_err(|err| Error::Search(format!("invalid regex pattern: {err}")))?;
+        let filename_regex = Regex::new(pattern)
+            .map_err(|err| Error::Search(format!("invalid regex pattern: {err}")))?;
+
+        Ok(Self {
+            analyzer,
+            content: ContentMatcher::Regex(token_regex),
+            stems: Vec::new(),
+            filename_patterns: Vec::new(),
+            filename_regex: Some(filename_regex),
+        })
+    }
+
+    /// Highlights grep matches over the full text, including those across token boundaries.
+    pub(crate) fn grep(analyzer: TextAnalyzer, regex: Regex) -> Self {
+        Self {
+            analyzer,
+            content: ContentMatcher::Grep(regex.clone()),
+            stems: Vec::new(),
+            filename_patterns: Vec::new(),
+            filename_regex: Some(regex),
+        }
+    }
+
+    /// Returns up to [`MAX_SNIPPETS`] excerpts around matched terms in `content`, whose
+    /// detected language is `language`.
+    pub(crate) fn content_snippets(
+        &mut self,
+        content: &str,
+        language: Option<&str>,
+    ) -> Vec<Snippet> {
+        if let ContentMatcher::Grep(regex) = &self.content {
+            let matches: Vec<Range<usize>> = regex
+                .find_iter(content)
+                .filter(|found| !found.is_empty())
+                .map(|found| found.range())
+                .collect();
+            return build_snippets(content, &matches);
+        }
+
+        let mut matches = Vec::new();
+        let content_matcher = &self.content;
+        let mut stream = self.analyzer.token_stream(content);
+        stream.process(&mut |token| {
+            if content_matcher.matches(&token.text) {
+                matches.push(token.offset_from..token.offset_to);
+            }
+        });
+        if let Some(stems) = self
+            .stems
+            .iter_mut()
+            .find(|stems| Some(stems.language.as_str()) == language)
+        {
+            let wanted = &stems.stems;
+            let mut stream = stems.stemmer.token_stream(content);
+            stream.process(&mut |token| {
+                if wanted.binary_search(&token.text).is_ok() {
+                    matches.push(token.offset_from..token.offset_to);
+                }
+            });
+            // Both passes can match the same word.
+            matches.sort_by_key(|range| range.start);
+            matches.dedup_by(|next, previous| next.start < previous.end);
+        }
+
+        build_snippets(content, &matches)
+    }
+
+    /// Returns byte ranges of matched characters within `filename`.
+    pub(crate) fn filename_ranges(&self, filename: &str) -> Vec<Range<usize>> {
+        if let Some(regex) = &self.filename_regex {
+            return regex
+                .find_iter(filename)
+                .filter(|found| !found.is_empty())
+                .map(|found| found.range())
+                .collect();
+        }
+
+        let mut matcher = Matcher::new(Config::DEFAULT.match_paths());
+        let mut scratch = Vec::new();
+        let mut indices = Vec::new();
+        for pattern in &self.filename_patterns {
+            let haystack = Utf32Str::new(filename, &mut scratch);
+            let mut pattern_indices = Vec::new();
+            if pattern
+                .indices(haystack, &mut matcher, &mut pattern_indices)
+                .is_some()
+            {
+                indices.extend(pattern_indices);
+            }
+        }
+        indices.sort_unstable();
+        indices.dedup();
+
+        char_indices_to_ranges(filename, &indices)
+    }
+}
+
+impl ContentMatcher {
+    fn matches(&self, token: &str) -> bool {
+        match self {
+            // Mirrors the term queries `fuzzy_conjunction` builds for content search.
+            Self::Fuzzy(terms) => terms.iter().any(|(term, matching)| {
+                if is_cjk_single_char(term) {
+                    token.contains(term.as_str())
+                } else if matching.is_exact() || contains_cjk(term) {
+                    term == token
+                } else if matching.prefix {
+                    prefix_edit_distance(term, token) <= usize::from(matching.distance)
+                } else {
+                    edit_distance(term, token) <= usize::from(matching.distance)
+                }
+            }),
+            Self::Regex(regex) | Self::Grep(regex) => regex.is_match(token),
+        }
+    }
+}
+
+fn analyzed_terms(analyzer: &mut TextAnalyzer, text: &str) -> Vec<String> {
+    let mut terms = Vec::new();
+    let mut stream = analyzer.token_stream(text);
+    stream.process(&mut |token| terms.push(token.text.clone()));
+    terms
+}
+
+/// Smallest edit distance between `term` and any prefix of `candidate`.
+fn prefix_edit_distance(term: &str, candidate: &str) -> usize {
+    prefix_distances(term, candidate)
+        .into_iter()
+        .min()
+        .unwrap_or(0)
+}
+
+fn edit_distance(term: &str, candidate: &str) -> usize {
+    prefix_distances(term, candidate).pop().unwrap_or_default()
+}
+
+/// Edit distances between `term` and each prefix of `candidate`, shortest prefix first.
+fn prefix_distances(term: &str, candidate: &str) -> Vec<usize> {
+    let term: Vec<char> = term.chars().collect();
+    let candidate: Vec<char> = candidate.chars().collect();
+
+    let mut prev_row: Vec<usize> = (0..=candidate.len()).collect();
+    let mut current_row = vec![0usize; candidate.len() + 1];
+    for (i, term_char) in term.iter().enumerate() {
+        current_row[0] = i + 1;
+        for (j, candidate_char) in candidate.iter().enumerate() {
+            let cost = usize::from(term_char != candidate_char);
+            current_row[j + 1] = (prev_row[j + 1] + 1)
+                .min(current_row[j] + 1)
+                .min(prev_row[j] + cost);
+        }
+        std::mem::swap(&mut prev_row, &mut current_row);
+    }
+
+    prev_row
+}
+
+fn build_snippets(content: &str, matches: &[Range<usize>]) -> Vec<Snippet> {
+    let mut snippets = Vec::new();
+    let mut remaining = matches.iter().peekable();
+
+    while let Some(first) = remaining.next() {
+        if snippets.len() == MAX_SNIPPETS {
+            break;
+        }
+
+        let start = floor_char_boundary(content, first.start.saturating_sub(SNIPPET_CONTEXT_BYTES));
+        let limit = start + MAX_SNIPPET_BYTES;
+        let mut highlights = vec![first.clone()];
+        while let Some(next) = remaining.next_if(|next| next.end <= limit) {
+            highlights.push(next.clone());
+        }
+
+        let last_end = highlights.last().map_or(first.end, |range| range.end);
+        let end = ceil_char_boundary(
+            content,
+            (last_end + SNIPPET_CONTEXT_BYTES).min(limit.max(last_end)),
+        );
+
+        snippets.push(Snippet {
+            text: flatten_whitespace(&content[start..end]),
+            offset: start,
+            highlights: highlights
+                .into_iter()
+                .map(|range| range.start - start..range.end - start)
+                .collect(),
+        });
+    }
+
+    snippets
+}
+
+/// Replaces single-byte whitespace control characters with spaces so byte offsets stay valid.
+fn flatten_whitespace(text: &str) -> String {
+    text.chars()
+        .map(|ch| {
+            if matches!(ch, '\n' | '\r' | '\t') {
+                ' '
+            } else {
+                ch
+            }
+        })
+        .collect()
+}
+
+fn floor_char_boundary(text: &str, mut index: usize) -> usize {
+    index = index.min(text.len());
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+fn ceil_char_boundary(text: &str, mut index: usize) -> usize {
+    index = index.min(text.len());
+    while !text.is_char_boundary(index) {
+        index += 1;
+    }
+    index
+}
+
+fn char_indices_to_ranges(text: &str, char_indices: &[u32]) -> Vec<Range<usize>> {
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    let mut wanted = char_indices.iter().peekable();
+    for (char_index, (byte_index, ch)) in text.char_indices().enumerate() {
+        if wanted
+            .next_if(|index| **index as usize == char_index)
+            .is_none()
+        {
+            continue;
+        }
+
+        let end = byte_index + ch.len_utf8();
+        match ranges.last_mut() {
+            Some(last) if last.end == byte_index => last.end = end,
+            _ => ranges.push(byte_index..end),
+        }
+    }
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::folding_analyzer;
+
+    fn analyzer() -> TextAnalyzer {
+        folding_analyzer()
+    }
+
+    fn fuzzy_highlighter(query_text: &str) -> Highlighter {
+        let node = crate::search::query::parse(query_text).expect("parse query");
+        Highlighter::fuzzy(analyzer(), &node, TermMatching::default(), Vec::new())
+    }
+
+    #[test]
+    fn prefix_edit_distance_matches_fuzzy_prefix_semantics() {
+        assert_eq!(prefix_edit_distance("arch", "architecture"), 0);
+        assert_eq!(prefix_edit_distance("archtecture", "architecture"), 1);
+        assert_eq!(prefix_edit_distance("zzz", "architecture"), 3);
+        assert_eq!(edit_distance("arch", "architecture"), 8);
+        assert_eq!(edit_distance("archtecture", "architecture"), 1);
+    }
+
+    #[test]
+    fn content_snippets_highlight_fuzzy_matches() {
+        let mut highlighter = fuzzy_highlighter("archtecture");
+        let snippets = highlighter.content_snippets("notes on\ndistributed Architecture", None);
+
+        assert_eq!(snippets.len(), 1);
+        let snippet = &snippets[0];
+        assert_eq!(snippet.offset, 0);
+        assert_eq!(snippet.text, "notes on distributed Architecture");
+        assert_eq!(&snippet.text[snippet.highlights[0].clone()], "Architecture");
+    }
+
+    #[test]
+    fn content_snippets_highlight_stem_matches_in_the_document_language() {
+        let node = crate::search::query::parse("running").expect("parse query");
+        let english = crate::analysis::stemming_analyzer("en").expect("english stemmer");
+        let mut highlighter = Highlighter::fuzzy(
+            analyzer(),
+            &node,
+            TermMatching::default(),
+            vec![("en".to_string(), english)],
+        );
+        let content = "she runs every morning";
+
+        let snippets = highlighter.content_snippets(content, Some("en"));
+        assert_eq!(snippets.len(), 1);
+        assert_eq!(snippets[0].highlights, vec![4..8]);
+        assert!(highlighter.content_snippets(content, Some("de")).is_empty());
+        assert!(highlighter.content_snippets(content, None).is_empty());
+    }
+
+    #[test]
+    fn content_snippets_split_distant_matches_into_separate_excerpts() {
+        let filler = "lorem ".repeat(80);
+        let content = format!("needle {filler} needle");
+        let mut highlighter = fuzzy_highlighter("needle");
+        let snippets = highlighter.content_snippets(&content, None);
+
+        assert_eq!(snippets.len(), 2);
+        assert_eq!(
+            snippets[1].offset + snippets[1].highlights[0].start,
+            content.len() - 6
+        );
+    }
+
+    #[test]
+    fn regex_highlighter_matches_whole_tokens() {
+        let mut highlighter =
+            Highlighter::regex(analyzer(), "beta[0-9]{3}").expect("build highlighter");
+        let snippets = highlighter.content_snippets("order beta123 beta1234", None);
+
+        assert_eq!(snippets.len(), 1);
+        assert_eq!(snippets[0].highlights, vec![6..13]);
+        assert_eq!(highlighter.filename_ranges("x-beta123.txt"), vec![2..9]);
+    }
+
+    #[test]
+    fn filename_ranges_returns_fuzzy_match_spans() {
+        let highlighter = fuzzy_highlighter("rprt");
+        let ranges = highlighter.filename_ranges("report.md");
+        let matched: String = ranges
+            .iter()
+            .map(|range| &"report.md"[range.clone()])
+            .collect();
+
+        assert_eq!(matched, "rprt");
+    }
+}
@@ -0,0 +1,93 @@
+use std::sync::mpsc::Sender;
+
+use crate::cancel::CancellationToken;
+use crate::error::{Error, Result};
+use crate::search::{SearchPage, SearchResult};
+
+/// Ranked results sent per [`SearchEvent::Ranked`] batch.
+pub const STREAM_BATCH_SIZE: usize = 20;
+
+/// One step of a streamed search.
+#[derive(Debug, Clone)]
+pub enum SearchEvent {
+    /// Documents a content scan (grep, case-sensitive or whole-word search) has confirmed
+    /// so far, unranked, with a score of 0 and no snippets. Each event adds to the last;
+    /// the ranked page replaces them all.
+    Partial(Vec<SearchResult>),
+    /// The next ranked results of the page, in order, with the page's totals and facets.
+    Ranked(SearchPage),
+}
+
+/// Where a running search reports events and checks for cancellation.
+pub(crate) struct Progress<'a> {
+    cancel: CancellationToken,
+    events: Option<&'a Sender<SearchEvent>>,
+}
+
+impl<'a> Progress<'a> {
+    pub(crate) fn new(cancel: &CancellationToken, events: &'a Sender<SearchEvent>) -> Self {
+        Self {
+            cancel: cancel.clone(),
+            events: Some(events),
+        }
+    }
+
+    /// Progress that reports nothing and is never cancelled.
+    pub(crate) fn silent() -> Self {
+        Self {
+            cancel: CancellationToken::new(),
+            events: None,
+        }
+    }
+
+    /// Progress that shares this one's cancellation but reports nothing.
+    pub(crate) fn quiet(&self) -> Progress<'a> {
+        Self {
+            cancel: self.cancel.clone(),
+            events: None,
+        }
+    }
+
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.cancel.is_cancelled()
+    }
+
+    pub(crate) fn check(&self) -> Result<()> {
+        if self.is_cancelled() {
+            Err(Error::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Sends the results `build` makes, building them only when someone listens.
+    pub(crate) fn partial(&self, build: impl FnOnce() -> Vec<SearchResult>) -> Result<()> {
+        if self.events.is_none() {
+            return Ok(());
+        }
+        let results = build();
+        if results.is_empty() {
+            return Ok(());
+        }
+        self.send(SearchEvent::Partial(results))
+    }
+
+    pub(crate) fn ranked(&self, batch: impl FnOnce() -> SearchPage) -> Result<()> {
+        if self.events.is_none() {
+            return Ok(());
+        }
+        self.send(SearchEvent::Ranked(batch()))
+    }
+
+    /// A dropped receiver means nobody wants the rest, so it cancels the search.
+    fn send(&self, event: SearchEvent) -> Result<()> {
+        let Some(events) = self.events else {
+            return Ok(());
+        };
+        if events.send(event).is_err() {
+            self.cancel.cancel();
+            return Err(Error::Cancelled);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,217 @@
+use std::collections::{HashMap, HashSet};
+
+use tantivy::schema::Field;
+use tantivy::tokenizer::TextAnalyzer;
+use tantivy::{Searcher, Term};
+
+use crate::analysis::contains_cjk;
+use crate::error::Result;
+use crate::search::query::QueryNode;
+
+/// Words up to this many characters allow one edit; longer words allow two.
+const SHORT_WORD_CHARS: usize = 4;
+
+/// An indexed term offered as a correction or completion.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TermSuggestion {
+    pub term: String,
+    /// Number of documents containing the term, summed over the searched fields.
+    pub doc_freq: u64,
+}
+
+/// Indexed terms starting with `prefix`, most frequent first.
+pub(crate) fn completions(
+    searcher: &Searcher,
+    fields: &[Field],
+    prefix: &str,
+    limit: usize,
+) -> Result<Vec<TermSuggestion>> {
+    if prefix.is_empty() || limit == 0 {
+        return Ok(Vec::new());
+    }
+
+    let mut suggestions: Vec<TermSuggestion> =
+        prefixed_terms(searcher, fields, prefix, |term| term != prefix)?
+            .into_iter()
+            .map(|(term, doc_freq)| TermSuggestion { term, doc_freq })
+            .collect();
+    suggestions.sort_by(|left, right| {
+        right
+            .doc_freq
+            .cmp(&left.doc_freq)
+            .then_with(|| left.term.cmp(&right.term))
+    });
+    suggestions.truncate(limit);
+    Ok(suggestions)
+}
+
+/// Indexed terms within a small edit distance of `word`, closest and then most frequent
+/// first.
+///
+/// Only terms sharing the first character are considered, which keeps the dictionary scan
+/// short at the cost of missing typos in that character.
+pub(crate) fn corrections(
+    searcher: &Searcher,
+    fields: &[Field],
+    word: &str,
+    limit: usize,
+) -> Result<Vec<TermSuggestion>> {
+    let chars: Vec<char> = word.chars().collect();
+    let Some(first) = chars.first() else {
+        return Ok(Vec::new());
+    };
+    let max_distance = if chars.len() <= SHORT_WORD_CHARS {
+        1
+    } else {
+        2
+    };
+    let distance = |term: &str| {
+        let candidate: Vec<char> = term.chars().collect();
+        bounded_edit_distance(&chars, &candidate, max_distance)
+    };
+
+    let mut buffer = [0; 4];
+    let candidates = prefixed_terms(searcher, fields, first.encode_utf8(&mut buffer), |term| {
+        term != word && distance(term).is_some()
+    })?;
+    let mut ranked: Vec<(usize, TermSuggestion)> = candidates
+        .into_iter()
+        .filter_map(|(term, doc_freq)| Some((distance(&term)?, TermSuggestion { term, doc_freq })))
+        .collect();
+    ranked.sort_by(|(left_distance, left), (right_distance, right)| {
+        left_distance
+            .cmp(right_distance)
+            .then_with(|| right.doc_freq.cmp(&left.doc_freq))
+            .then_with(|| left.term.cmp(&right.term))
+    });
+    Ok(ranked
+        .into_iter()
+        .take(limit)
+        .map(|(_, suggestion)| suggestion)
+        .collect())
+}
+
+/// Rewrites `query_text` with every free-text word missing from the index replaced by its
+/// best correction, or `None` if nothing was replaced.
+pub(crate) fn corrected_query(
+    searcher: &Searcher,
+    fields: &[Field],
+    analyzer: &mut TextAnalyzer,
+    node: &QueryNode,
+    query_text: &str,
+) -> Result<Option<String>> {
+    let mut words = HashSet::new();
+    for text in node.positive_terms() {
+        analyzer.token_stream(text).process(&mut |token| {
+            words.insert(token.text.clone());
+        });
+    }
+
+    // Field filters and operators are tokenized too, but only free-text words are replaced.
+    let mut tokens = Vec::new();
+    analyzer.token_stream(query_text).process(&mut |token| {
+        if words.contains(&token.text) && !contains_cjk(&token.text) {
+            tokens.push((token.offset_from..token.offset_to, token.text.clone()));
+        }
+    });
+
+    let mut corrected = String::with_capacity(query_text.len());
+    let mut copied_to = 0;
+    for (range, word) in tokens {
+        if doc_freq(searcher, fields, &word)? > 0 {
+            continue;
+        }
+        if let Some(best) = corrections(searcher, fields, &word, 1)?.into_iter().next() {
+            corrected.push_str(&query_text[copied_to..range.start]);
+            corrected.push_str(&best.term);
+            copied_to = range.end;
+        }
+    }
+    if copied_to == 0 {
+        return Ok(None);
+    }
+    corrected.push_str(&query_text[copied_to..]);
+    Ok(Some(corrected))
+}
+
+fn doc_freq(searcher: &Searcher, fields: &[Field], word: &str) -> Result<u64> {
+    let mut total = 0;
+    for &field in fields {
+        total += searcher.doc_freq(&Term::from_field_text(field, word))?;
+    }
+    Ok(total)
+}
+
+/// Sums per-segment document frequencies of the terms starting with `prefix` that `keep`
+/// accepts.
+fn prefixed_terms(
+    searcher: &Searcher,
+    fields: &[Field],
+    prefix: &str,
+    mut keep: impl FnMut(&str) -> bool,
+) -> Result<HashMap<String, u64>> {
+    let mut terms = HashMap::new();
+    for segment_reader in searcher.segment_readers() {
+        for &field in fields {
+            let inverted_index = segment_reader.inverted_index(field)?;
+            let mut stream = inverted_index.terms().range().ge(prefix).into_stream()?;
+            while stream.advance() {
+                if !stream.key().starts_with(prefix.as_bytes()) {
+                    break;
+                }
+                let Ok(term) = std::str::from_utf8(stream.key()) else {
+                    continue;
+                };
+                if keep(term) {
+                    *terms.entry(term.to_string()).or_insert(0) +=
+                        u64::from(stream.value().doc_freq);
+                }
+            }
+        }
+    }
+    Ok(terms)
+}
+
+/// Levenshtein distance between `left` and `right`, or `None` if it exceeds `max`.
+fn bounded_edit_distance(left: &[char], right: &[char], max: usize) -> Option<usize> {
+    if left.len().abs_diff(right.len()) > max {
+        return None;
+    }
+
+    let mut previous: Vec<usize> = (0..=right.len()).collect();
+    let mut current = vec![0; right.len() + 1];
+    for (i, left_char) in left.iter().enumerate() {
+        current[0] = i + 1;
+        for (j, right_char) in right.iter().enumerate() {
+            let substitution = previous[j] + usize::from(left_char != right_char);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        if current.iter().all(|distance| *distance > max) {
+            return None;
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    let distance = previous[right.len()];
+    (distance <= max).then_some(distance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn distance(left: &str, right: &str, max: usize) -> Option<usize> {
+        let left: Vec<char> = left.chars().collect();
+        let right: Vec<char> = right.chars().collect();
+        bounded_edit_distance(&left, &right, max)
+    }
+
+    #[test]
+    fn bounded_edit_distance_stops_past_the_limit() {
+        assert_eq!(distance("architecure", "architecture", 2), Some(1));
+        assert_eq!(distance("reprot", "report", 2), Some(2));
+        assert_eq!(distance("kitten", "sitting", 3), Some(3));
+        assert_eq!(distance("kitten", "sitting", 2), None);
+        assert_eq!(distance("cat", "catalog", 2), None);
+    }
+}
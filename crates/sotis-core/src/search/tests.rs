@@ -1,9 +1,13 @@
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::process;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::sync::mpsc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
+use crate::cancel::CancellationToken;
+use crate::config::{ContentTokenizer, FolderEntry, GeneralConfig, RankingProfile};
 use crate::index::SearchIndex;
+use crate::scanner::ScanResult;
 
 use super::*;
 
@@ -143,6 +147,1291 @@ fn combined_mode_merges_content_and_filename_scores() {
     cleanup_temp_dir(&base);
 }
 
+#[test]
+fn search_results_carry_content_snippets_and_filename_highlights() {
+    let base = unique_temp_dir();
+    let index_dir = base.join("index");
+    fs::create_dir_all(&base).expect("create temp dir");
+
+    let report = base.join("forecast.txt");
+    fs::write(&report, "summary\nquarterly revenue forecast for the board").expect("write file");
+
+    build_index(&index_dir, std::slice::from_ref(&report));
+
+    let engine = SearchEngine::open(&index_dir).expect("open search engine");
+    let results = engine
+        .search("forecast", QueryMode::Fuzzy, SearchMode::Combined, 10)
+        .expect("run combined search");
+
+    assert_eq!(results.len(), 1);
+    let snippet = &results[0].snippets[0];
+    assert_eq!(&snippet.text[snippet.highlights[0].clone()], "forecast");
+    assert_eq!(snippet.offset, 0);
+    assert_eq!(results[0].filename_highlights, vec![0..8]);
+
+    cleanup_temp_dir(&base);
+}
+
+#[test]
+fn boolean_operators_combine_content_terms() {
+    let base = unique_temp_dir();
+    let index_dir = base.join("index");
+    fs::create_dir_all(&base).expect("create temp dir");
+    fs::write(base.join("one.txt"), "alpha beta").expect("write one file");
+    fs::write(base.join("two.txt"), "alpha gamma").expect("write two file");
+    fs::write(base.join("three.txt"), "delta").expect("write three file");
+
+    build_index(
+        &index_dir,
+        &[
+            base.join("one.txt"),
+            base.join("two.txt"),
+            base.join("three.txt"),
+        ],
+    );
+
+    let engine = SearchEngine::open(&index_dir).expect("open search engine");
+    let search = |query: &str| {
+        let mut names: Vec<String> = engine
+            .search(query, QueryMode::Fuzzy, SearchMode::ContentOnly, 10)
+            .expect("run content search")
+            .into_iter()
+            .map(|result| result.filename)
+            .collect();
+        names.sort();
+        names
+    };
+
+    assert_eq!(search("alpha AND NOT gamma"), vec!["one.txt"]);
+    assert_eq!(search("alpha -beta"), vec!["two.txt"]);
+    assert_eq!(
+        search("(beta OR gamma) AND alpha"),
+        vec!["one.txt", "two.txt"]
+    );
+    assert_eq!(search("beta delta"), vec!["one.txt", "three.txt"]);
+
+    cleanup_temp_dir(&base);
+}
+
+#[test]
+fn field_prefixes_filter_content_and_filename_hits() {
+    let base = unique_temp_dir();
+    let index_dir = base.join("index");
+    let nested = base.join("projects");
+    fs::create_dir_all(&nested).expect("create nested dir");
+
+    let markdown = nested.join("report.md");
+    let text = base.join("report.txt");
+    fs::write(&markdown, "report body").expect("write markdown file");
+    fs::write(&text, "report body ".repeat(200)).expect("write text file");
+
+    build_index(&index_dir, &[markdown, text]);
+
+    let engine = SearchEngine::open(&index_dir).expect("open search engine");
+    let only = |query: &str, search_mode: SearchMode| {
+        let results = engine
+            .search(query, QueryMode::Fuzzy, search_mode, 10)
+            .expect("run field search");
+        assert_eq!(results.len(), 1, "query {query:?}");
+        results[0].path.clone()
+    };
+
+    assert!(only("report ext:md", SearchMode::Combined).ends_with("report.md"));
+    assert!(only("report ext:md", SearchMode::FilenameOnly).ends_with("report.md"));
+    assert!(only("body path:projects/", SearchMode::ContentOnly).ends_with("report.md"));
+    assert!(only("name:reprt size:>1kb", SearchMode::Combined).ends_with("report.txt"));
+    assert!(only(
+        "body modified:>2000-01-01 size:<1kb",
+        SearchMode::ContentOnly
+    )
+    .ends_with("report.md"));
+
+    cleanup_temp_dir(&base);
+}
+
+#[test]
+fn quoted_phrases_require_adjacent_terms() {
+    let base = unique_temp_dir();
+    let index_dir = base.join("index");
+    fs::create_dir_all(&base).expect("create temp dir");
+    fs::write(base.join("adjacent.txt"), "the quarterly revenue forecast").expect("write file");
+    fs::write(
+        base.join("scattered.txt"),
+        "revenue in the quarterly report",
+    )
+    .expect("write file");
+
+    build_index(
+        &index_dir,
+        &[base.join("adjacent.txt"), base.join("scattered.txt")],
+    );
+
+    let engine = SearchEngine::open(&index_dir).expect("open search engine");
+    let results = engine
+        .search(
+            "\"quarterly revenue\"",
+            QueryMode::Fuzzy,
+            SearchMode::ContentOnly,
+            10,
+        )
+        .expect("run phrase search");
+
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].filename, "adjacent.txt");
+
+    cleanup_temp_dir(&base);
+}
+
+#[test]
+fn near_matches_within_distance_and_ranks_adjacent_first() {
+    let base = unique_temp_dir();
+    let index_dir = base.join("index");
+    fs::create_dir_all(&base).expect("create temp dir");
+    fs::write(base.join("adjacent.txt"), "budget review meeting notes").expect("write file");
+    fs::write(
+        base.join("nearby.txt"),
+        "review of the annual budget numbers",
+    )
+    .expect("write file");
+    fs::write(
+        base.join("distant.txt"),
+        "budget one two three four five six review",
+    )
+    .expect("write file");
+
+    build_index(
+        &index_dir,
+        &[
+            base.join("adjacent.txt"),
+            base.join("nearby.txt"),
+            base.join("distant.txt"),
+        ],
+    );
+
+    let engine = SearchEngine::open(&index_dir).expect("open search engine");
+    let results = engine
+        .search(
+            "budget NEAR/3 review",
+            QueryMode::Fuzzy,
+            SearchMode::ContentOnly,
+            10,
+        )
+        .expect("run near search");
+    let filenames: Vec<&str> = results.iter().map(|r| r.filename.as_str()).collect();
+    assert_eq!(filenames, vec!["adjacent.txt", "nearby.txt"]);
+
+    let results = engine
+        .search(
+            "\"budget review\"~6",
+            QueryMode::Fuzzy,
+            SearchMode::ContentOnly,
+            10,
+        )
+        .expect("run sloppy phrase search");
+    assert_eq!(results.len(), 3);
+    assert_eq!(results[0].filename, "adjacent.txt");
+
+    let results = engine
+        .search(
+            "\"budget review\"~5",
+            QueryMode::Fuzzy,
+            SearchMode::ContentOnly,
+            10,
+        )
+        .expect("run sloppy phrase search");
+    assert!(results.iter().all(|r| r.filename != "distant.txt"));
+
+    cleanup_temp_dir(&base);
+}
+
+#[test]
+fn filename_catalog_follows_index_updates_between_searches() {
+    let base = unique_temp_dir();
+    let index_dir = base.join("index");
+    fs::create_dir_all(&base).expect("create temp dir");
+    fs::write(base.join("alpha-notes.txt"), "first").expect("write file");
+    fs::write(base.join("alpha-plan.txt"), "second").expect("write file");
+    build_index(
+        &index_dir,
+        &[base.join("alpha-notes.txt"), base.join("alpha-plan.txt")],
+    );
+
+    let engine = SearchEngine::open(&index_dir).expect("open search engine");
+    let search = |query_text: &str| -> Vec<String> {
+        let mut filenames: Vec<String> = engine
+            .search(query_text, QueryMode::Fuzzy, SearchMode::FilenameOnly, 10)
+            .expect("run filename search")
+            .into_iter()
+            .map(|result| result.filename)
+            .collect();
+        filenames.sort();
+        filenames
+    };
+    assert_eq!(search("alpha"), vec!["alpha-notes.txt", "alpha-plan.txt"]);
+
+    fs::write(base.join("alpha-draft.txt"), "third").expect("write file");
+    let mut index = SearchIndex::open(&index_dir).expect("open index");
+    index
+        .remove_document(&base.join("alpha-notes.txt"))
+        .expect("remove file");
+    index
+        .add_document(&base.join("alpha-draft.txt"))
+        .expect("index file");
+    engine.reload().expect("reload engine");
+
+    assert_eq!(search("alpha"), vec!["alpha-draft.txt", "alpha-plan.txt"]);
+
+    cleanup_temp_dir(&base);
+}
+
+#[test]
+fn filename_catalog_matches_indexed_metadata() {
+    let base = unique_temp_dir();
+    let index_dir = base.join("index");
+    fs::create_dir_all(&base).expect("create temp dir");
+    let report = base.join("Résumé Report.PDF.txt");
+    let notes = base.join("notes.MD");
+    fs::write(&report, "quarterly figures").expect("write report");
+    fs::write(&notes, "# notes").expect("write notes");
+    build_index(&index_dir, &[report.clone(), notes.clone()]);
+
+    let engine = SearchEngine::open(&index_dir).expect("open search engine");
+    let searcher = engine.reader.searcher();
+    let catalog = engine
+        .catalog
+        .snapshot(&searcher, &engine.fields)
+        .expect("load catalog");
+    let mut docs: Vec<&DocData> = catalog.par_docs().collect();
+    docs.sort_by(|left, right| left.path.cmp(&right.path));
+
+    let expected = [
+        (&report, "Résumé Report.PDF.txt", "txt"),
+        (&notes, "notes.MD", "md"),
+    ];
+    assert_eq!(docs.len(), expected.len());
+    for (doc, (path, filename, ext)) in docs.into_iter().zip(expected) {
+        let metadata = fs::metadata(path).expect("read metadata");
+        assert_eq!(&doc.path, path);
+        assert_eq!(doc.filename, filename);
+        assert_eq!(doc.ext, ext);
+        assert_eq!(doc.size, metadata.len());
+        assert!(doc.modified > 0);
+    }
+
+    cleanup_temp_dir(&base);
+}
+
+#[test]
+fn paged_search_matches_single_combined_ranking() {
+    let base = unique_temp_dir();
+    let index_dir = base.join("index");
+    fs::create_dir_all(&base).expect("create temp dir");
+    let files: Vec<PathBuf> = (0..5)
+        .map(|number| {
+            let path = base.join(format!("ledger-{number}.txt"));
+            fs::write(&path, "ledger ".repeat(number + 1)).expect("write file");
+            path
+        })
+        .collect();
+    build_index(&index_dir, &files);
+
+    let engine = SearchEngine::open(&index_dir).expect("open search engine");
+    let full: Vec<PathBuf> = engine
+        .search("ledger", QueryMode::Fuzzy, SearchMode::Combined, 10)
+        .expect("run full search")
+        .into_iter()
+        .map(|result| result.path)
+        .collect();
+
+    let mut request =
+        SearchRequest::new("ledger", QueryMode::Fuzzy, SearchMode::Combined).with_page(0, 2);
+    let mut paged = Vec::new();
+    loop {
+        let page = engine.search_page(&request).expect("run paged search");
+        assert_eq!(page.total_hits, 5);
+        paged.extend(page.results.into_iter().map(|result| result.path));
+        match page.next_page {
+            Some(token) => request = request.after(&token).expect("resume from token"),
+            None => break,
+        }
+    }
+    assert_eq!(paged, full);
+
+    let token = engine
+        .search_page(&request.clone().with_page(0, 2))
+        .expect("run paged search")
+        .next_page
+        .expect("next page token");
+    let other = SearchRequest::new("ledger", QueryMode::Fuzzy, SearchMode::ContentOnly);
+    assert!(matches!(other.after(&token), Err(Error::Search(_))));
+
+    cleanup_temp_dir(&base);
+}
+
+#[test]
+fn page_tokens_go_stale_when_the_index_or_ranking_changes() {
+    let base = unique_temp_dir();
+    let index_dir = base.join("index");
+    fs::create_dir_all(&base).expect("create temp dir");
+    let files: Vec<PathBuf> = (0..4)
+        .map(|number| {
+            let path = base.join(format!("ledger-{number}.txt"));
+            fs::write(&path, "ledger ".repeat(number + 1)).expect("write file");
+            path
+        })
+        .collect();
+    build_index(&index_dir, &files);
+
+    let folders = vec![FolderEntry {
+        path: base.clone(),
+        recursive: true,
+        extensions: Vec::new(),
+        priority: 1.0,
+    }];
+    let request = SearchRequest::new("ledger", QueryMode::Fuzzy, SearchMode::Combined)
+        .with_ranking(RankingProfile::default(), &folders)
+        .with_page(0, 2);
+    let engine = SearchEngine::open(&index_dir).expect("open search engine");
+    let token = engine
+        .search_page(&request)
+        .expect("run paged search")
+        .next_page
+        .expect("next page token");
+
+    let mut reweighted = RankingProfile::default();
+    reweighted.filename_weight += 0.5;
+    let other_weights = request.clone().with_ranking(reweighted, &folders);
+    assert!(matches!(other_weights.after(&token), Err(Error::Search(_))));
+    let mut reprioritized = folders.clone();
+    reprioritized[0].priority = 2.0;
+    let other_priority = request
+        .clone()
+        .with_ranking(RankingProfile::default(), &reprioritized);
+    assert!(matches!(
+        other_priority.after(&token),
+        Err(Error::Search(_))
+    ));
+
+    // A reload without a new commit keeps the token valid.
+    engine.reload().expect("reload engine");
+    let resumed = request.clone().after(&token).expect("resume from token");
+    assert_eq!(
+        engine
+            .search_page(&resumed)
+            .expect("run resumed search")
+            .results
+            .len(),
+        2
+    );
+
+    let added = base.join("ledger-new.txt");
+    fs::write(&added, "ledger ledger ledger ledger ledger").expect("write file");
+    build_index(&index_dir, &[added]);
+    engine.reload().expect("reload engine");
+    assert!(matches!(
+        engine.search_page(&resumed),
+        Err(Error::StalePage)
+    ));
+    assert_eq!(
+        engine
+            .search_page(&resumed.with_page(0, 10))
+            .expect("run fresh search")
+            .total_hits,
+        5
+    );
+
+    cleanup_temp_dir(&base);
+}
+
+#[test]
+fn facets_count_whole_hit_set_and_filters_narrow_results() {
+    let base = unique_temp_dir();
+    let index_dir = base.join("index");
+    let docs = base.join("docs");
+    let work = base.join("work");
+    fs::create_dir_all(docs.join("nested")).expect("create docs dir");
+    fs::create_dir_all(&work).expect("create work dir");
+
+    let files = [
+        docs.join("a.txt"),
+        docs.join("nested").join("b.md"),
+        work.join("c.txt"),
+        work.join("d.txt"),
+    ];
+    for file in &files {
+        fs::write(file, "quarterly budget").expect("write file");
+    }
+    build_index(&index_dir, &files);
+
+    let folders: Vec<FolderEntry> = [&docs, &docs.join("nested"), &work]
+        .into_iter()
+        .map(|path| FolderEntry {
+            path: path.clone(),
+            recursive: true,
+            extensions: Vec::new(),
+            priority: 1.0,
+        })
+        .collect();
+    let request = SearchRequest::new("budget", QueryMode::Fuzzy, SearchMode::ContentOnly)
+        .with_page(0, 1)
+        .with_ranking(RankingProfile::default(), &folders);
+
+    let engine = SearchEngine::open(&index_dir).expect("open search engine");
+    let page = engine.search_page(&request).expect("run search");
+    assert_eq!(page.results.len(), 1);
+    assert_eq!(page.facets.extensions.get("txt"), Some(&3));
+    assert_eq!(page.facets.extensions.get("md"), Some(&1));
+    assert_eq!(page.facets.folders.get(&docs), Some(&2));
+    assert_eq!(page.facets.folders.get(&work), Some(&2));
+    assert_eq!(page.facets.modified.get(&ModifiedBucket::Today), Some(&4));
+
+    let filtered = request.with_filters(FacetFilters {
+        extensions: vec!["txt".to_string()],
+        folders: vec![work.clone()],
+        modified: Vec::new(),
+    });
+    let page = engine.search_page(&filtered).expect("run filtered search");
+    assert_eq!(page.total_hits, 2);
+    assert!(page.results[0].path.starts_with(&work));
+    assert_eq!(page.facets.extensions.get("txt"), Some(&2));
+    assert_eq!(page.facets.extensions.get("md"), None);
+    assert_eq!(page.facets.folders.get(&docs), Some(&1));
+
+    // The selections reach the index query and the filename catalog alike.
+    let older = filtered.clone().with_filters(FacetFilters {
+        modified: vec![ModifiedBucket::Older],
+        ..FacetFilters::default()
+    });
+    let page = engine.search_page(&older).expect("run filtered search");
+    assert_eq!(page.total_hits, 0);
+    assert_eq!(page.facets.modified.get(&ModifiedBucket::Today), Some(&4));
+    let filenames = SearchRequest::new("c", QueryMode::Fuzzy, SearchMode::FilenameOnly)
+        .with_ranking(RankingProfile::default(), &folders)
+        .with_filters(FacetFilters {
+            folders: vec![docs.clone()],
+            ..FacetFilters::default()
+        });
+    let page = engine.search_page(&filenames).expect("run filename search");
+    assert!(page
+        .results
+        .iter()
+        .all(|result| result.path.starts_with(&docs)));
+    assert_eq!(page.facets.folders.get(&work), Some(&1));
+
+    cleanup_temp_dir(&base);
+}
+
+#[test]
+fn size_and_modified_ranges_filter_the_full_hit_set() {
+    let base = unique_temp_dir();
+    let index_dir = base.join("index");
+    fs::create_dir_all(&base).expect("create temp dir");
+
+    let day = 86_400;
+    let files: Vec<PathBuf> = (0..6_u64)
+        .map(|number| {
+            let path = base.join(format!("invoice-{number}.txt"));
+            let body = format!("invoice {}", "x".repeat(number as usize * 100));
+            fs::write(&path, body).expect("write file");
+            let modified = UNIX_EPOCH + Duration::from_secs(1_700_000_000 + number * day);
+            fs::File::options()
+                .write(true)
+                .open(&path)
+                .and_then(|file| file.set_modified(modified))
+                .expect("set modified time");
+            path
+        })
+        .collect();
+    build_index(&index_dir, &files);
+
+    let engine = SearchEngine::open(&index_dir).expect("open search engine");
+    let paths = |page: SearchPage| -> Vec<PathBuf> {
+        let mut paths: Vec<PathBuf> = page.results.into_iter().map(|result| result.path).collect();
+        paths.sort();
+        paths
+    };
+
+    for search_mode in [SearchMode::ContentOnly, SearchMode::FilenameOnly] {
+        let request = SearchRequest::new("invoice", QueryMode::Fuzzy, search_mode)
+            .with_page(0, 2)
+            .with_size(RangeFilter::new(Some(250), None));
+        let page = engine
+            .search_page(&request)
+            .expect("run size-filtered search");
+        assert_eq!(page.total_hits, 3, "{search_mode:?}");
+
+        let request = request
+            .with_page(0, 10)
+            .with_modified(RangeFilter::new(None, Some(1_700_000_000 + 4 * day)));
+        let page = engine
+            .search_page(&request)
+            .expect("run range-filtered search");
+        assert_eq!(paths(page), files[3..5].to_vec(), "{search_mode:?}");
+    }
+
+    cleanup_temp_dir(&base);
+}
+
+#[test]
+fn grep_mode_matches_across_token_boundaries_with_line_positions() {
+    let base = unique_temp_dir();
+    let index_dir = base.join("index");
+    fs::create_dir_all(&base).expect("create temp dir");
+    fs::write(
+        base.join("service.log"),
+        "boot ok\nwarn: ERR-1001: disk full\ncalling foo   bar now\n",
+    )
+    .expect("write file");
+    fs::write(base.join("short.log"), "ERR-12: truncated code\nfoo\nbar").expect("write file");
+
+    build_index(
+        &index_dir,
+        &[base.join("service.log"), base.join("short.log")],
+    );
+
+    let engine = SearchEngine::open(&index_dir).expect("open search engine");
+    let results = engine
+        .search(r"ERR-\d{4}:", QueryMode::Grep, SearchMode::ContentOnly, 10)
+        .expect("run grep search");
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].filename, "service.log");
+    let line_match = &results[0].line_matches[0];
+    assert_eq!(line_match.line_number, 2);
+    assert_eq!(line_match.column, 7);
+    assert_eq!(&line_match.line[line_match.ranges[0].clone()], "ERR-1001:");
+    assert_eq!(results[0].snippets.len(), 1);
+
+    let results = engine
+        .search(r"foo\s+bar", QueryMode::Grep, SearchMode::ContentOnly, 10)
+        .expect("run grep search");
+    let filenames: Vec<&str> = results.iter().map(|r| r.filename.as_str()).collect();
+    assert_eq!(filenames, vec!["short.log", "service.log"]);
+    assert_eq!(results[0].line_matches.len(), 2);
+
+    cleanup_temp_dir(&base);
+}
+
+#[test]
+fn search_folds_case_and_diacritics() {
+    let base = unique_temp_dir();
+    let index_dir = base.join("index");
+    fs::create_dir_all(&base).expect("create temp dir");
+
+    let letter = base.join("Lettre.txt");
+    fs::write(
+        &letter,
+        "Herr Müller verbringt den Sommer, l'été, in Mühlhäuser Straße.",
+    )
+    .expect("write file");
+    let resume = base.join("Résumé.txt");
+    fs::write(&resume, "work history").expect("write file");
+    build_index(&index_dir, &[letter.clone(), resume.clone()]);
+
+    let engine = SearchEngine::open(&index_dir).expect("open search engine");
+    for query in ["muller", "ÉTÉ", "\"herr MULLER\"", "muhlhauser", "strasse"] {
+        let results = engine
+            .search(query, QueryMode::Fuzzy, SearchMode::ContentOnly, 10)
+            .expect("run folded search");
+        assert_eq!(results.len(), 1, "{query}");
+        assert_eq!(results[0].path, letter, "{query}");
+    }
+
+    let results = engine
+        .search("muller", QueryMode::Fuzzy, SearchMode::ContentOnly, 10)
+        .expect("run folded search");
+    let snippet = &results[0].snippets[0];
+    assert_eq!(&snippet.text[snippet.highlights[0].clone()], "Müller");
+
+    let results = engine
+        .search("resume", QueryMode::Fuzzy, SearchMode::FilenameOnly, 10)
+        .expect("run filename search");
+    assert_eq!(results[0].path, resume);
+
+    cleanup_temp_dir(&base);
+}
+
+#[test]
+fn stemmed_terms_match_inflections_in_detected_language() {
+    let base = unique_temp_dir();
+    let index_dir = base.join("index");
+    fs::create_dir_all(&base).expect("create temp dir");
+
+    let english = base.join("network.txt");
+    fs::write(
+        &english,
+        "All of the office computers were connected to the new network yesterday afternoon.",
+    )
+    .expect("write file");
+    let french = base.join("repas.txt");
+    fs::write(
+        &french,
+        "Pendant les vacances, la famille mangeait toujours ensemble dans la petite cuisine.",
+    )
+    .expect("write file");
+    build_index(&index_dir, &[english.clone(), french.clone()]);
+
+    let engine = SearchEngine::open(&index_dir).expect("open search engine");
+    for (query, expected, word) in [
+        ("connections", &english, "connected"),
+        ("mangeaient", &french, "mangeait"),
+    ] {
+        let results = engine
+            .search(query, QueryMode::Fuzzy, SearchMode::ContentOnly, 10)
+            .expect("run stemmed search");
+        let paths: Vec<&PathBuf> = results.iter().map(|result| &result.path).collect();
+        assert_eq!(paths, vec![expected], "{query}");
+
+        // Snippets highlight the inflection the stem matched.
+        let snippet = &results[0].snippets[0];
+        let highlighted: Vec<&str> = snippet
+            .highlights
+            .iter()
+            .map(|range| &snippet.text[range.clone()])
+            .collect();
+        assert_eq!(highlighted, vec![word], "{query}");
+    }
+
+    cleanup_temp_dir(&base);
+}
+
+#[test]
+fn cjk_bigram_indexes_match_cjk_words_and_latin_terms() {
+    let base = unique_temp_dir();
+    let index_dir = base.join("index");
+    fs::create_dir_all(&base).expect("create temp dir");
+    let minutes = base.join("minutes.txt");
+    fs::write(&minutes, "東京都の会議 meeting notes").expect("write minutes file");
+    let other = base.join("other.txt");
+    fs::write(&other, "京都 travel plans").expect("write other file");
+    let trip = base.join("trip.txt");
+    fs::write(&trip, "東京 then 京都").expect("write trip file");
+
+    let config = GeneralConfig {
+        content_tokenizer: ContentTokenizer::CjkBigram,
+        ..GeneralConfig::default()
+    };
+    let mut index = SearchIndex::open_with_config(&index_dir, &config).expect("open index");
+    index.add_document(&minutes).expect("index minutes");
+    index.add_document(&other).expect("index other");
+    index.add_document(&trip).expect("index trip");
+    drop(index);
+
+    // The engine keeps the tokenizer the index was created with.
+    let engine = SearchEngine::open(&index_dir).expect("open search engine");
+    for (query, expected) in [
+        ("東京", vec!["minutes.txt", "trip.txt"]),
+        ("会議", vec!["minutes.txt"]),
+        ("京都", vec!["minutes.txt", "other.txt", "trip.txt"]),
+        ("meting", vec!["minutes.txt"]),
+        ("\"東京都\"", vec!["minutes.txt"]),
+        // Bigrams of one word must stay adjacent, not just co-occur.
+        ("東京都", vec!["minutes.txt"]),
+        // Single characters match inside longer runs, at either end of a bigram.
+        ("議", vec!["minutes.txt"]),
+        ("京", vec!["minutes.txt", "other.txt", "trip.txt"]),
+        ("\"都\"", vec!["minutes.txt", "other.txt", "trip.txt"]),
+    ] {
+        let results = engine
+            .search(query, QueryMode::Fuzzy, SearchMode::ContentOnly, 10)
+            .expect("run CJK search");
+        let mut names: Vec<&str> = results.iter().map(|r| r.filename.as_str()).collect();
+        names.sort_unstable();
+        assert_eq!(names, expected, "{query}");
+    }
+
+    cleanup_temp_dir(&base);
+}
+
+#[test]
+fn engine_creates_indexes_with_the_given_tokenizer_and_keeps_stored_ones() {
+    let base = unique_temp_dir();
+    let index_dir = base.join("index");
+    fs::create_dir_all(&base).expect("create temp dir");
+    let minutes = base.join("minutes.txt");
+    fs::write(&minutes, "東京都の会議").expect("write minutes file");
+
+    let config = GeneralConfig {
+        content_tokenizer: ContentTokenizer::CjkBigram,
+        ..GeneralConfig::default()
+    };
+    let engine = SearchEngine::open_with_config(&index_dir, &config, ReloadPolicy::Manual)
+        .expect("create index through the engine");
+    // Opening for writing with another tokenizer keeps the one the index was created with.
+    let mut index =
+        SearchIndex::open_with_config(&index_dir, &GeneralConfig::default()).expect("open index");
+    index.add_document(&minutes).expect("index minutes");
+    drop(index);
+    engine.reload().expect("reload engine");
+
+    let search = |engine: &SearchEngine| {
+        engine
+            .search("京都", QueryMode::Fuzzy, SearchMode::ContentOnly, 10)
+            .expect("run CJK search")
+            .len()
+    };
+    assert_eq!(search(&engine), 1);
+    let reopened = SearchEngine::open_with_policy(&index_dir, ReloadPolicy::Manual)
+        .expect("reopen search engine");
+    assert_eq!(search(&reopened), 1);
+
+    cleanup_temp_dir(&base);
+}
+
+#[test]
+fn suggestions_and_completions_come_from_the_term_dictionary() {
+    let base = unique_temp_dir();
+    let index_dir = base.join("index");
+    fs::create_dir_all(&base).expect("create temp dir");
+    fs::write(base.join("alpha.txt"), "distributed architecture notes").expect("write alpha file");
+    fs::write(base.join("beta.txt"), "architecture review").expect("write beta file");
+    fs::write(base.join("gamma.txt"), "archive index").expect("write gamma file");
+    build_index(
+        &index_dir,
+        &[
+            base.join("alpha.txt"),
+            base.join("beta.txt"),
+            base.join("gamma.txt"),
+        ],
+    );
+
+    let engine = SearchEngine::open(&index_dir).expect("open search engine");
+    let suggestions = engine.suggest("Architecure", 3).expect("suggest terms");
+    assert_eq!(
+        suggestions.first(),
+        Some(&TermSuggestion {
+            term: "architecture".to_string(),
+            doc_freq: 2,
+        })
+    );
+
+    assert_eq!(
+        engine
+            .did_you_mean("distribted AND ext:txt")
+            .expect("correct query"),
+        Some("distributed AND ext:txt".to_string())
+    );
+    assert_eq!(engine.did_you_mean("architecture").expect("no-op"), None);
+    assert_eq!(engine.did_you_mean("zzzzzz").expect("no match"), None);
+
+    let completions: Vec<String> = engine
+        .complete("arch", 5)
+        .expect("complete prefix")
+        .into_iter()
+        .map(|completion| completion.term)
+        .collect();
+    assert_eq!(completions, vec!["architecture", "archive"]);
+
+    cleanup_temp_dir(&base);
+}
+
+#[test]
+fn similar_to_ranks_related_documents_and_excludes_the_source() {
+    let base = unique_temp_dir();
+    let index_dir = base.join("index");
+    fs::create_dir_all(&base).expect("create temp dir");
+    let lease = base.join("lease.txt");
+    fs::write(
+        &lease,
+        "lease agreement: rent payment schedule, termination notice and tenant liability",
+    )
+    .expect("write lease file");
+    let rental = base.join("rental.txt");
+    fs::write(
+        &rental,
+        "rental agreement covering rent payment, termination notice and deposit",
+    )
+    .expect("write rental file");
+    let recipe = base.join("recipe.txt");
+    fs::write(&recipe, "flour sugar butter eggs, bake in a hot oven").expect("write recipe");
+    build_index(&index_dir, &[lease.clone(), rental.clone(), recipe.clone()]);
+
+    let engine = SearchEngine::open(&index_dir).expect("open search engine");
+    let results = engine.similar_to(&lease, 10).expect("find similar files");
+    let paths: Vec<&PathBuf> = results.iter().map(|result| &result.path).collect();
+    assert_eq!(paths, vec![&rental]);
+    assert_eq!(results[0].score, 1.0);
+
+    // Files outside the index are extracted and compared the same way.
+    let draft = base.join("draft.txt");
+    fs::write(&draft, "draft notice of termination for unpaid rent").expect("write draft");
+    let results = engine.similar_to(&draft, 10).expect("find similar files");
+    let mut paths: Vec<&PathBuf> = results.iter().map(|result| &result.path).collect();
+    paths.sort();
+    assert_eq!(paths, vec![&lease, &rental]);
+
+    cleanup_temp_dir(&base);
+}
+
+#[test]
+fn explain_reproduces_ranked_scores_from_raw_channel_scores() {
+    let base = unique_temp_dir();
+    let index_dir = base.join("index");
+    fs::create_dir_all(&base).expect("create temp dir");
+    fs::write(base.join("architecture.txt"), "architecture notes").expect("write first file");
+    fs::write(base.join("notes.txt"), "architecture architecture review").expect("write second");
+    fs::write(base.join("other.txt"), "nothing related").expect("write third file");
+    build_index(
+        &index_dir,
+        &[
+            base.join("architecture.txt"),
+            base.join("notes.txt"),
+            base.join("other.txt"),
+        ],
+    );
+
+    let engine = SearchEngine::open(&index_dir).expect("open search engine");
+    let request = SearchRequest::new("architecture", QueryMode::Fuzzy, SearchMode::Combined);
+    let page = engine.search_page(&request).expect("run search");
+    assert_eq!(page.results.len(), 2);
+    for result in &page.results {
+        let explanation = engine
+            .explain(&request, &result.path)
+            .expect("explain result")
+            .expect("indexed file");
+        assert!(explanation.content_raw.is_some());
+        assert!(explanation.content_divisor >= 1.0);
+        assert_eq!(explanation.content_weight, request.ranking.content_weight);
+        assert!((explanation.score - result.score).abs() < 1e-6);
+    }
+
+    let filename_match = engine
+        .explain(&request, &base.join("architecture.txt"))
+        .expect("explain filename match")
+        .expect("indexed file");
+    assert!(filename_match.filename_raw.is_some());
+    assert_eq!(filename_match.filename_score(), 1.0);
+
+    let unmatched = engine
+        .explain(&request, &base.join("other.txt"))
+        .expect("explain unmatched file")
+        .expect("indexed file");
+    assert_eq!(unmatched.content_raw, None);
+    assert_eq!(unmatched.score, 0.0);
+    assert_eq!(
+        engine
+            .explain(&request, &base.join("missing.txt"))
+            .expect("explain missing file"),
+        None
+    );
+
+    cleanup_temp_dir(&base);
+}
+
+#[test]
+fn explain_reproduces_ranked_scores_under_facet_filters() {
+    let base = unique_temp_dir();
+    let index_dir = base.join("index");
+    let docs = base.join("docs");
+    let drafts = base.join("drafts");
+    fs::create_dir_all(&docs).expect("create docs dir");
+    fs::create_dir_all(&drafts).expect("create drafts dir");
+    let files = [
+        docs.join("overview.txt"),
+        docs.join("design.md"),
+        drafts.join("sketch.txt"),
+    ];
+    fs::write(&files[0], "architecture notes").expect("write overview");
+    fs::write(&files[1], "architecture architecture architecture").expect("write design");
+    fs::write(&files[2], "architecture architecture sketch").expect("write sketch");
+    // Unrelated files make the term rare enough for raw scores to exceed 1, so the
+    // divisors depend on which hits are ranked.
+    let mut indexed = files.to_vec();
+    for day in [
+        "monday",
+        "tuesday",
+        "wednesday",
+        "thursday",
+        "friday",
+        "saturday",
+    ] {
+        let filler = drafts.join(format!("{day}.txt"));
+        fs::write(&filler, format!("{day} errands")).expect("write filler");
+        indexed.push(filler);
+    }
+    build_index(&index_dir, &indexed);
+
+    let engine = SearchEngine::open(&index_dir).expect("open search engine");
+    // Exact terms are scored by BM25; fuzzy ones all score 1.
+    let request = SearchRequest::new("architecture", QueryMode::Fuzzy, SearchMode::ContentOnly)
+        .with_term_matching(TermMatching {
+            distance: 0,
+            prefix: false,
+        });
+    let by_extension = request.clone().with_filters(FacetFilters {
+        extensions: vec!["txt".to_string()],
+        ..FacetFilters::default()
+    });
+    let by_folder = request.clone().with_filters(FacetFilters {
+        folders: vec![docs.clone()],
+        ..FacetFilters::default()
+    });
+    for (filtered, left_out) in [(by_extension, &files[1]), (by_folder, &files[2])] {
+        let page = engine.search_page(&filtered).expect("run search");
+        assert_eq!(page.results.len(), 2);
+        let mut divisor = None;
+        for result in &page.results {
+            let explanation = engine
+                .explain(&filtered, &result.path)
+                .expect("explain result")
+                .expect("indexed file");
+            assert!((explanation.score - result.score).abs() < 1e-6);
+            divisor = Some(explanation.content_divisor);
+        }
+
+        // Files the filters leave out keep their own raw score but are normalized
+        // against the hits that were ranked.
+        let unfiltered = engine
+            .explain(&request, left_out)
+            .expect("explain unfiltered")
+            .expect("indexed file");
+        let filtered_out = engine
+            .explain(&filtered, left_out)
+            .expect("explain filtered-out file")
+            .expect("indexed file");
+        assert_eq!(filtered_out.content_raw, unfiltered.content_raw);
+        assert_eq!(Some(filtered_out.content_divisor), divisor);
+        assert!(filtered_out.content_divisor > 1.0);
+    }
+
+    cleanup_temp_dir(&base);
+}
+
+#[test]
+fn term_modifiers_and_defaults_control_fuzzy_matching() {
+    let base = unique_temp_dir();
+    let index_dir = base.join("index");
+    fs::create_dir_all(&base).expect("create temp dir");
+    let pet = base.join("pet.txt");
+    fs::write(&pet, "the cat naps").expect("write pet file");
+    let catalog = base.join("catalog.txt");
+    fs::write(&catalog, "product catalog listing").expect("write catalog file");
+    let typo = base.join("typo.txt");
+    fs::write(&typo, "a cot bed").expect("write typo file");
+    build_index(&index_dir, &[pet.clone(), catalog.clone(), typo.clone()]);
+
+    let engine = SearchEngine::open(&index_dir).expect("open search engine");
+    let exact_default = TermMatching {
+        distance: 0,
+        prefix: false,
+    };
+    let paths = |query: &str, search_mode: SearchMode, matching: TermMatching| {
+        let request =
+            SearchRequest::new(query, QueryMode::Fuzzy, search_mode).with_term_matching(matching);
+        let mut paths: Vec<PathBuf> = engine
+            .search_page(&request)
+            .expect("run search")
+            .results
+            .into_iter()
+            .map(|result| result.path)
+            .collect();
+        paths.sort();
+        paths
+    };
+
+    let content = SearchMode::ContentOnly;
+    let fuzzy = TermMatching::default();
+    assert_eq!(
+        paths("cat", content, fuzzy),
+        vec![catalog.clone(), pet.clone(), typo.clone()]
+    );
+    assert_eq!(paths("=cat", content, fuzzy), vec![pet.clone()]);
+    assert_eq!(paths("cat", content, exact_default), vec![pet.clone()]);
+    assert_eq!(
+        paths("cat*", content, exact_default),
+        vec![catalog.clone(), pet.clone()]
+    );
+    assert_eq!(
+        paths("cat~1", content, exact_default),
+        vec![pet.clone(), typo.clone()]
+    );
+
+    let filename = SearchMode::FilenameOnly;
+    assert_eq!(paths("catal", filename, fuzzy), vec![catalog.clone()]);
+    assert!(paths("=catal", filename, fuzzy).is_empty());
+    assert_eq!(paths("=catalog", filename, fuzzy), vec![catalog]);
+
+    let results = engine
+        .search_page(&SearchRequest::new("=cat", QueryMode::Fuzzy, content))
+        .expect("run exact search");
+    let snippet = &results.results[0].snippets[0];
+    assert_eq!(snippet.highlights.len(), 1);
+    assert_eq!(&snippet.text[snippet.highlights[0].clone()], "cat");
+
+    cleanup_temp_dir(&base);
+}
+
+#[test]
+fn case_sensitive_and_whole_word_options_check_original_text() {
+    let base = unique_temp_dir();
+    let index_dir = base.join("index");
+    fs::create_dir_all(&base).expect("create temp dir");
+    let code = base.join("code.rs");
+    fs::write(&code, "let ID = lookup();").expect("write code file");
+    let notes = base.join("notes.txt");
+    fs::write(&notes, "the id field").expect("write notes file");
+    let identity = base.join("identity.txt");
+    fs::write(&identity, "identity checks").expect("write identity file");
+    build_index(&index_dir, &[code.clone(), notes.clone(), identity.clone()]);
+
+    let engine = SearchEngine::open(&index_dir).expect("open search engine");
+    let run =
+        |query: &str, query_mode: QueryMode, search_mode: SearchMode, options: MatchOptions| {
+            let request =
+                SearchRequest::new(query, query_mode, search_mode).with_match_options(options);
+            engine.search_page(&request).expect("run search").results
+        };
+    let paths = |results: Vec<SearchResult>| {
+        let mut paths: Vec<PathBuf> = results.into_iter().map(|result| result.path).collect();
+        paths.sort();
+        paths
+    };
+    let case_sensitive = MatchOptions {
+        case_sensitive: true,
+        whole_word: false,
+    };
+    let whole_word = MatchOptions {
+        case_sensitive: false,
+        whole_word: true,
+    };
+    let both = MatchOptions {
+        case_sensitive: true,
+        whole_word: true,
+    };
+    let content = SearchMode::ContentOnly;
+
+    assert_eq!(
+        paths(run(
+            "ID",
+            QueryMode::Fuzzy,
+            content,
+            MatchOptions::default()
+        )),
+        vec![code.clone(), identity.clone(), notes.clone()]
+    );
+    let results = run("ID", QueryMode::Fuzzy, content, case_sensitive);
+    assert_eq!(paths(results.clone()), vec![code.clone()]);
+    let snippet = &results[0].snippets[0];
+    assert_eq!(&snippet.text[snippet.highlights[0].clone()], "ID");
+    assert_eq!(
+        paths(run("id", QueryMode::Fuzzy, content, whole_word)),
+        vec![code.clone(), notes.clone()]
+    );
+    assert_eq!(
+        paths(run("i[d]", QueryMode::Regex, content, case_sensitive)),
+        vec![notes.clone()]
+    );
+    assert_eq!(
+        paths(run("id", QueryMode::Grep, content, both)),
+        vec![notes.clone()]
+    );
+
+    let filename = SearchMode::FilenameOnly;
+    assert!(run("Notes", QueryMode::Fuzzy, filename, case_sensitive).is_empty());
+    assert_eq!(
+        paths(run("notes", QueryMode::Fuzzy, filename, case_sensitive)),
+        vec![notes]
+    );
+
+    cleanup_temp_dir(&base);
+}
+
+#[test]
+fn scopes_limit_results_to_folder_subtrees() {
+    let base = unique_temp_dir();
+    let index_dir = base.join("index");
+    let reports = base.join("reports");
+    let archive = reports.join("archive");
+    let siblings = base.join("reports-old");
+    fs::create_dir_all(&archive).expect("create archive dir");
+    fs::create_dir_all(&siblings).expect("create sibling dir");
+    let current = reports.join("ledger-current.txt");
+    let archived = archive.join("ledger-archived.txt");
+    let sibling = siblings.join("ledger-sibling.txt");
+    for path in [&current, &archived, &sibling] {
+        fs::write(path, "ledger totals").expect("write ledger file");
+    }
+    build_index(
+        &index_dir,
+        &[current.clone(), archived.clone(), sibling.clone()],
+    );
+
+    let engine = SearchEngine::open(&index_dir).expect("open search engine");
+    let paths = |search_mode: SearchMode, scopes: Vec<PathBuf>| {
+        let request =
+            SearchRequest::new("ledger", QueryMode::Fuzzy, search_mode).with_scopes(scopes);
+        let mut paths: Vec<PathBuf> = engine
+            .search_page(&request)
+            .expect("run scoped search")
+            .results
+            .into_iter()
+            .map(|result| result.path)
+            .collect();
+        paths.sort();
+        paths
+    };
+
+    for search_mode in [SearchMode::ContentOnly, SearchMode::FilenameOnly] {
+        assert_eq!(
+            paths(search_mode, vec![reports.clone()]),
+            vec![archived.clone(), current.clone()]
+        );
+        assert_eq!(
+            paths(search_mode, vec![archive.clone(), siblings.clone()]),
+            vec![archived.clone(), sibling.clone()]
+        );
+        assert_eq!(paths(search_mode, Vec::new()).len(), 3);
+    }
+
+    cleanup_temp_dir(&base);
+}
+
+#[test]
+fn streamed_search_sends_partial_hits_then_ranked_batches_and_cancels() {
+    let base = unique_temp_dir();
+    let index_dir = base.join("index");
+    fs::create_dir_all(&base).expect("create temp dir");
+    let files: Vec<PathBuf> = (0..70)
+        .map(|number| {
+            let path = base.join(format!("log-{number:02}.txt"));
+            fs::write(&path, "ERR-1001: disk\n".repeat(number % 3 + 1)).expect("write log file");
+            path
+        })
+        .collect();
+    let mut index = SearchIndex::open(&index_dir).expect("open index");
+    index
+        .build_from_scan(&ScanResult {
+            files: files.clone(),
+            errors: Vec::new(),
+        })
+        .expect("build index");
+    drop(index);
+
+    let engine = SearchEngine::open(&index_dir).expect("open search engine");
+    let request =
+        SearchRequest::new(r"ERR-\d{4}", QueryMode::Grep, SearchMode::ContentOnly).with_page(0, 30);
+    let (tx, rx) = mpsc::channel();
+    let page = engine
+        .search_stream(&request, &CancellationToken::new(), &tx)
+        .expect("run streamed search");
+    drop(tx);
+
+    let mut partial = Vec::new();
+    let mut ranked = Vec::new();
+    for event in rx {
+        match event {
+            SearchEvent::Partial(results) => {
+                assert!(ranked.is_empty(), "partial hits precede the ranked page");
+                partial.extend(results.into_iter().map(|result| result.path));
+            }
+            SearchEvent::Ranked(batch) => {
+                assert!(batch.results.len() <= STREAM_BATCH_SIZE);
+                assert_eq!(batch.total_hits, 70);
+                ranked.extend(batch.results.into_iter().map(|result| result.path));
+            }
+        }
+    }
+    partial.sort();
+    assert_eq!(partial, files);
+    let page_paths: Vec<PathBuf> = page
+        .results
+        .iter()
+        .map(|result| result.path.clone())
+        .collect();
+    assert_eq!(ranked, page_paths);
+    assert_eq!(
+        page_paths,
+        engine
+            .search_page(&request)
+            .expect("run page")
+            .results
+            .into_iter()
+            .map(|result| result.path)
+            .collect::<Vec<_>>()
+    );
+
+    let cancelled = CancellationToken::new();
+    cancelled.cancel();
+    let (tx, _rx) = mpsc::channel();
+    assert!(matches!(
+        engine.search_stream(&request, &cancelled, &tx),
+        Err(Error::Cancelled)
+    ));
+
+    let (tx, rx) = mpsc::channel();
+    drop(rx);
+    let token = CancellationToken::new();
+    assert!(matches!(
+        engine.search_stream(&request, &token, &tx),
+        Err(Error::Cancelled)
+    ));
+    assert!(token.is_cancelled());
+
+    cleanup_temp_dir(&base);
+}
+
+#[test]
+fn manual_reload_policy_keeps_snapshot_until_reload() {
+    fn assert_shareable<T: Send + Sync>() {}
+    assert_shareable::<SearchEngine>();
+
+    let base = unique_temp_dir();
+    let index_dir = base.join("index");
+    fs::create_dir_all(&base).expect("create temp dir");
+    let first = base.join("first.txt");
+    let second = base.join("second.txt");
+    fs::write(&first, "quarterly budget").expect("write first file");
+    fs::write(&second, "quarterly forecast").expect("write second file");
+    build_index(&index_dir, std::slice::from_ref(&first));
+
+    let engine = SearchEngine::open_with_policy(&index_dir, ReloadPolicy::Manual)
+        .expect("open search engine");
+    let search = || {
+        engine
+            .search("quarterly", QueryMode::Fuzzy, SearchMode::ContentOnly, 10)
+            .expect("search should succeed")
+            .len()
+    };
+    assert_eq!(search(), 1);
+
+    build_index(&index_dir, std::slice::from_ref(&second));
+    assert_eq!(search(), 1);
+
+    engine.reload().expect("reload engine");
+    assert_eq!(search(), 2);
+
+    cleanup_temp_dir(&base);
+}
+
+#[test]
+fn malformed_queries_report_syntax_error_position() {
+    let base = unique_temp_dir();
+    let index_dir = base.join("index");
+    fs::create_dir_all(&base).expect("create temp dir");
+
+    let engine = SearchEngine::open(&index_dir).expect("open search engine");
+    let result = engine.search(
+        "alpha AND (beta",
+        QueryMode::Fuzzy,
+        SearchMode::Combined,
+        10,
+    );
+
+    assert!(matches!(
+        result,
+        Err(Error::QuerySyntax { position: 10, .. })
+    ));
+
+    cleanup_temp_dir(&base);
+}
+
+#[test]
+fn highlight_terms_skip_operators_filters_and_negations() {
+    assert_eq!(
+        highlight_terms("report AND NOT draft ext:pdf \"revenue plan\""),
+        vec!["report".to_string(), "revenue plan".to_string()]
+    );
+}
+
 fn build_index(index_dir: &Path, files: &[PathBuf]) {
     let mut index = SearchIndex::open(index_dir).expect("open index");
     for file in files {
@@ -1,50 +1,87 @@
+mod explain;
+mod facets;
 mod folders;
 mod jobs;
 mod shortcuts;
+mod similar;
+mod suggestions;
 mod watcher;
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
 use std::sync::mpsc::Receiver;
+use std::sync::Arc;
 use std::time::Duration;
 
 use eframe::egui;
+use regex::Regex;
+use sotis_core::cancel::CancellationToken;
 use sotis_core::config::Config;
 use sotis_core::extract;
-use sotis_core::index::SearchIndex;
-use sotis_core::search::{QueryMode, SearchEngine, SearchMode, SearchResult};
+use sotis_core::index::{BuildProgress, SearchIndex};
+use sotis_core::search::{
+    highlight_terms, FacetFilters, Facets, MatchOptions, ModifiedBucket, PageToken, QueryMode,
+    RangeFilter, SearchEngine, SearchEvent, SearchMode, SearchResult,
+};
 use sotis_core::watcher::FsWatcher;
 
+use self::explain::ExplainJobResult;
 use self::jobs::{ReindexJobResult, SearchJobResult};
+use self::similar::SimilarJobResult;
+use self::suggestions::CompletionJobResult;
 use crate::filters::{
-    default_file_type_filters, extension_allowed, file_size_text, format_unix_hh_mm_utc,
-    parse_megabytes_input, size_allowed, FileTypeFilter,
+    default_file_type_filters, file_size_text, format_unix_hh_mm_utc, FileTypeFilter,
+};
+use crate::preview::{
+    build_highlight_job, build_line_match_job, build_regex_highlight_job, build_snippet_job,
+    find_all_match_positions, find_regex_match_positions,
 };
-use crate::preview::{build_highlight_job, find_all_match_positions};
 
-const RESULTS_LIMIT: usize = 100;
+const LINE_MATCHES_SHOWN: usize = 5;
 
 pub struct SotisApp {
     query: String,
     query_mode: QueryMode,
     search_mode: SearchMode,
+    match_options: MatchOptions,
     raw_results: Vec<SearchResult>,
     results: Vec<SearchResult>,
+    total_hits: usize,
+    next_page: Option<PageToken>,
+    facets: Facets,
+    /// Corrected query offered when the last search found nothing.
+    suggestion: Option<String>,
+    completions: Vec<String>,
+    /// Score breakdowns of the current results, shown as tooltips.
+    explanations: HashMap<PathBuf, String>,
     selected_path: Option<PathBuf>,
     preview_text: String,
     match_positions: Vec<usize>,
     current_match_index: usize,
     should_scroll_to_match: bool,
+    /// Exact matches of the last search, when its options or mode make folding irrelevant.
+    preview_regex: Option<Regex>,
     last_query: String,
     last_query_mode: QueryMode,
     last_search_mode: SearchMode,
+    ranking_profile: String,
+    last_ranking_profile: String,
+    last_filters: FacetFilters,
+    last_size: RangeFilter,
+    last_match_options: MatchOptions,
+    last_scopes: Vec<PathBuf>,
     status: String,
     search_index: Option<SearchIndex>,
+    /// Engine shared by every search job; opened again if it failed to open.
+    search_engine: Option<Arc<SearchEngine>>,
     config: Config,
     fs_watcher: Option<FsWatcher>,
     new_folder_recursive: bool,
     selected_folder_index: Option<usize>,
+    search_in_selected_folder: bool,
     file_type_filters: Vec<FileTypeFilter>,
+    folder_filters: Vec<PathBuf>,
+    modified_filters: Vec<ModifiedBucket>,
     indexed_extensions: HashSet<String>,
     min_size_mb: String,
     max_size_mb: String,
@@ -57,7 +94,19 @@ pub struct SotisApp {
     is_searching: bool,
     is_reindexing: bool,
     search_job_rx: Option<Receiver<SearchJobResult>>,
+    /// Progress of the running search, for showing hits before it finishes.
+    search_events_rx: Option<Receiver<SearchEvent>>,
+    search_cancel: Option<CancellationToken>,
+    /// Whether the results are unranked hits of a search still running.
+    showing_partial_results: bool,
     reindex_job_rx: Option<Receiver<ReindexJobResult>>,
+    reindex_progress_rx: Option<Receiver<BuildProgress>>,
+    /// Latest progress of the running reindex; `None` while folders are still scanned.
+    reindex_progress: Option<BuildProgress>,
+    reindex_cancel: Option<CancellationToken>,
+    completion_job_rx: Option<Receiver<CompletionJobResult>>,
+    similar_job_rx: Option<Receiver<SimilarJobResult>>,
+    explain_job_rx: Option<Receiver<ExplainJobResult>>,
 }
 
 impl Default for SotisApp {
@@ -80,31 +129,53 @@ impl Default for SotisApp {
             }
         };
 
-        if let Err(err) = SearchEngine::open_default() {
-            status = format!("Search error: {err}");
-        }
+        let search_engine = match SearchEngine::open_default() {
+            Ok(engine) => Some(Arc::new(engine)),
+            Err(err) => {
+                status = format!("Search error: {err}");
+                None
+            }
+        };
 
         let mut app = Self {
             query: String::new(),
             query_mode: QueryMode::Fuzzy,
             search_mode: SearchMode::Combined,
+            match_options: MatchOptions::default(),
             raw_results: Vec::new(),
             results: Vec::new(),
+            total_hits: 0,
+            next_page: None,
+            facets: Facets::default(),
+            suggestion: None,
+            completions: Vec::new(),
+            explanations: HashMap::new(),
             selected_path: None,
             preview_text: String::new(),
             match_positions: Vec::new(),
             current_match_index: 0,
             should_scroll_to_match: false,
+            preview_regex: None,
             last_query: String::new(),
             last_query_mode: QueryMode::Fuzzy,
             last_search_mode: SearchMode::Combined,
+            ranking_profile: config.general.ranking_profile.clone(),
+            last_ranking_profile: String::new(),
+            last_filters: FacetFilters::default(),
+            last_size: RangeFilter::default(),
+            last_match_options: MatchOptions::default(),
+            last_scopes: Vec::new(),
             status,
             search_index,
+            search_engine,
             config,
             fs_watcher: None,
             new_folder_recursive: true,
             selected_folder_index: None,
+            search_in_selected_folder: false,
             file_type_filters: default_file_type_filters(),
+            folder_filters: Vec::new(),
+            modified_filters: Vec::new(),
             indexed_extensions: HashSet::new(),
             min_size_mb: String::new(),
             max_size_mb: String::new(),
@@ -117,10 +188,28 @@ impl Default for SotisApp {
             is_searching: false,
             is_reindexing: false,
             search_job_rx: None,
+            search_events_rx: None,
+            search_cancel: None,
+            showing_partial_results: false,
             reindex_job_rx: None,
+            reindex_progress_rx: None,
+            reindex_progress: None,
+            reindex_cancel: None,
+            completion_job_rx: None,
+            similar_job_rx: None,
+            explain_job_rx: None,
         };
         app.refresh_indexed_extensions();
         app.restart_watcher();
+        // An index from an older schema was replaced by an empty one; refill it.
+        if app
+            .search_index
+            .as_ref()
+            .is_some_and(SearchIndex::was_rebuilt)
+        {
+            app.start_rebuild_index(false);
+            app.status = "Index format changed; rebuilding index...".to_string();
+        }
         app
     }
 }
@@ -139,6 +228,10 @@ impl eframe::App for SotisApp {
                     response.request_focus();
                     self.focus_search_bar = false;
                 }
+                if response.changed() {
+                    self.cancel_search();
+                    self.request_completions();
+                }
                 let trigger_with_enter =
                     response.lost_focus() && ui.input(|input| input.key_pressed(egui::Key::Enter));
                 if trigger_with_enter {
@@ -178,6 +271,27 @@ impl eframe::App for SotisApp {
                 {
                     self.query_mode = QueryMode::Regex;
                 }
+                if ui
+                    .selectable_label(matches!(self.query_mode, QueryMode::Grep), "Grep")
+                    .on_hover_text(
+                        "Regex over whole lines of document text; ignores case unless Aa is on",
+                    )
+                    .clicked()
+                {
+                    self.query_mode = QueryMode::Grep;
+                }
+                ui.toggle_value(&mut self.match_options.case_sensitive, "Aa")
+                    .on_hover_text("Match case");
+                // Grep users tend to expect case-sensitive patterns, so say which applies.
+                if matches!(self.query_mode, QueryMode::Grep) {
+                    ui.weak(if self.match_options.case_sensitive {
+                        "case-sensitive"
+                    } else {
+                        "ignoring case"
+                    });
+                }
+                ui.toggle_value(&mut self.match_options.whole_word, "Word")
+                    .on_hover_text("Match whole words only");
 
                 ui.separator();
                 ui.label("Search In:");
@@ -205,7 +319,11 @@ impl eframe::App for SotisApp {
                 {
                     self.search_mode = SearchMode::ContentOnly;
                 }
+
+                ui.separator();
+                self.render_ranking_profile_picker(ui);
             });
+            self.render_completions(ui);
         });
 
         egui::SidePanel::left("filters_and_folders")
@@ -230,17 +348,18 @@ impl eframe::App for SotisApp {
                 .map(format_unix_hh_mm_utc)
                 .unwrap_or_else(|| "never".to_string());
             ui.label(format!(
-                "{} | indexed docs: {} | last index: {} | index errors: {} | results: {}",
+                "{} | indexed docs: {} | last index: {} | index errors: {} | results: {} of {}",
                 self.status,
                 self.indexed_docs,
                 last_build,
                 self.index_error_count,
-                self.results.len()
+                self.results.len(),
+                self.total_hits
             ));
             if self.is_reindexing {
                 ui.horizontal(|ui| {
                     ui.add(egui::Spinner::new());
-                    ui.label("indexing in progress...");
+                    ui.label(self.reindex_progress_text());
                 });
             }
             if !self.pending_pdf_ocr_paths.is_empty() {
@@ -256,35 +375,29 @@ impl eframe::App for SotisApp {
 }
 
 impl SotisApp {
-    fn render_filters_panel(&mut self, ui: &mut egui::Ui) {
-        ui.heading("Filters");
-        ui.label("File types:");
-
-        let mut changed = false;
-        let indexed_extensions = &self.indexed_extensions;
-        for filter in &mut self.file_type_filters {
-            if !filter
-                .extensions
-                .iter()
-                .any(|ext| indexed_extensions.contains(*ext))
-            {
-                continue;
-            }
-            changed |= ui.checkbox(&mut filter.enabled, filter.label).changed();
-        }
+    fn render_ranking_profile_picker(&mut self, ui: &mut egui::Ui) {
+        let names: Vec<String> = self
+            .config
+            .ranking_profiles
+            .iter()
+            .map(|profile| profile.name.clone())
+            .collect();
 
-        ui.separator();
-        ui.horizontal(|ui| {
-            ui.label("Min MB:");
-            changed |= ui.text_edit_singleline(&mut self.min_size_mb).changed();
-        });
-        ui.horizontal(|ui| {
-            ui.label("Max MB:");
-            changed |= ui.text_edit_singleline(&mut self.max_size_mb).changed();
-        });
+        ui.label("Rank:");
+        let mut selected = self.ranking_profile.clone();
+        egui::ComboBox::from_id_salt("ranking_profile")
+            .selected_text(&selected)
+            .show_ui(ui, |ui| {
+                for name in names {
+                    ui.selectable_value(&mut selected, name.clone(), name);
+                }
+            });
 
-        if changed {
-            self.apply_client_filters();
+        if selected != self.ranking_profile {
+            self.ranking_profile = selected;
+            if !self.last_query.is_empty() {
+                self.submit_search();
+            }
         }
     }
 
@@ -293,34 +406,88 @@ impl SotisApp {
         ui.separator();
 
         if self.results.is_empty() {
-            ui.label("Press Enter or click Search");
+            if self.last_query.is_empty() {
+                ui.label("Press Enter or click Search");
+            } else {
+                self.render_no_results(ui);
+            }
             return;
         }
 
+        let mut similar_source = None;
         egui::ScrollArea::vertical()
             .id_salt("results")
             .show(ui, |ui| {
                 for index in 0..self.results.len() {
-                    let (path, filename, score, size) = {
+                    let (path, filename, score, size, snippets, line_matches) = {
                         let result = &self.results[index];
                         (
                             result.path.clone(),
                             result.filename.clone(),
                             result.score,
                             file_size_text(&result.path),
+                            result.snippets.clone(),
+                            result.line_matches.clone(),
                         )
                     };
 
                     let is_selected = self.selected_path.as_ref() == Some(&path);
                     let label = format!("{} ({:.2})", filename, score);
-                    if ui.selectable_label(is_selected, label).clicked() {
-                        self.select_result(index);
-                    }
+                    ui.horizontal(|ui| {
+                        let response = ui.selectable_label(is_selected, label);
+                        let response = match self.explanation_text(&path, response.hovered()) {
+                            Some(text) => response.on_hover_text(text),
+                            None => response,
+                        };
+                        if response.clicked() {
+                            self.select_result(index);
+                        }
+                        if ui
+                            .add_enabled(!self.is_searching, egui::Button::new("Similar").small())
+                            .on_hover_text("Find files with similar content")
+                            .clicked()
+                        {
+                            similar_source = Some(path.clone());
+                        }
+                    });
                     ui.label(path.display().to_string());
                     ui.label(size);
+                    if line_matches.is_empty() {
+                        for snippet in &snippets {
+                            ui.label(build_snippet_job(snippet));
+                        }
+                    } else {
+                        for line_match in line_matches.iter().take(LINE_MATCHES_SHOWN) {
+                            ui.label(build_line_match_job(line_match));
+                        }
+                        if line_matches.len() > LINE_MATCHES_SHOWN {
+                            ui.label(format!(
+                                "... {} more matching lines",
+                                line_matches.len() - LINE_MATCHES_SHOWN
+                            ));
+                        }
+                    }
                     ui.separator();
                 }
+
+                if self.next_page.is_some() {
+                    let label = format!(
+                        "Load more ({} of {} loaded)",
+                        self.raw_results.len(),
+                        self.total_hits
+                    );
+                    if ui
+                        .add_enabled(!self.is_searching, egui::Button::new(label))
+                        .clicked()
+                    {
+                        self.load_more_results();
+                    }
+                }
             });
+
+        if let Some(source) = similar_source {
+            self.find_similar(source);
+        }
     }
 
     fn render_preview_panel(&mut self, ui: &mut egui::Ui) {
@@ -358,13 +525,17 @@ impl SotisApp {
         ui.separator();
 
         let selected_line = self.selected_match_line();
-        let query = self.last_query.trim().to_string();
+        let query = self.preview_query();
         let mut should_scroll = self.should_scroll_to_match;
         egui::ScrollArea::vertical()
             .id_salt("preview")
             .show(ui, |ui| {
                 for (line_idx, line) in self.preview_text.lines().enumerate() {
-                    let response = ui.label(build_highlight_job(line, &query));
+                    let job = match &self.preview_regex {
+                        Some(regex) => build_regex_highlight_job(line, regex),
+                        None => build_highlight_job(line, &query),
+                    };
+                    let response = ui.label(job);
                     if should_scroll && selected_line == Some(line_idx) {
                         ui.scroll_to_rect(response.rect, Some(egui::Align::Center));
                         should_scroll = false;
@@ -375,19 +546,13 @@ impl SotisApp {
     }
 
     fn apply_client_filters(&mut self) {
-        let allowed_extensions = self.enabled_extensions();
-        let min_size_bytes = parse_megabytes_input(&self.min_size_mb);
-        let max_size_bytes = parse_megabytes_input(&self.max_size_mb);
-
-        self.results = self
-            .raw_results
-            .iter()
-            .filter(|result| {
-                extension_allowed(&result.path, &allowed_extensions)
-                    && size_allowed(&result.path, min_size_bytes, max_size_bytes)
-            })
-            .cloned()
-            .collect();
+        // Every filter runs in the search itself. An empty extension filter means any type
+        // there, so unchecking every type is handled here.
+        self.results = if self.enabled_extensions().is_empty() {
+            Vec::new()
+        } else {
+            self.raw_results.clone()
+        };
 
         if self.results.is_empty() {
             self.selected_path = None;
@@ -457,8 +622,10 @@ impl SotisApp {
         ) {
             Ok(text) => {
                 self.preview_text = text;
-                self.match_positions =
-                    find_all_match_positions(&self.preview_text, self.last_query.trim());
+                self.match_positions = match &self.preview_regex {
+                    Some(regex) => find_regex_match_positions(&self.preview_text, regex),
+                    None => find_all_match_positions(&self.preview_text, &self.preview_query()),
+                };
                 self.current_match_index = 0;
                 self.should_scroll_to_match = !self.match_positions.is_empty();
             }
@@ -471,6 +638,13 @@ impl SotisApp {
         }
     }
 
+    fn preview_query(&self) -> String {
+        match self.last_query_mode {
+            QueryMode::Fuzzy => highlight_terms(self.last_query.trim()).join(" "),
+            QueryMode::Regex | QueryMode::Grep => self.last_query.trim().to_string(),
+        }
+    }
+
     fn selected_match_line(&self) -> Option<usize> {
         let offset = *self.match_positions.get(self.current_match_index)?;
         Some(
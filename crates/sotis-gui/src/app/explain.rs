@@ -0,0 +1,93 @@
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+
+use sotis_core::search::ScoreExplanation;
+
+use crate::app::SotisApp;
+
+pub(super) struct ExplainJobResult {
+    pub(super) path: PathBuf,
+    pub(super) result: std::result::Result<Option<ScoreExplanation>, String>,
+}
+
+impl SotisApp {
+    /// Score breakdown tooltip for a result, computed in the background on first hover.
+    pub(super) fn explanation_text(&mut self, path: &Path, hovered: bool) -> Option<String> {
+        // Similar-file results have no query to explain.
+        if !hovered || self.last_query.is_empty() {
+            return None;
+        }
+        if let Some(text) = self.explanations.get(path) {
+            return Some(text.clone());
+        }
+
+        if self.explain_job_rx.is_none() {
+            let request = self.last_search_request();
+            let job_path = path.to_path_buf();
+            let (tx, rx) = mpsc::channel();
+            self.explain_job_rx = Some(rx);
+            let engine = self.search_engine();
+            thread::spawn(move || {
+                let result = engine
+                    .and_then(|engine| engine.explain(&request, &job_path))
+                    .map_err(|err| err.to_string());
+                let _ = tx.send(ExplainJobResult {
+                    path: job_path,
+                    result,
+                });
+            });
+        }
+        Some("Computing score breakdown...".to_string())
+    }
+
+    pub(super) fn poll_explain_job(&mut self) {
+        let Some(receiver) = &self.explain_job_rx else {
+            return;
+        };
+        let Ok(job) = receiver.try_recv() else {
+            return;
+        };
+
+        self.explain_job_rx = None;
+        let text = match job.result {
+            Ok(Some(explanation)) => explanation_text(&explanation),
+            Ok(None) => "File is no longer indexed".to_string(),
+            Err(err) => format!("Score breakdown failed: {err}"),
+        };
+        self.explanations.insert(job.path, text);
+    }
+}
+
+fn explanation_text(explanation: &ScoreExplanation) -> String {
+    let channel =
+        |name: &str, raw: Option<f32>, divisor: f32, normalized: f32, weight: f32| match raw {
+            Some(raw) => {
+                format!("{name}: {raw:.3} / {divisor:.3} = {normalized:.3}, weight {weight:.2}")
+            }
+            None => format!("{name}: no match, weight {weight:.2}"),
+        };
+
+    [
+        channel(
+            "Content",
+            explanation.content_raw,
+            explanation.content_divisor,
+            explanation.content_score(),
+            explanation.content_weight,
+        ),
+        channel(
+            "Filename",
+            explanation.filename_raw,
+            explanation.filename_divisor,
+            explanation.filename_score(),
+            explanation.filename_weight,
+        ),
+        format!(
+            "Recency x{:.2}, folder x{:.2}",
+            explanation.recency_factor, explanation.folder_factor
+        ),
+        format!("Score: {:.3}", explanation.score),
+    ]
+    .join("\n")
+}
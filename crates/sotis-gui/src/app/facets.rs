@@ -0,0 +1,139 @@
+use std::path::PathBuf;
+
+use eframe::egui;
+use sotis_core::search::{FacetFilters, ModifiedBucket, RangeFilter};
+
+use crate::app::SotisApp;
+use crate::filters::{extension_hit_count, parse_megabytes_input};
+
+impl SotisApp {
+    pub(super) fn render_filters_panel(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Filters");
+        // Counts only describe the last search; before one runs there is nothing to count.
+        let show_counts = !self.last_query.is_empty();
+        let count_label = |label: &str, count: usize| {
+            if show_counts {
+                format!("{label} ({count})")
+            } else {
+                label.to_string()
+            }
+        };
+
+        let mut facets_changed = false;
+        ui.label("File types:");
+        let indexed_extensions = &self.indexed_extensions;
+        for filter in &mut self.file_type_filters {
+            if !filter
+                .extensions
+                .iter()
+                .any(|ext| indexed_extensions.contains(*ext))
+            {
+                continue;
+            }
+            let count = extension_hit_count(&self.facets.extensions, filter.extensions);
+            facets_changed |= ui
+                .checkbox(&mut filter.enabled, count_label(filter.label, count))
+                .changed();
+        }
+
+        let top_level_folders = self.top_level_folders();
+        if !top_level_folders.is_empty() {
+            ui.separator();
+            ui.label("Folders:");
+            for folder in top_level_folders {
+                let count = self.facets.folders.get(&folder).copied().unwrap_or(0);
+                let mut selected = self.folder_filters.contains(&folder);
+                let label = count_label(&folder.display().to_string(), count);
+                if ui.checkbox(&mut selected, label).changed() {
+                    if selected {
+                        self.folder_filters.push(folder);
+                    } else {
+                        self.folder_filters.retain(|path| path != &folder);
+                    }
+                    facets_changed = true;
+                }
+            }
+        }
+
+        ui.separator();
+        ui.label("Modified:");
+        for bucket in ModifiedBucket::ALL {
+            let count = self.facets.modified.get(&bucket).copied().unwrap_or(0);
+            let mut selected = self.modified_filters.contains(&bucket);
+            if ui
+                .checkbox(&mut selected, count_label(bucket.label(), count))
+                .changed()
+            {
+                if selected {
+                    self.modified_filters.push(bucket);
+                } else {
+                    self.modified_filters.retain(|other| *other != bucket);
+                }
+                facets_changed = true;
+            }
+        }
+
+        // Size bounds apply once editing finishes, not on every keystroke.
+        let mut size_committed = false;
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.label("Min MB:");
+            size_committed |= ui.text_edit_singleline(&mut self.min_size_mb).lost_focus();
+        });
+        ui.horizontal(|ui| {
+            ui.label("Max MB:");
+            size_committed |= ui.text_edit_singleline(&mut self.max_size_mb).lost_focus();
+        });
+
+        if (facets_changed || size_committed) && !self.last_query.is_empty() {
+            self.submit_search();
+        }
+    }
+
+    /// Size bounds sent with the next search; unparsable inputs leave that side open.
+    pub(super) fn size_filter(&self) -> RangeFilter {
+        RangeFilter::new(
+            parse_megabytes_input(&self.min_size_mb),
+            parse_megabytes_input(&self.max_size_mb),
+        )
+    }
+
+    /// Facet selections sent with the next search.
+    pub(super) fn facet_filters(&self) -> FacetFilters {
+        let all_types_enabled = self.file_type_filters.iter().all(|filter| filter.enabled);
+        let extensions = if all_types_enabled {
+            Vec::new()
+        } else {
+            self.enabled_extensions()
+                .into_iter()
+                .map(str::to_string)
+                .collect()
+        };
+
+        let top_level_folders = self.top_level_folders();
+        FacetFilters {
+            extensions,
+            folders: self
+                .folder_filters
+                .iter()
+                .filter(|folder| top_level_folders.contains(folder))
+                .cloned()
+                .collect(),
+            modified: self.modified_filters.clone(),
+        }
+    }
+
+    /// Configured folders not nested in another configured folder.
+    fn top_level_folders(&self) -> Vec<PathBuf> {
+        let folders = &self.config.folders;
+        folders
+            .iter()
+            .filter(|folder| {
+                !folders
+                    .iter()
+                    .any(|other| other.path != folder.path && folder.path.starts_with(&other.path))
+            })
+            .map(|folder| folder.path.clone())
+            .collect()
+    }
+}
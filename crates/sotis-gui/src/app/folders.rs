@@ -4,14 +4,57 @@ use std::path::PathBuf;
 use std::process::Command;
 
 use eframe::egui;
+use sotis_core::cancel::CancellationToken;
 use sotis_core::config::FolderEntry;
+use sotis_core::search::Facets;
 
 use crate::app::SotisApp;
-use crate::filters::file_size_text;
+use crate::filters::{file_size_text, format_duration_short};
 
 impl SotisApp {
+    fn render_reindex_progress(&mut self, ui: &mut egui::Ui) {
+        let cancelling = self
+            .reindex_cancel
+            .as_ref()
+            .is_some_and(CancellationToken::is_cancelled);
+        ui.horizontal(|ui| {
+            match &self.reindex_progress {
+                Some(progress) => {
+                    let mut text = format!("{:.0}%", progress.fraction() * 100.0);
+                    if let Some(eta) = progress.eta.filter(|_| progress.extracted > 0) {
+                        text.push_str(&format!(", about {} left", format_duration_short(eta)));
+                    }
+                    ui.add(
+                        egui::ProgressBar::new(progress.fraction())
+                            .desired_width(200.0)
+                            .text(text),
+                    );
+                }
+                None => {
+                    ui.add(egui::Spinner::new());
+                    ui.label("Scanning folders...");
+                }
+            }
+            if ui
+                .add_enabled(!cancelling, egui::Button::new("Cancel"))
+                .on_hover_text("Stop indexing and keep the files indexed so far")
+                .clicked()
+            {
+                self.cancel_reindex();
+            }
+        });
+        if let Some(current) = self
+            .reindex_progress
+            .as_ref()
+            .and_then(|progress| progress.current.as_ref())
+        {
+            ui.small(current.display().to_string());
+        }
+    }
+
     pub(super) fn render_folder_panel(&mut self, ui: &mut egui::Ui) {
         ui.heading("Folders");
+        let mut scope_changed = false;
 
         if self.config.folders.is_empty() {
             ui.label("No indexed folders yet.");
@@ -31,11 +74,21 @@ impl SotisApp {
                             }
                         );
                         if ui.selectable_label(is_selected, label).clicked() {
+                            scope_changed |= self.search_in_selected_folder && !is_selected;
                             self.selected_folder_index = Some(index);
                         }
                     }
                 });
         }
+        scope_changed |= ui
+            .checkbox(
+                &mut self.search_in_selected_folder,
+                "Search only in selected folder",
+            )
+            .changed();
+        if scope_changed && !self.last_query.is_empty() {
+            self.submit_search();
+        }
 
         ui.separator();
         ui.checkbox(&mut self.new_folder_recursive, "Recursive");
@@ -88,10 +141,7 @@ impl SotisApp {
         }
 
         if self.is_reindexing {
-            ui.horizontal(|ui| {
-                ui.add(egui::Spinner::new());
-                ui.label("Indexing...");
-            });
+            self.render_reindex_progress(ui);
         }
 
         if !self.pending_pdf_ocr_paths.is_empty() {
@@ -128,6 +178,17 @@ impl SotisApp {
         }
     }
 
+    /// Folders the next search is limited to; empty searches every indexed folder.
+    pub(super) fn search_scopes(&self) -> Vec<PathBuf> {
+        if !self.search_in_selected_folder {
+            return Vec::new();
+        }
+        self.selected_folder_index
+            .and_then(|index| self.config.folders.get(index))
+            .map(|folder| vec![folder.path.clone()])
+            .unwrap_or_default()
+    }
+
     fn pick_and_add_folder(&mut self) {
         match pick_folder_path() {
             Ok(Some(path)) => self.add_folder(path),
@@ -156,6 +217,7 @@ impl SotisApp {
             path,
             recursive: self.new_folder_recursive,
             extensions: Vec::new(),
+            priority: 1.0,
         });
 
         if let Err(err) = self.config.save() {
@@ -256,6 +318,8 @@ impl SotisApp {
             .unwrap_or_else(|| sotis_core::config::data_dir().join("index"));
 
         self.search_index = None;
+        // The next search opens an engine over the fresh index.
+        self.search_engine = None;
 
         if index_path.exists() {
             if let Err(err) = fs::remove_dir_all(&index_path) {
@@ -270,6 +334,10 @@ impl SotisApp {
                 self.search_index = Some(index);
                 self.raw_results.clear();
                 self.results.clear();
+                self.total_hits = 0;
+                self.next_page = None;
+                self.facets = Facets::default();
+                self.suggestion = None;
                 self.selected_path = None;
                 self.preview_text.clear();
                 self.match_positions.clear();
@@ -1,18 +1,26 @@
 use std::collections::HashSet;
 use std::sync::mpsc;
+use std::sync::Arc;
 use std::thread;
 
+use sotis_core::cancel::CancellationToken;
+use sotis_core::error::{Error, Result};
 use sotis_core::index::BuildStats;
-use sotis_core::search::{QueryMode, SearchEngine, SearchMode, SearchResult};
+use sotis_core::search::{
+    match_regex, Facets, QueryMode, SearchEngine, SearchEvent, SearchMode, SearchPage,
+    SearchRequest, TermMatching, DEFAULT_PAGE_SIZE,
+};
 
-use crate::app::{SotisApp, RESULTS_LIMIT};
-use crate::filters::current_unix_secs;
+use crate::app::SotisApp;
+use crate::filters::{current_unix_secs, format_duration_short};
 
 pub(super) struct SearchJobResult {
-    pub(super) query: String,
-    pub(super) query_mode: QueryMode,
-    pub(super) search_mode: SearchMode,
-    pub(super) result: std::result::Result<Vec<SearchResult>, String>,
+    pub(super) request: SearchRequest,
+    /// Whether the page extends the current results instead of replacing them.
+    pub(super) append: bool,
+    pub(super) result: Result<SearchPage>,
+    /// "Did you mean" rewrite of a fuzzy query without hits.
+    pub(super) suggestion: Option<String>,
 }
 
 pub(super) struct ReindexJobSuccess {
@@ -27,15 +35,16 @@ pub(super) struct ReindexJobResult {
 
 impl SotisApp {
     pub(super) fn submit_search(&mut self) {
-        if self.is_searching {
-            return;
-        }
-
         let trimmed = self.query.trim();
         if trimmed.is_empty() {
             self.last_query.clear();
             self.raw_results.clear();
             self.results.clear();
+            self.total_hits = 0;
+            self.next_page = None;
+            self.facets = Facets::default();
+            self.suggestion = None;
+            self.preview_regex = None;
             self.selected_path = None;
             self.preview_text.clear();
             self.status = "SOTIS — Ready".to_string();
@@ -50,30 +59,33 @@ impl SotisApp {
         let query = trimmed.to_string();
         let query_mode = self.query_mode;
         let search_mode = self.search_mode;
+        let filters = self.facet_filters();
+        let size = self.size_filter();
+        let match_options = self.match_options;
+        let scopes = self.search_scopes();
 
         if self.last_query == query
             && self.last_query_mode == query_mode
             && self.last_search_mode == search_mode
+            && self.last_ranking_profile == self.ranking_profile
+            && self.last_filters == filters
+            && self.last_size == size
+            && self.last_match_options == match_options
+            && self.last_scopes == scopes
         {
             return;
         }
 
-        let (tx, rx) = mpsc::channel();
-        self.search_job_rx = Some(rx);
-        self.is_searching = true;
+        // A newer search supersedes one still running.
+        self.cancel_search();
         self.status = format!("Searching for '{query}'...");
-
-        thread::spawn(move || {
-            let result = SearchEngine::open_default()
-                .and_then(|engine| engine.search(&query, query_mode, search_mode, RESULTS_LIMIT))
-                .map_err(|err| err.to_string());
-            let _ = tx.send(SearchJobResult {
-                query,
-                query_mode,
-                search_mode,
-                result,
-            });
-        });
+        let request = self
+            .search_request(&query, query_mode, search_mode, &self.ranking_profile)
+            .with_filters(filters)
+            .with_size(size)
+            .with_match_options(match_options)
+            .with_scopes(scopes);
+        self.spawn_search(request, false);
     }
 
     pub(super) fn rerun_last_search(&mut self) {
@@ -81,31 +93,170 @@ impl SotisApp {
             return;
         }
 
-        let query = self.last_query.clone();
-        let query_mode = self.last_query_mode;
-        let search_mode = self.last_search_mode;
+        // Reruns follow index changes, so pick them up without waiting for the reader.
+        if let Some(engine) = &self.search_engine {
+            if let Err(err) = engine.reload() {
+                self.status = format!("Search error: {err}");
+                return;
+            }
+        }
+
+        // Refresh everything already loaded rather than collapsing back to one page.
+        let loaded = self.raw_results.len().max(DEFAULT_PAGE_SIZE);
+        let request = self.last_search_request().with_page(0, loaded);
+        self.status = format!("Refreshing search for '{}'...", self.last_query);
+        self.spawn_search(request, false);
+    }
 
+    pub(super) fn load_more_results(&mut self) {
+        if self.is_searching || self.is_reindexing {
+            return;
+        }
+        let Some(token) = &self.next_page else {
+            return;
+        };
+
+        let request = self.last_search_request().after(token);
+        match request {
+            Ok(request) => {
+                self.status = format!("Loading more results for '{}'...", self.last_query);
+                self.spawn_search(request, true);
+            }
+            Err(err) => {
+                self.next_page = None;
+                self.status = format!("Search failed: {err}");
+            }
+        }
+    }
+
+    /// The engine for a background job, opening it if that failed before.
+    pub(super) fn search_engine(&mut self) -> Result<Arc<SearchEngine>> {
+        if let Some(engine) = &self.search_engine {
+            return Ok(Arc::clone(engine));
+        }
+        let engine = Arc::new(SearchEngine::open_default()?);
+        self.search_engine = Some(Arc::clone(&engine));
+        Ok(engine)
+    }
+
+    /// The request behind the current results, from its first page.
+    pub(super) fn last_search_request(&self) -> SearchRequest {
+        self.search_request(
+            &self.last_query,
+            self.last_query_mode,
+            self.last_search_mode,
+            &self.last_ranking_profile,
+        )
+        .with_filters(self.last_filters.clone())
+        .with_size(self.last_size)
+        .with_match_options(self.last_match_options)
+        .with_scopes(self.last_scopes.clone())
+    }
+
+    fn search_request(
+        &self,
+        query: &str,
+        query_mode: QueryMode,
+        search_mode: SearchMode,
+        ranking_profile: &str,
+    ) -> SearchRequest {
+        SearchRequest::new(query, query_mode, search_mode)
+            .with_ranking(
+                self.config.ranking_profile(ranking_profile),
+                &self.config.folders,
+            )
+            .with_term_matching(TermMatching::from_config(&self.config.general))
+    }
+
+    /// Abandons the running search, keeping whatever results are shown.
+    pub(super) fn cancel_search(&mut self) {
+        let Some(cancel) = self.search_cancel.take() else {
+            return;
+        };
+        cancel.cancel();
+        self.search_job_rx = None;
+        self.search_events_rx = None;
+        self.showing_partial_results = false;
+        self.is_searching = false;
+        self.status = "Search cancelled".to_string();
+    }
+
+    fn spawn_search(&mut self, request: SearchRequest, append: bool) {
         let (tx, rx) = mpsc::channel();
+        let (events_tx, events_rx) = mpsc::channel();
+        let cancel = CancellationToken::new();
         self.search_job_rx = Some(rx);
+        // Pages loaded with "Load more" extend ranked results, so only new searches
+        // show hits early.
+        self.search_events_rx = (!append).then_some(events_rx);
+        self.search_cancel = Some(cancel.clone());
         self.is_searching = true;
-        self.status = format!("Refreshing search for '{query}'...");
+        self.completions.clear();
+        self.completion_job_rx = None;
+        let engine = self.search_engine();
 
         thread::spawn(move || {
-            let result = SearchEngine::open_default()
-                .and_then(|engine| engine.search(&query, query_mode, search_mode, RESULTS_LIMIT))
-                .map_err(|err| err.to_string());
+            let mut suggestion = None;
+            let result = engine.and_then(|engine| {
+                let page = if append {
+                    engine.search_page(&request)?
+                } else {
+                    engine.search_stream(&request, &cancel, &events_tx)?
+                };
+                if page.total_hits == 0 && request.query_mode == QueryMode::Fuzzy {
+                    // A failed lookup only costs the hint, not the search.
+                    suggestion = engine.did_you_mean(&request.query_text).ok().flatten();
+                }
+                Ok(page)
+            });
             let _ = tx.send(SearchJobResult {
-                query,
-                query_mode,
-                search_mode,
+                request,
+                append,
                 result,
+                suggestion,
             });
         });
     }
 
     pub(super) fn poll_background_jobs(&mut self) {
+        self.poll_search_events();
         self.poll_search_job();
+        self.poll_reindex_progress();
         self.poll_reindex_job();
+        self.poll_completion_job();
+        self.poll_similar_job();
+        self.poll_explain_job();
+    }
+
+    /// Shows the hits a running scan has confirmed until the ranked page arrives.
+    fn poll_search_events(&mut self) {
+        let Some(receiver) = &self.search_events_rx else {
+            return;
+        };
+        // Ranked batches are ignored; the whole page arrives with the job result.
+        let partial: Vec<_> = receiver
+            .try_iter()
+            .filter_map(|event| match event {
+                SearchEvent::Partial(results) => Some(results),
+                SearchEvent::Ranked(_) => None,
+            })
+            .flatten()
+            .collect();
+        if partial.is_empty() {
+            return;
+        }
+
+        if !self.showing_partial_results {
+            self.showing_partial_results = true;
+            self.raw_results.clear();
+            self.next_page = None;
+            self.suggestion = None;
+            self.explanations.clear();
+        }
+        self.raw_results.extend(partial);
+        self.total_hits = self.raw_results.len();
+        self.status = format!("Searching... {} files found so far", self.total_hits);
+        self.apply_client_filters();
     }
 
     fn poll_search_job(&mut self) {
@@ -117,20 +268,44 @@ impl SotisApp {
         };
 
         self.search_job_rx = None;
+        self.search_events_rx = None;
+        self.search_cancel = None;
+        self.showing_partial_results = false;
         self.is_searching = false;
 
         match job.result {
-            Ok(results) => {
-                self.last_query = job.query;
-                self.last_query_mode = job.query_mode;
-                self.last_search_mode = job.search_mode;
+            Ok(page) => {
+                // A pattern that fails here already failed the search itself.
+                self.preview_regex = match_regex(&job.request).ok().flatten();
+                self.last_query = job.request.query_text;
+                self.last_query_mode = job.request.query_mode;
+                self.last_search_mode = job.request.search_mode;
+                self.last_ranking_profile = job.request.ranking.name;
+                self.last_filters = job.request.filters;
+                self.last_size = job.request.size;
+                self.last_match_options = job.request.match_options;
+                self.last_scopes = job.request.scopes;
                 self.status = format!("Search completed for '{}'", self.last_query);
-                self.raw_results = results;
+                if !job.append {
+                    self.raw_results.clear();
+                }
+                self.raw_results.extend(page.results);
+                self.total_hits = page.total_hits;
+                self.next_page = page.next_page;
+                self.facets = page.facets;
+                self.suggestion = job.suggestion;
+                self.explanations.clear();
                 self.apply_client_filters();
             }
+            // The index changed under the loaded pages, so reload them all instead.
+            Err(Error::StalePage) if job.append => self.rerun_last_search(),
             Err(err) => {
                 self.raw_results.clear();
                 self.results.clear();
+                self.total_hits = 0;
+                self.next_page = None;
+                self.facets = Facets::default();
+                self.suggestion = None;
                 self.selected_path = None;
                 self.preview_text.clear();
                 self.status = format!("Search failed: {err}");
@@ -138,6 +313,42 @@ impl SotisApp {
         }
     }
 
+    /// Summary of the running reindex for the status bar.
+    pub(super) fn reindex_progress_text(&self) -> String {
+        let Some(progress) = &self.reindex_progress else {
+            return "indexing: scanning folders...".to_string();
+        };
+        let mut text = format!(
+            "indexing: {}/{} files ({} indexed, {} unchanged, {} failed)",
+            progress.extracted + progress.skipped + progress.failed,
+            progress.total,
+            progress.extracted,
+            progress.skipped,
+            progress.failed
+        );
+        if let Some(eta) = progress.eta.filter(|_| progress.extracted > 0) {
+            text.push_str(&format!(", about {} left", format_duration_short(eta)));
+        }
+        text
+    }
+
+    /// Stops the running reindex; the files indexed so far are kept.
+    pub(super) fn cancel_reindex(&mut self) {
+        if let Some(cancel) = &self.reindex_cancel {
+            cancel.cancel();
+            self.status = "Cancelling indexing...".to_string();
+        }
+    }
+
+    fn poll_reindex_progress(&mut self) {
+        let Some(receiver) = &self.reindex_progress_rx else {
+            return;
+        };
+        if let Some(progress) = receiver.try_iter().last() {
+            self.reindex_progress = Some(progress);
+        }
+    }
+
     fn poll_reindex_job(&mut self) {
         let Some(receiver) = &self.reindex_job_rx else {
             return;
@@ -147,10 +358,18 @@ impl SotisApp {
         };
 
         self.reindex_job_rx = None;
+        self.reindex_progress_rx = None;
+        self.reindex_progress = None;
+        self.reindex_cancel = None;
         self.is_reindexing = false;
 
         match job.result {
             Ok(success) => {
+                let outcome = if success.stats.cancelled {
+                    "Reindex cancelled"
+                } else {
+                    "Reindex complete"
+                };
                 self.index_error_count = success.stats.errors.len();
                 self.indexed_docs = success.doc_count;
                 self.pending_pdf_ocr_paths = success.stats.ocr_pending.clone();
@@ -158,16 +377,18 @@ impl SotisApp {
                 self.last_build_unix_secs = Some(current_unix_secs());
                 self.status = if success.stats.ocr_pending.is_empty() {
                     format!(
-                        "Reindex complete: added {}, already added {}, errors {}",
+                        "{outcome}: added {}, already added {}, removed {}, errors {}",
                         success.stats.added,
                         success.stats.skipped,
+                        success.stats.removed,
                         success.stats.errors.len()
                     )
                 } else {
                     format!(
-                        "Reindex complete: added {}, already added {}, errors {}, OCR pending {}",
+                        "{outcome}: added {}, already added {}, removed {}, errors {}, OCR pending {}",
                         success.stats.added,
                         success.stats.skipped,
+                        success.stats.removed,
                         success.stats.errors.len(),
                         success.stats.ocr_pending.len()
                     )
@@ -189,7 +410,12 @@ impl SotisApp {
         let folders = self.config.folders.clone();
         let general = self.config.general.clone();
         let (tx, rx) = mpsc::channel();
+        let (progress_tx, progress_rx) = mpsc::channel();
+        let cancel = CancellationToken::new();
         self.reindex_job_rx = Some(rx);
+        self.reindex_progress_rx = Some(progress_rx);
+        self.reindex_progress = None;
+        self.reindex_cancel = Some(cancel.clone());
         self.is_reindexing = true;
         self.status = "Indexing started...".to_string();
 
@@ -198,7 +424,13 @@ impl SotisApp {
             let result = sotis_core::index::SearchIndex::open_default()
                 .and_then(|mut index| {
                     index
-                        .build_from_scan_with_config(&scan_result, &general, pdf_ocr_approved)
+                        .build_from_scan_with_config(
+                            &scan_result,
+                            &general,
+                            pdf_ocr_approved,
+                            &cancel,
+                            Some(&progress_tx),
+                        )
                         .and_then(|stats| {
                             let doc_count = index.doc_count();
                             let indexed_extensions = index.indexed_extensions()?;
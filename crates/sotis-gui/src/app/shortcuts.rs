@@ -1,4 +1,5 @@
 use eframe::egui;
+use sotis_core::search::Facets;
 
 use crate::app::SotisApp;
 
@@ -27,8 +28,14 @@ impl SotisApp {
         if !self.query.is_empty() {
             self.query.clear();
             self.last_query.clear();
+            self.preview_regex = None;
             self.raw_results.clear();
             self.results.clear();
+            self.total_hits = 0;
+            self.next_page = None;
+            self.facets = Facets::default();
+            self.suggestion = None;
+            self.completions.clear();
             self.status = "Search cleared".to_string();
         }
     }
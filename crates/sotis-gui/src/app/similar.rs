@@ -0,0 +1,69 @@
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::thread;
+
+use sotis_core::search::{Facets, SearchResult, DEFAULT_PAGE_SIZE};
+
+use crate::app::SotisApp;
+
+pub(super) struct SimilarJobResult {
+    pub(super) source: PathBuf,
+    pub(super) result: std::result::Result<Vec<SearchResult>, String>,
+}
+
+impl SotisApp {
+    /// Replaces the results with files whose content resembles `source`.
+    pub(super) fn find_similar(&mut self, source: PathBuf) {
+        if self.is_searching || self.is_reindexing {
+            return;
+        }
+
+        let (tx, rx) = mpsc::channel();
+        self.similar_job_rx = Some(rx);
+        self.is_searching = true;
+        self.status = format!("Finding files similar to {}...", source.display());
+
+        let engine = self.search_engine();
+        thread::spawn(move || {
+            let result = engine
+                .and_then(|engine| engine.similar_to(&source, DEFAULT_PAGE_SIZE))
+                .map_err(|err| err.to_string());
+            let _ = tx.send(SimilarJobResult { source, result });
+        });
+    }
+
+    pub(super) fn poll_similar_job(&mut self) {
+        let Some(receiver) = &self.similar_job_rx else {
+            return;
+        };
+        let Ok(job) = receiver.try_recv() else {
+            return;
+        };
+
+        self.similar_job_rx = None;
+        self.is_searching = false;
+
+        match job.result {
+            Ok(results) => {
+                // Similar files are not a query, so there is nothing to rerun, page or facet.
+                self.last_query.clear();
+                self.preview_regex = None;
+                self.total_hits = results.len();
+                self.raw_results = results;
+                self.next_page = None;
+                self.facets = Facets::default();
+                self.suggestion = None;
+                self.explanations.clear();
+                self.status = format!(
+                    "{} files similar to {}",
+                    self.total_hits,
+                    job.source.display()
+                );
+                self.apply_client_filters();
+            }
+            Err(err) => {
+                self.status = format!("Similar search failed: {err}");
+            }
+        }
+    }
+}
@@ -0,0 +1,144 @@
+use std::sync::mpsc;
+use std::thread;
+
+use eframe::egui;
+use sotis_core::search::QueryMode;
+
+use crate::app::SotisApp;
+
+const COMPLETIONS_SHOWN: usize = 5;
+/// Shorter prefixes match too many terms to be useful.
+const MIN_COMPLETION_PREFIX_CHARS: usize = 2;
+
+pub(super) struct CompletionJobResult {
+    pub(super) query: String,
+    pub(super) completions: Vec<String>,
+}
+
+impl SotisApp {
+    /// Looks up completions for the word being typed in a fuzzy query.
+    pub(super) fn request_completions(&mut self) {
+        self.completions.clear();
+        self.completion_job_rx = None;
+        if self.query_mode != QueryMode::Fuzzy {
+            return;
+        }
+        let Some(prefix) = completion_prefix(&self.query) else {
+            return;
+        };
+
+        let prefix = prefix.to_string();
+        let query = self.query.clone();
+        let (tx, rx) = mpsc::channel();
+        // Replacing the receiver drops the results of any lookup still running.
+        self.completion_job_rx = Some(rx);
+        let engine = self.search_engine();
+        thread::spawn(move || {
+            let completions = engine
+                .and_then(|engine| engine.complete(&prefix, COMPLETIONS_SHOWN))
+                .map(|found| {
+                    found
+                        .into_iter()
+                        .map(|suggestion| suggestion.term)
+                        .collect()
+                })
+                .unwrap_or_default();
+            let _ = tx.send(CompletionJobResult { query, completions });
+        });
+    }
+
+    pub(super) fn poll_completion_job(&mut self) {
+        let Some(receiver) = &self.completion_job_rx else {
+            return;
+        };
+        let Ok(job) = receiver.try_recv() else {
+            return;
+        };
+
+        self.completion_job_rx = None;
+        if job.query == self.query {
+            self.completions = job.completions;
+        }
+    }
+
+    pub(super) fn render_completions(&mut self, ui: &mut egui::Ui) {
+        if self.completions.is_empty() {
+            return;
+        }
+
+        let mut chosen = None;
+        ui.horizontal_wrapped(|ui| {
+            ui.label("Complete:");
+            for completion in &self.completions {
+                if ui.small_button(completion).clicked() {
+                    chosen = Some(completion.clone());
+                }
+            }
+        });
+
+        if let Some(completion) = chosen {
+            self.query = replace_last_word(&self.query, &completion);
+            self.completions.clear();
+            self.focus_search_bar = true;
+        }
+    }
+
+    /// Offers the "did you mean" rewrite of a search that found nothing.
+    pub(super) fn render_no_results(&mut self, ui: &mut egui::Ui) {
+        ui.label(format!("No results for '{}'", self.last_query));
+        let Some(suggestion) = self.suggestion.clone() else {
+            return;
+        };
+        if ui.link(format!("Did you mean: {suggestion}?")).clicked() {
+            self.query = suggestion;
+            self.completions.clear();
+            self.submit_search();
+        }
+    }
+}
+
+/// The word at the end of `query` to complete, without operators or quotes.
+///
+/// Field filters such as `ext:pdf`, words with `~n` or `*` suffixes and words shorter than two characters get no completions.
+fn completion_prefix(query: &str) -> Option<&str> {
+    if query.ends_with(char::is_whitespace) {
+        return None;
+    }
+    let word = query.split_whitespace().last()?;
+    let word = word.trim_start_matches(['-', '(', '"', '=']);
+    let usable = word.chars().count() >= MIN_COMPLETION_PREFIX_CHARS
+        && word.chars().all(char::is_alphanumeric);
+    usable.then_some(word)
+}
+
+/// Replaces the word [`completion_prefix`] found with `completion`, ready for the next word.
+fn replace_last_word(query: &str, completion: &str) -> String {
+    let Some(prefix) = completion_prefix(query) else {
+        return query.to_string();
+    };
+    format!("{}{completion} ", &query[..query.len() - prefix.len()])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn completion_prefix_skips_filters_operators_and_finished_words() {
+        assert_eq!(completion_prefix("distributed arch"), Some("arch"));
+        assert_eq!(completion_prefix("alpha -(\"arch"), Some("arch"));
+        assert_eq!(completion_prefix("=arch"), Some("arch"));
+        assert_eq!(completion_prefix("ext:pdf"), None);
+        assert_eq!(completion_prefix("arch "), None);
+        assert_eq!(completion_prefix("a"), None);
+    }
+
+    #[test]
+    fn replace_last_word_keeps_the_rest_of_the_query() {
+        assert_eq!(
+            replace_last_word("notes AND -arch", "architecture"),
+            "notes AND -architecture "
+        );
+        assert_eq!(replace_last_word("ext:pdf", "pdfs"), "ext:pdf");
+    }
+}
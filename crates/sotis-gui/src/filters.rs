@@ -1,6 +1,7 @@
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::Path;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 #[derive(Clone)]
 pub struct FileTypeFilter {
@@ -51,31 +52,9 @@ pub fn default_file_type_filters() -> Vec<FileTypeFilter> {
     ]
 }
 
-pub fn extension_allowed(path: &Path, allowed_extensions: &[&str]) -> bool {
-    if allowed_extensions.is_empty() {
-        return false;
-    }
-
-    path.extension()
-        .and_then(|ext| ext.to_str())
-        .map(str::to_ascii_lowercase)
-        .is_some_and(|ext| allowed_extensions.contains(&ext.as_str()))
-}
-
-pub fn size_allowed(path: &Path, min_size: Option<u64>, max_size: Option<u64>) -> bool {
-    let Ok(metadata) = fs::metadata(path) else {
-        return true;
-    };
-
-    let size = metadata.len();
-    if min_size.is_some_and(|min| size < min) {
-        return false;
-    }
-    if max_size.is_some_and(|max| size > max) {
-        return false;
-    }
-
-    true
+/// Hits across a file type's extensions, from the search's extension facet.
+pub fn extension_hit_count(counts: &BTreeMap<String, usize>, extensions: &[&str]) -> usize {
+    extensions.iter().filter_map(|ext| counts.get(*ext)).sum()
 }
 
 pub fn parse_megabytes_input(raw: &str) -> Option<u64> {
@@ -113,9 +92,25 @@ pub fn format_unix_hh_mm_utc(unix_secs: u64) -> String {
     format!("{hours:02}:{minutes:02} UTC")
 }
 
+/// Rough remaining time, e.g. "45s", "3m 20s" or "2h 5m".
+pub fn format_duration_short(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    match secs {
+        0..60 => format!("{secs}s"),
+        60..3_600 => format!("{}m {}s", secs / 60, secs % 60),
+        _ => format!("{}h {}m", secs / 3_600, (secs % 3_600) / 60),
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{extension_allowed, format_unix_hh_mm_utc, parse_megabytes_input};
+    use std::collections::BTreeMap;
+
+    use std::time::Duration;
+
+    use super::{
+        extension_hit_count, format_duration_short, format_unix_hh_mm_utc, parse_megabytes_input,
+    };
 
     #[test]
     fn empty_or_invalid_megabytes_input_returns_none() {
@@ -134,17 +129,14 @@ mod tests {
     }
 
     #[test]
-    fn extension_filter_matches_lowercase_extensions() {
-        let allowed = vec!["rs", "md"];
-        assert!(extension_allowed(std::path::Path::new("test.rs"), &allowed));
-        assert!(extension_allowed(
-            std::path::Path::new("README.MD"),
-            &allowed
-        ));
-        assert!(!extension_allowed(
-            std::path::Path::new("book.pdf"),
-            &allowed
-        ));
+    fn extension_hit_count_sums_file_type_extensions() {
+        let counts = BTreeMap::from([
+            ("jpg".to_string(), 2),
+            ("png".to_string(), 3),
+            ("pdf".to_string(), 7),
+        ]);
+        assert_eq!(extension_hit_count(&counts, &["png", "jpg", "jpeg"]), 5);
+        assert_eq!(extension_hit_count(&counts, &["docx"]), 0);
     }
 
     #[test]
@@ -152,4 +144,11 @@ mod tests {
         assert_eq!(format_unix_hh_mm_utc(0), "00:00 UTC");
         assert_eq!(format_unix_hh_mm_utc(3_661), "01:01 UTC");
     }
+
+    #[test]
+    fn format_duration_short_picks_two_largest_units() {
+        assert_eq!(format_duration_short(Duration::from_secs(45)), "45s");
+        assert_eq!(format_duration_short(Duration::from_secs(200)), "3m 20s");
+        assert_eq!(format_duration_short(Duration::from_secs(7_530)), "2h 5m");
+    }
 }
@@ -1,7 +1,30 @@
 use eframe::egui::text::LayoutJob;
 use eframe::egui::{Color32, FontId, TextFormat};
+use std::ops::Range;
+
+use regex::Regex;
+use sotis_core::analysis::{fold, FoldedText};
+use sotis_core::search::{LineMatch, Snippet};
 
 pub fn build_highlight_job(text: &str, query: &str) -> LayoutJob {
+    let mut ranges = Vec::new();
+    for token in query.split_whitespace().filter(|part| !part.is_empty()) {
+        let token_ranges = find_case_insensitive_ranges(text, token);
+        if token_ranges.is_empty() {
+            ranges.extend(find_fuzzy_word_ranges(text, token));
+        } else {
+            ranges.extend(token_ranges);
+        }
+    }
+    build_preview_job(text, ranges)
+}
+
+/// Highlights exact matches of `regex`, for case-sensitive, whole-word and grep searches.
+pub fn build_regex_highlight_job(text: &str, regex: &Regex) -> LayoutJob {
+    build_preview_job(text, find_regex_ranges(text, regex))
+}
+
+fn build_preview_job(text: &str, mut ranges: Vec<(usize, usize)>) -> LayoutJob {
     let mut job = LayoutJob::default();
     let default_format = TextFormat {
         font_id: FontId::monospace(13.0),
@@ -15,21 +38,6 @@ pub fn build_highlight_job(text: &str, query: &str) -> LayoutJob {
         ..Default::default()
     };
 
-    if query.is_empty() {
-        job.append(text, 0.0, default_format);
-        return job;
-    }
-
-    let mut ranges = Vec::new();
-    for token in query.split_whitespace().filter(|part| !part.is_empty()) {
-        let token_ranges = find_case_insensitive_ranges(text, token);
-        if token_ranges.is_empty() {
-            ranges.extend(find_fuzzy_word_ranges(text, token));
-        } else {
-            ranges.extend(token_ranges);
-        }
-    }
-
     ranges.sort_unstable_by_key(|range| range.0);
     ranges.dedup();
     if ranges.is_empty() {
@@ -56,6 +64,55 @@ pub fn build_highlight_job(text: &str, query: &str) -> LayoutJob {
     job
 }
 
+pub fn build_snippet_job(snippet: &Snippet) -> LayoutJob {
+    build_ranges_job(&snippet.text, &snippet.highlights)
+}
+
+/// Renders a grep match as `line:column: text` with the matched spans highlighted.
+pub fn build_line_match_job(line_match: &LineMatch) -> LayoutJob {
+    let prefix = format!("{}:{}: ", line_match.line_number, line_match.column);
+    let text = format!("{prefix}{}", line_match.line);
+    let ranges: Vec<Range<usize>> = line_match
+        .ranges
+        .iter()
+        .map(|range| range.start + prefix.len()..range.end + prefix.len())
+        .collect();
+    build_ranges_job(&text, &ranges)
+}
+
+fn build_ranges_job(text: &str, highlights: &[Range<usize>]) -> LayoutJob {
+    let mut job = LayoutJob::default();
+    let default_format = TextFormat {
+        font_id: FontId::proportional(12.0),
+        color: Color32::GRAY,
+        ..Default::default()
+    };
+    let highlight_format = TextFormat {
+        font_id: FontId::proportional(12.0),
+        color: Color32::BLACK,
+        background: Color32::from_rgb(244, 208, 63),
+        ..Default::default()
+    };
+
+    let mut cursor = 0usize;
+    for range in highlights {
+        if range.start < cursor || range.end > text.len() {
+            continue;
+        }
+        if range.start > cursor {
+            job.append(&text[cursor..range.start], 0.0, default_format.clone());
+        }
+        job.append(&text[range.clone()], 0.0, highlight_format.clone());
+        cursor = range.end;
+    }
+
+    if cursor < text.len() {
+        job.append(&text[cursor..], 0.0, default_format);
+    }
+
+    job
+}
+
 pub fn find_all_match_positions(text: &str, query: &str) -> Vec<usize> {
     let query = query.trim();
     if query.is_empty() {
@@ -81,21 +138,42 @@ pub fn find_all_match_positions(text: &str, query: &str) -> Vec<usize> {
     positions
 }
 
+/// Start offsets of every match of `regex` in `text`.
+pub fn find_regex_match_positions(text: &str, regex: &Regex) -> Vec<usize> {
+    find_regex_ranges(text, regex)
+        .into_iter()
+        .map(|(start, _)| start)
+        .collect()
+}
+
+fn find_regex_ranges(text: &str, regex: &Regex) -> Vec<(usize, usize)> {
+    regex
+        .find_iter(text)
+        .filter(|found| !found.is_empty())
+        .map(|found| (found.start(), found.end()))
+        .collect()
+}
+
 fn find_case_insensitive_ranges(text: &str, token: &str) -> Vec<(usize, usize)> {
     let token = token.trim();
     if token.is_empty() {
         return Vec::new();
     }
 
-    let text_lower = text.to_ascii_lowercase();
-    let token_lower = token.to_ascii_lowercase();
+    // Fold both sides like the index does, then map matches back to the original bytes.
+    let folded_text = FoldedText::new(text);
+    let folded_token = fold(token);
+    if folded_token.is_empty() {
+        return Vec::new();
+    }
 
     let mut ranges = Vec::new();
     let mut cursor = 0usize;
-    while let Some(offset) = text_lower[cursor..].find(&token_lower) {
+    while let Some(offset) = folded_text.as_str()[cursor..].find(&folded_token) {
         let start = cursor + offset;
-        let end = start + token_lower.len();
-        ranges.push((start, end));
+        let end = start + folded_token.len();
+        let original = folded_text.original_range(start..end);
+        ranges.push((original.start, original.end));
         cursor = end;
     }
 
@@ -103,7 +181,7 @@ fn find_case_insensitive_ranges(text: &str, token: &str) -> Vec<(usize, usize)>
 }
 
 fn find_fuzzy_word_ranges(text: &str, token: &str) -> Vec<(usize, usize)> {
-    let token_lower = token.to_ascii_lowercase();
+    let token_lower = fold(token);
     if token_lower.len() < 3 {
         return Vec::new();
     }
@@ -111,7 +189,7 @@ fn find_fuzzy_word_ranges(text: &str, token: &str) -> Vec<(usize, usize)> {
     word_ranges(text)
         .into_iter()
         .filter(|(start, end)| {
-            let word = text[*start..*end].to_ascii_lowercase();
+            let word = fold(&text[*start..*end]);
             fuzzy_token_matches(&word, &token_lower)
         })
         .collect()
@@ -220,7 +298,12 @@ fn merge_ranges(ranges: &[(usize, usize)]) -> Vec<(usize, usize)> {
 mod tests {
     use eframe::egui::Color32;
 
-    use super::{build_highlight_job, find_all_match_positions};
+    use sotis_core::search::{LineMatch, Snippet};
+
+    use super::{
+        build_highlight_job, build_line_match_job, build_regex_highlight_job, build_snippet_job,
+        find_all_match_positions, find_regex_match_positions,
+    };
 
     fn highlighted_fragments(text: &str, query: &str) -> Vec<String> {
         let highlighted_bg = Color32::from_rgb(244, 208, 63);
@@ -238,12 +321,72 @@ mod tests {
         assert_eq!(parts, vec!["Search"]);
     }
 
+    #[test]
+    fn highlights_ignore_case_and_diacritics() {
+        let parts = highlighted_fragments("Herr MÜLLER, l'été", "muller ÉTÉ");
+        assert_eq!(parts, vec!["MÜLLER", "été"]);
+    }
+
     #[test]
     fn highlights_fuzzy_word_match_when_exact_token_is_missing() {
         let parts = highlighted_fragments("A fuzzy engine", "fzzy");
         assert_eq!(parts, vec!["fuzzy"]);
     }
 
+    #[test]
+    fn regex_highlights_respect_case() {
+        let regex = regex::Regex::new(r"\bID\b").expect("compile");
+        let text = "id ID identity";
+        let highlighted_bg = Color32::from_rgb(244, 208, 63);
+        let job = build_regex_highlight_job(text, &regex);
+        let parts: Vec<&str> = job
+            .sections
+            .iter()
+            .filter(|section| section.format.background == highlighted_bg)
+            .map(|section| &job.text[section.byte_range.clone()])
+            .collect();
+        assert_eq!(parts, vec!["ID"]);
+        assert_eq!(find_regex_match_positions(text, &regex), vec![3]);
+    }
+
+    #[test]
+    fn snippet_job_highlights_reported_ranges() {
+        let highlighted_bg = Color32::from_rgb(244, 208, 63);
+        let snippet = Snippet {
+            text: "quarterly revenue forecast".to_string(),
+            offset: 0,
+            highlights: std::iter::once(10..17).collect(),
+        };
+        let job = build_snippet_job(&snippet);
+        let parts: Vec<&str> = job
+            .sections
+            .iter()
+            .filter(|section| section.format.background == highlighted_bg)
+            .map(|section| &job.text[section.byte_range.clone()])
+            .collect();
+        assert_eq!(parts, vec!["revenue"]);
+    }
+
+    #[test]
+    fn line_match_job_prefixes_position_and_highlights_ranges() {
+        let highlighted_bg = Color32::from_rgb(244, 208, 63);
+        let line_match = LineMatch {
+            line_number: 12,
+            column: 7,
+            line: "first ERR-1001: disk".to_string(),
+            ranges: std::iter::once(6..15).collect(),
+        };
+        let job = build_line_match_job(&line_match);
+        let parts: Vec<&str> = job
+            .sections
+            .iter()
+            .filter(|section| section.format.background == highlighted_bg)
+            .map(|section| &job.text[section.byte_range.clone()])
+            .collect();
+        assert!(job.text.starts_with("12:7: first"));
+        assert_eq!(parts, vec!["ERR-1001:"]);
+    }
+
     #[test]
     fn find_all_match_positions_finds_case_insensitive_matches() {
         let text = "line one\nTarget line\ntarget again";